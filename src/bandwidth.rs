@@ -1,7 +1,14 @@
+use crate::display::{self, TableStyle};
+use crate::output::{self, OutputMode, RecordSink};
+use crate::utils::{
+    format_rate, get_default_interface, get_process_names, is_elevated, netctl_dir, parse_bytes,
+    parse_duration, run_command, AppFilter, IfaceFilter, COMMAND_TIMEOUT,
+};
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tabled::{Table, settings::Style};
-use crate::utils::{format_rate, get_process_name};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, tabled::Tabled)]
 struct AppBandwidth {
@@ -15,34 +22,342 @@ struct AppBandwidth {
     total: String,
 }
 
+/// Per-application bandwidth with raw numeric values for machine output.
+#[derive(Debug, Serialize)]
+struct AppBandwidthSample {
+    application: String,
+    download_bytes_per_sec: u64,
+    upload_bytes_per_sec: u64,
+}
+
+#[derive(Debug, Clone, tabled::Tabled)]
+struct IfaceBandwidth {
+    #[tabled(rename = "Interface")]
+    interface: String,
+    #[tabled(rename = "Download")]
+    download: String,
+    #[tabled(rename = "Upload")]
+    upload: String,
+    #[tabled(rename = "Total")]
+    total: String,
+}
+
+/// Per-interface bandwidth with raw numeric values for machine output.
+#[derive(Debug, Serialize)]
+struct IfaceBandwidthSample {
+    interface: String,
+    download_bytes_per_sec: u64,
+    upload_bytes_per_sec: u64,
+}
+
+/// One snapshot, printed by `--json` or streamed to `--output` as NDJSON.
+/// With `--per-interface`, `apps` is empty and `interfaces` holds the rows.
+#[derive(Serialize)]
+struct BandwidthRecord {
+    timestamp: String,
+    interface: String,
+    total_download_bytes_per_sec: u64,
+    total_upload_bytes_per_sec: u64,
+    apps: Vec<AppBandwidthSample>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    interfaces: Vec<IfaceBandwidthSample>,
+}
+
+/// One recorded per-application sample in the history log.
+#[derive(Debug, Serialize, Deserialize)]
+struct HistorySample {
+    timestamp: String,
+    application: String,
+    download_bytes_per_sec: u64,
+    upload_bytes_per_sec: u64,
+    interval_secs: u64,
+}
+
+#[derive(tabled::Tabled)]
+struct AppUsage {
+    #[tabled(rename = "Application")]
+    application: String,
+    #[tabled(rename = "Downloaded")]
+    downloaded: String,
+    #[tabled(rename = "Uploaded")]
+    uploaded: String,
+    #[tabled(rename = "Total")]
+    total: String,
+}
+
+/// Which rate `--sort` ranks rows by.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum SortKey {
+    Download,
+    Upload,
+    Total,
+}
+
+/// Which rows to show: the top N by the sort key after application
+/// filtering, with interface-level rows filtered by name.
+pub struct BandwidthFilter {
+    pub top: Option<usize>,
+    pub apps: AppFilter,
+    pub interfaces: IfaceFilter,
+    pub sort: SortKey,
+    /// Ascending instead of descending, so `--top` picks the quietest rows
+    pub reverse: bool,
+}
+
+/// How `run` samples and presents bandwidth: once or in watch mode, per
+/// application or per interface, as a table or as JSON.
+pub struct BandwidthOptions {
+    pub watch: bool,
+    /// Seconds between samples, and the window each rate is averaged over
+    pub interval: u64,
+    pub output: Option<String>,
+    pub record: bool,
+    pub mode: OutputMode,
+    /// One row per network interface instead of per application
+    pub per_interface: bool,
+    /// Shell command run each time the total rate crosses `--alert`
+    pub alert_cmd: Option<String>,
+}
+
+impl BandwidthFilter {
+    /// Interface totals are labelled "(eth0)"; everything else is an app.
+    fn allows(&self, name: &str) -> bool {
+        if let Some(iface) = name.strip_prefix('(').and_then(|n| n.strip_suffix(')')) {
+            if !self.interfaces.allows(iface) {
+                return false;
+            }
+        }
+        self.apps.allows(name)
+    }
+
+    /// Order rows by the sort key, highest first unless `reverse`. Ties
+    /// fall back to the name so refreshes don't shuffle equal rows.
+    fn sort(&self, entries: &mut [(String, RawBandwidth)]) {
+        let key = |bw: &RawBandwidth| match self.sort {
+            SortKey::Download => bw.bytes_in,
+            SortKey::Upload => bw.bytes_out,
+            SortKey::Total => bw.bytes_in + bw.bytes_out,
+        };
+        entries.sort_by(|a, b| {
+            let order = key(&b.1).cmp(&key(&a.1));
+            let order = if self.reverse { order.reverse() } else { order };
+            order.then_with(|| a.0.cmp(&b.0))
+        });
+    }
+}
+
+/// Byte counts per direction: cumulative counters from `read_bandwidth`,
+/// or bytes per second once two samples are compared by `rates_between`.
 #[derive(Debug, Default, Clone)]
 struct RawBandwidth {
     bytes_in: u64,
     bytes_out: u64,
 }
 
-fn parse_alert_bytes(alert: &str) -> Option<u64> {
-    let alert = alert.trim().to_uppercase();
-    if let Some(num) = alert.strip_suffix("GB") {
-        num.trim().parse::<f64>().ok().map(|n| (n * 1_000_000_000.0) as u64)
-    } else if let Some(num) = alert.strip_suffix("MB") {
-        num.trim().parse::<f64>().ok().map(|n| (n * 1_000_000.0) as u64)
-    } else if let Some(num) = alert.strip_suffix("KB") {
-        num.trim().parse::<f64>().ok().map(|n| (n * 1_000.0) as u64)
-    } else {
-        alert.parse::<u64>().ok()
+/// Per-second rates from two counter samples taken `elapsed` apart. A
+/// counter that went backwards (sockets closed, process restarted) counts
+/// as no traffic rather than a huge wrapped value.
+fn rates_between(
+    previous: &HashMap<String, RawBandwidth>,
+    current: &HashMap<String, RawBandwidth>,
+    elapsed: std::time::Duration,
+) -> HashMap<String, RawBandwidth> {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    current
+        .iter()
+        .map(|(name, now)| {
+            let before = previous.get(name).cloned().unwrap_or_default();
+            let rate = |now: u64, before: u64| (now.saturating_sub(before) as f64 / secs) as u64;
+            (
+                name.clone(),
+                RawBandwidth {
+                    bytes_in: rate(now.bytes_in, before.bytes_in),
+                    bytes_out: rate(now.bytes_out, before.bytes_out),
+                },
+            )
+        })
+        .collect()
+}
+
+/// A long-running `nethogs -t` that refreshes every `window`. A reader
+/// thread hands over each finished "Refreshing:" block, so a refresh waits
+/// one cycle instead of restarting nethogs, whose first cycle has no history
+/// yet and is dropped.
+///
+/// Each block has one tab-separated row per process:
+/// `<program path>/<pid>/<uid>  <sent KB/s>  <received KB/s>`. The sent
+/// column is upload and the received column is download.
+struct Nethogs {
+    child: std::process::Child,
+    blocks: std::sync::mpsc::Receiver<Vec<String>>,
+}
+
+impl Nethogs {
+    /// Start nethogs, or `None` when it isn't installed.
+    fn spawn(window: std::time::Duration) -> Option<Self> {
+        let delay = window.as_secs().max(1).to_string();
+        let mut child = std::process::Command::new("nethogs")
+            .args(["-t", "-d", &delay])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .ok()?;
+        let stdout = child.stdout.take()?;
+        let (tx, blocks) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            // A block is only known to be complete when the next one starts
+            let mut block: Option<Vec<String>> = None;
+            let mut first = true;
+            for line in std::io::BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if line.starts_with("Refreshing:") {
+                    if let Some(done) = block.replace(Vec::new()) {
+                        if !std::mem::take(&mut first) && tx.send(done).is_err() {
+                            return;
+                        }
+                    }
+                } else if let Some(rows) = block.as_mut() {
+                    if !line.is_empty() {
+                        rows.push(line);
+                    }
+                }
+            }
+        });
+        Some(Nethogs { child, blocks })
+    }
+
+    /// Per-process rates from the next block, or `None` when nethogs exited
+    /// (it needs root or CAP_NET_ADMIN to capture) or stopped refreshing.
+    async fn next(&self, window: std::time::Duration) -> Option<HashMap<String, RawBandwidth>> {
+        // The first block takes the dropped cycle plus one more to arrive
+        let timeout = window * 3 + std::time::Duration::from_secs(5);
+        let block = tokio::task::block_in_place(|| self.blocks.recv_timeout(timeout)).ok()?;
+        Some(parse_nethogs(&block).await)
+    }
+}
+
+impl Drop for Nethogs {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Sum the rows of one nethogs block per application.
+async fn parse_nethogs(block: &[String]) -> HashMap<String, RawBandwidth> {
+    let kbps = |v: &str| (v.trim().parse::<f64>().unwrap_or(0.0) * 1024.0) as u64;
+    let mut rows = Vec::new();
+    for line in block {
+        let mut cols = line.split('\t');
+        let (Some(process), Some(sent), Some(received)) = (cols.next(), cols.next(), cols.next())
+        else {
+            continue;
+        };
+        // The program path may itself contain slashes, so split from the right
+        let mut fields = process.rsplitn(3, '/');
+        let (_uid, pid, program) = (fields.next(), fields.next(), fields.next());
+        // "unknown TCP/0/0" collects traffic nethogs couldn't attribute
+        let (Some(pid), Some(program)) = (pid.filter(|&p| p != "0"), program) else {
+            continue;
+        };
+        rows.push((pid, program, kbps(received), kbps(sent)));
+    }
+
+    // Name processes as the other sources do; the path covers ones that exited
+    let pids: Vec<&str> = rows.iter().map(|(pid, ..)| *pid).collect();
+    let mut names = get_process_names(&pids).await;
+    let mut app_bw: HashMap<String, RawBandwidth> = HashMap::new();
+    for (pid, program, bytes_in, bytes_out) in rows {
+        let name = match names.remove(pid) {
+            Some(name) if name != "Unknown" => name,
+            _ => program.rsplit('/').next().unwrap_or(program).to_string(),
+        };
+        let entry = app_bw.entry(name).or_default();
+        entry.bytes_in += bytes_in;
+        entry.bytes_out += bytes_out;
     }
+    app_bw
+}
+
+/// Where `run` gets its per-second rates from: nethogs, which does its own
+/// packet accounting and reports rates directly, or cumulative counters
+/// compared against the previous reading.
+struct Sampler {
+    nethogs: Option<Nethogs>,
+    per_interface: bool,
+    previous: Option<(std::time::Instant, HashMap<String, RawBandwidth>)>,
 }
 
+impl Sampler {
+    async fn new(per_interface: bool, window: std::time::Duration) -> Self {
+        // nethogs is the most accurate per-process source on Linux
+        let nethogs = if cfg!(target_os = "linux") && !per_interface {
+            Nethogs::spawn(window)
+        } else {
+            None
+        };
+        let mut sampler = Sampler {
+            per_interface,
+            previous: None,
+            nethogs,
+        };
+        if sampler.nethogs.is_none() {
+            sampler.take_baseline().await;
+        }
+        sampler
+    }
+
+    async fn read_counters(&self) -> HashMap<String, RawBandwidth> {
+        if self.per_interface {
+            read_interface_counters().await
+        } else {
+            read_bandwidth().await
+        }
+    }
+
+    async fn take_baseline(&mut self) {
+        let counters = self.read_counters().await;
+        self.previous = Some((std::time::Instant::now(), counters));
+    }
+
+    /// Wait out one `window` and return the rates over it with the time
+    /// they actually cover.
+    async fn next(
+        &mut self,
+        window: std::time::Duration,
+    ) -> (HashMap<String, RawBandwidth>, std::time::Duration) {
+        if let Some(nethogs) = &self.nethogs {
+            if let Some(rates) = nethogs.next(window).await {
+                return (rates, window);
+            }
+            log::debug!("nethogs unavailable, falling back to counters");
+            self.nethogs = None;
+            self.take_baseline().await;
+        }
+
+        tokio::time::sleep(window).await;
+        let now = std::time::Instant::now();
+        let counters = self.read_counters().await;
+        let (then, before) = self.previous.take().unwrap_or((now, HashMap::new()));
+        let rates = rates_between(&before, &counters, now - then);
+        self.previous = Some((now, counters));
+        (rates, now - then)
+    }
+}
 
-/// Read per-process bandwidth from /proc/net or platform-specific tools.
-fn read_bandwidth() -> HashMap<String, RawBandwidth> {
+/// Read cumulative per-process byte counters from nettop (macOS) or
+/// `ss -ti` (Linux), falling back to per-interface totals from /proc/net/dev.
+async fn read_bandwidth() -> HashMap<String, RawBandwidth> {
     let mut app_bw: HashMap<String, RawBandwidth> = HashMap::new();
 
     // Try nettop on macOS
-    if let Ok(output) = std::process::Command::new("nettop")
-        .args(["-P", "-L", "1", "-J", "bytes_in,bytes_out", "-x"])
-        .output()
+    if let Ok(output) = run_command(
+        "nettop",
+        &["-P", "-L", "1", "-J", "bytes_in,bytes_out", "-x"],
+        COMMAND_TIMEOUT,
+    )
+    .await
     {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines().skip(1) {
@@ -58,58 +373,51 @@ fn read_bandwidth() -> HashMap<String, RawBandwidth> {
         }
     }
 
-    // Fallback: on Linux, read from /proc/net/dev and correlate with process info
+    // Fallback: per-socket byte counters from ss, summed per owning process
     if app_bw.is_empty() {
-        // Use ss + /proc approach: get per-socket stats
-        if let Ok(output) = std::process::Command::new("ss")
-            .args(["-tunap"])
-            .output()
-        {
+        log::debug!("nettop gave no per-app data, falling back to ss");
+        // Per-socket TCP byte counters: each socket line is followed by an
+        // indented detail line with e.g. "bytes_acked:1200 bytes_received:5300"
+        if let Ok(output) = run_command("ss", &["-tinp"], COMMAND_TIMEOUT).await {
             let stdout = String::from_utf8_lossy(&output.stdout);
+            let mut pid: Option<String> = None;
+            let mut sockets = Vec::new();
             for line in stdout.lines().skip(1) {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() < 6 {
-                    continue;
-                }
-                let recv_q: u64 = parts[2].parse().unwrap_or(0);
-                let send_q: u64 = parts[3].parse().unwrap_or(0);
-
-                let pid_info = parts.get(6).unwrap_or(&"");
-                let pid = if pid_info.contains("pid=") {
-                    pid_info
+                if !line.starts_with(char::is_whitespace) {
+                    pid = line
                         .split("pid=")
                         .nth(1)
                         .and_then(|s| s.split(',').next())
-                        .unwrap_or("-")
-                        .to_string()
-                } else {
+                        .map(|s| s.to_string());
                     continue;
+                }
+                // Sockets of other users' processes have no pid without root
+                let Some(pid) = pid.take() else {
+                    continue;
+                };
+                let counter = |key: &str| {
+                    line.split_whitespace()
+                        .find_map(|t| t.strip_prefix(key))
+                        .and_then(|v| v.parse::<u64>().ok())
+                        .unwrap_or(0)
                 };
+                sockets.push((pid, counter("bytes_received:"), counter("bytes_acked:")));
+            }
 
-                let app_name = get_process_name(&pid);
-                let entry = app_bw.entry(app_name).or_default();
-                entry.bytes_in += recv_q;
-                entry.bytes_out += send_q;
+            let pids: Vec<&str> = sockets.iter().map(|(pid, ..)| pid.as_str()).collect();
+            let names = get_process_names(&pids).await;
+            for (pid, bytes_in, bytes_out) in &sockets {
+                let entry = app_bw.entry(names[pid].clone()).or_default();
+                entry.bytes_in += bytes_in;
+                entry.bytes_out += bytes_out;
             }
         }
 
         // Also try to get interface-level totals
         if app_bw.is_empty() {
-            if let Ok(content) = std::fs::read_to_string("/proc/net/dev") {
-                for line in content.lines().skip(2) {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 10 {
-                        let iface = parts[0].trim_end_matches(':');
-                        if iface == "lo" {
-                            continue;
-                        }
-                        let bytes_in: u64 = parts[1].parse().unwrap_or(0);
-                        let bytes_out: u64 = parts[9].parse().unwrap_or(0);
-                        let entry = app_bw.entry(format!("({})", iface)).or_default();
-                        entry.bytes_in += bytes_in;
-                        entry.bytes_out += bytes_out;
-                    }
-                }
+            log::debug!("ss gave no per-socket data, falling back to /proc/net/dev totals");
+            for (iface, bw) in read_interface_counters().await {
+                app_bw.insert(format!("({})", iface), bw);
             }
         }
     }
@@ -117,110 +425,348 @@ fn read_bandwidth() -> HashMap<String, RawBandwidth> {
     app_bw
 }
 
-fn get_default_interface() -> String {
-    // macOS
-    if let Ok(output) = std::process::Command::new("route")
-        .args(["-n", "get", "default"])
-        .output()
-    {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        for line in stdout.lines() {
-            if line.contains("interface:") {
-                return line.split(':').nth(1).unwrap_or("unknown").trim().to_string();
+/// Read cumulative byte counters per network interface from /proc/net/dev
+/// (Linux) or `netstat -ibn` (macOS). These count all traffic, so they
+/// work without the permissions per-process data needs.
+async fn read_interface_counters() -> HashMap<String, RawBandwidth> {
+    let mut counters: HashMap<String, RawBandwidth> = HashMap::new();
+
+    // Linux
+    if let Ok(content) = std::fs::read_to_string("/proc/net/dev") {
+        for line in content.lines().skip(2) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 10 {
+                let iface = parts[0].trim_end_matches(':');
+                let entry = counters.entry(iface.to_string()).or_default();
+                entry.bytes_in += parts[1].parse().unwrap_or(0);
+                entry.bytes_out += parts[9].parse().unwrap_or(0);
             }
         }
+        return counters;
     }
-    // Linux
-    if let Ok(output) = std::process::Command::new("ip")
-        .args(["route", "show", "default"])
-        .output()
-    {
+
+    // macOS: one row per address; the "<Link#N>" row carries the totals.
+    // Count columns from the end since the Address column may be blank.
+    if let Ok(output) = run_command("netstat", &["-ibn"], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
-        if let Some(line) = stdout.lines().next() {
+        for line in stdout.lines().skip(1) {
             let parts: Vec<&str> = line.split_whitespace().collect();
-            if let Some(idx) = parts.iter().position(|&p| p == "dev") {
-                if let Some(iface) = parts.get(idx + 1) {
-                    return iface.to_string();
-                }
+            if parts.len() < 9 || !parts[2].starts_with("<Link#") {
+                continue;
             }
+            let n = parts.len();
+            let entry = counters.entry(parts[0].to_string()).or_default();
+            entry.bytes_in += parts[n - 5].parse().unwrap_or(0);
+            entry.bytes_out += parts[n - 2].parse().unwrap_or(0);
+        }
+    }
+
+    counters
+}
+
+/// Run the `--alert-cmd` hook through `sh -c` without waiting for it, so a
+/// slow notifier can't stall sampling. The rate and threshold (bytes/sec)
+/// are passed as NETCTL_RATE and NETCTL_THRESHOLD.
+fn run_alert_cmd(command: &str, rate: u64, threshold: u64, quiet: bool) {
+    let mut cmd = std::process::Command::new("sh");
+    cmd.args(["-c", command])
+        .env("NETCTL_RATE", rate.to_string())
+        .env("NETCTL_THRESHOLD", threshold.to_string())
+        .stdin(std::process::Stdio::null());
+    // Keep stdout clean when it carries JSON or NDJSON records
+    if quiet {
+        cmd.stdout(std::process::Stdio::null());
+    }
+    log::debug!("alert hook: {} (NETCTL_RATE={})", command, rate);
+    match cmd.spawn() {
+        // Reap the child in the background so it doesn't linger as a zombie
+        Ok(mut child) => {
+            std::thread::spawn(move || {
+                if let Ok(status) = child.wait() {
+                    if !status.success() {
+                        log::warn!("alert command exited with {}", status);
+                    }
+                }
+            });
+        }
+        Err(e) => eprintln!("{}", format!("Failed to run alert command: {}", e).yellow()),
+    }
+}
+
+/// Return the path to the bandwidth history log (~/.netctl/bandwidth.jsonl).
+fn history_path() -> PathBuf {
+    netctl_dir().join("bandwidth.jsonl")
+}
+
+/// Append one sample per active application to the history log.
+fn record_samples(
+    entries: &[(String, RawBandwidth)],
+    interval_secs: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path())?;
+    let timestamp = output::timestamp();
+    for (name, bw) in entries {
+        if bw.bytes_in == 0 && bw.bytes_out == 0 {
+            continue;
+        }
+        let sample = HistorySample {
+            timestamp: timestamp.clone(),
+            application: name.clone(),
+            download_bytes_per_sec: bw.bytes_in,
+            upload_bytes_per_sec: bw.bytes_out,
+            interval_secs,
+        };
+        writeln!(file, "{}", serde_json::to_string(&sample)?)?;
+    }
+    Ok(())
+}
+
+/// Aggregate recorded samples over a period into top consumers.
+pub fn report(
+    last: &str,
+    filter: &BandwidthFilter,
+    style: TableStyle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let period = parse_duration(last)
+        .ok_or_else(|| format!("Invalid period '{}' (use e.g. 24h, 7d, 2w)", last))?;
+    let since = chrono::Local::now() - period;
+
+    let path = history_path();
+    let file = std::fs::File::open(&path).map_err(|_| {
+        format!(
+            "No bandwidth history at {} (record some with --watch --record)",
+            path.display()
+        )
+    })?;
+
+    let mut usage: HashMap<String, RawBandwidth> = HashMap::new();
+    let mut samples = 0;
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        let Ok(sample) = serde_json::from_str::<HistorySample>(&line) else {
+            continue;
+        };
+        let Ok(ts) = chrono::DateTime::parse_from_rfc3339(&sample.timestamp) else {
+            continue;
+        };
+        if ts < since {
+            continue;
+        }
+        if !filter.allows(&sample.application) {
+            continue;
         }
+        let entry = usage.entry(sample.application).or_default();
+        entry.bytes_in += sample.download_bytes_per_sec * sample.interval_secs;
+        entry.bytes_out += sample.upload_bytes_per_sec * sample.interval_secs;
+        samples += 1;
+    }
+
+    let mut entries: Vec<(String, RawBandwidth)> = usage.into_iter().collect();
+    filter.sort(&mut entries);
+    entries.truncate(filter.top.unwrap_or(10));
+
+    println!();
+    println!("{} (last {}):", "Bandwidth Usage Report".bold(), last);
+    println!();
+
+    if entries.is_empty() {
+        println!("  No samples recorded in this period.");
+    } else {
+        let rows: Vec<AppUsage> = entries
+            .iter()
+            .map(|(name, bw)| AppUsage {
+                application: name.clone(),
+                downloaded: display::format_bytes(bw.bytes_in),
+                uploaded: display::format_bytes(bw.bytes_out),
+                total: display::format_bytes(bw.bytes_in + bw.bytes_out),
+            })
+            .collect();
+        let table = display::render_table(&rows, style, &["Total", "Uploaded"]);
+        println!("{}", table);
+        println!();
+        println!(
+            "{}",
+            format!("Based on {} samples from {}", samples, path.display()).dimmed()
+        );
     }
-    "unknown".to_string()
+
+    println!();
+    Ok(())
 }
 
 pub async fn run(
-    top: Option<usize>,
-    app_filter: Option<String>,
+    filter: BandwidthFilter,
     alert: Option<String>,
-    watch: bool,
+    opts: BandwidthOptions,
+    style: TableStyle,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let alert_bytes = alert.as_deref().and_then(parse_alert_bytes);
+    let BandwidthOptions {
+        watch,
+        interval,
+        output: output_path,
+        record,
+        mode,
+        per_interface,
+        alert_cmd,
+    } = opts;
+    if interval == 0 {
+        return Err("--interval must be at least 1 second".into());
+    }
+    // Both the rate window and the watch-mode refresh period
+    let window = std::time::Duration::from_secs(interval);
+
+    let alert_bytes = alert.as_deref().and_then(parse_bytes);
+    // Whether the last sample was over the threshold, so the hook fires once
+    // per crossing rather than on every refresh
+    let mut alerting = false;
+    let mut sink = output_path.as_deref().map(RecordSink::open).transpose()?;
+    let quiet = mode.is_json() || output::is_stdout(output_path.as_deref());
+    let mut sampler = Sampler::new(per_interface, window).await;
+
+    if !quiet {
+        println!();
+        println!(
+            "{}",
+            format!("Sampling bandwidth over {}s...", interval).dimmed()
+        );
+    }
 
     loop {
-        if watch {
-            print!("\x1B[2J\x1B[H");
+        let (bw_data, elapsed) = sampler.next(window).await;
+
+        if watch && !quiet {
+            display::begin_frame();
         }
 
-        let bw_data = read_bandwidth();
         let mut entries: Vec<(String, RawBandwidth)> = bw_data.into_iter().collect();
 
-        // Apply app filter
-        if let Some(ref app) = app_filter {
-            let app_lower = app.to_lowercase();
-            entries.retain(|(name, _)| name.to_lowercase().contains(&app_lower));
+        // Apply app and interface filters
+        if per_interface {
+            entries.retain(|(name, _)| filter.interfaces.allows(name));
+        } else {
+            entries.retain(|(name, _)| filter.allows(name));
         }
 
-        // Sort by total bandwidth descending
-        entries.sort_by(|a, b| {
-            let total_a = a.1.bytes_in + a.1.bytes_out;
-            let total_b = b.1.bytes_in + b.1.bytes_out;
-            total_b.cmp(&total_a)
-        });
+        filter.sort(&mut entries);
 
-        let limit = top.unwrap_or(10);
+        let limit = filter.top.unwrap_or(10);
         let total_down: u64 = entries.iter().map(|(_, b)| b.bytes_in).sum();
         let total_up: u64 = entries.iter().map(|(_, b)| b.bytes_out).sum();
+        let iface = get_default_interface().await;
 
-        // Group remaining as "Other"
-        let (shown, rest) = if entries.len() > limit {
-            let (s, r) = entries.split_at(limit);
-            (s.to_vec(), Some(r.to_vec()))
-        } else {
-            (entries, None)
-        };
+        let over_threshold = alert_bytes.filter(|&t| total_down + total_up > t);
+        if let (Some(threshold), Some(command), false) =
+            (over_threshold, alert_cmd.as_deref(), alerting)
+        {
+            run_alert_cmd(command, total_down + total_up, threshold, quiet);
+        }
+        alerting = over_threshold.is_some();
 
-        let mut display_rows: Vec<AppBandwidth> = shown
-            .iter()
-            .map(|(name, bw)| AppBandwidth {
-                application: name.clone(),
-                download: format_rate(bw.bytes_in as f64),
-                upload: format_rate(bw.bytes_out as f64),
-                total: format_rate((bw.bytes_in + bw.bytes_out) as f64),
-            })
-            .collect();
+        if sink.is_some() || mode.is_json() {
+            let (apps, interfaces) = if per_interface {
+                let rows = entries
+                    .iter()
+                    .map(|(name, bw)| IfaceBandwidthSample {
+                        interface: name.clone(),
+                        download_bytes_per_sec: bw.bytes_in,
+                        upload_bytes_per_sec: bw.bytes_out,
+                    })
+                    .collect();
+                (Vec::new(), rows)
+            } else {
+                let rows = entries
+                    .iter()
+                    .map(|(name, bw)| AppBandwidthSample {
+                        application: name.clone(),
+                        download_bytes_per_sec: bw.bytes_in,
+                        upload_bytes_per_sec: bw.bytes_out,
+                    })
+                    .collect();
+                (rows, Vec::new())
+            };
+            let record = BandwidthRecord {
+                timestamp: output::timestamp(),
+                interface: iface.clone(),
+                total_download_bytes_per_sec: total_down,
+                total_upload_bytes_per_sec: total_up,
+                apps,
+                interfaces,
+            };
+            if mode.is_json() {
+                println!("{}", output::to_json(&record)?);
+            } else if let Some(ref mut sink) = sink {
+                if !sink.write(&record)? {
+                    eprintln!("{}", "Output reader disconnected, stopping.".dimmed());
+                    break;
+                }
+            }
+        }
 
-        if let Some(rest) = rest {
-            let rest_in: u64 = rest.iter().map(|(_, b)| b.bytes_in).sum();
-            let rest_out: u64 = rest.iter().map(|(_, b)| b.bytes_out).sum();
-            display_rows.push(AppBandwidth {
-                application: format!("Other ({} apps)", rest.len()),
-                download: format_rate(rest_in as f64),
-                upload: format_rate(rest_out as f64),
-                total: format_rate((rest_in + rest_out) as f64),
-            });
+        if record {
+            record_samples(&entries, elapsed.as_secs_f64().round().max(1.0) as u64)?;
         }
 
+        // With `--json` or `--output -` stdout carries only the records
+        if quiet {
+            if !watch {
+                return Ok(());
+            }
+            continue;
+        }
+
+        // Group remaining as "Other"
+        let rows: Vec<(String, RawBandwidth)> = if entries.len() > limit {
+            let (shown, rest) = entries.split_at(limit);
+            let other = RawBandwidth {
+                bytes_in: rest.iter().map(|(_, b)| b.bytes_in).sum(),
+                bytes_out: rest.iter().map(|(_, b)| b.bytes_out).sum(),
+            };
+            let kind = if per_interface { "interfaces" } else { "apps" };
+            let mut rows = shown.to_vec();
+            rows.push((format!("Other ({} {})", rest.len(), kind), other));
+            rows
+        } else {
+            entries
+        };
+
         println!();
-        println!("{}", "Real-time Bandwidth Usage:".bold());
+        if per_interface {
+            println!("{}", "Real-time Bandwidth by Interface:".bold());
+        } else {
+            println!("{}", "Real-time Bandwidth Usage:".bold());
+        }
         println!();
 
-        if display_rows.is_empty() {
+        if rows.is_empty() {
             println!("  No bandwidth data available.");
-            println!("  (This may require elevated privileges on some systems)");
         } else {
-            let table = Table::new(&display_rows)
-                .with(Style::modern())
-                .to_string();
+            let drop_order = ["Total", "Upload"];
+            let table = if per_interface {
+                let rows: Vec<IfaceBandwidth> = rows
+                    .iter()
+                    .map(|(name, bw)| IfaceBandwidth {
+                        interface: name.clone(),
+                        download: format_rate(bw.bytes_in as f64),
+                        upload: format_rate(bw.bytes_out as f64),
+                        total: format_rate((bw.bytes_in + bw.bytes_out) as f64),
+                    })
+                    .collect();
+                display::render_table(&rows, style, &drop_order)
+            } else {
+                let rows: Vec<AppBandwidth> = rows
+                    .iter()
+                    .map(|(name, bw)| AppBandwidth {
+                        application: name.clone(),
+                        download: format_rate(bw.bytes_in as f64),
+                        upload: format_rate(bw.bytes_out as f64),
+                        total: format_rate((bw.bytes_in + bw.bytes_out) as f64),
+                    })
+                    .collect();
+                display::render_table(&rows, style, &drop_order)
+            };
             println!("{}", table);
             println!();
             println!(
@@ -232,34 +778,43 @@ pub async fn run(
             );
         }
 
-        let iface = get_default_interface();
-        println!();
-        println!("Network Interface: {}", iface.cyan());
+        if !per_interface {
+            println!();
+            println!("Network Interface: {}", iface.cyan());
 
-        // Alert check
-        if let Some(threshold) = alert_bytes {
-            let total = total_down + total_up;
-            if total > threshold {
+            // nettop and ss only see the current user's sockets without root
+            if !is_elevated() {
                 println!();
-                println!(
-                    "{}",
-                    format!(
-                        "  ALERT: Bandwidth usage ({}) exceeds threshold!",
-                        format_rate(total as f64)
-                    )
-                    .red()
-                    .bold()
+                display::print_unprivileged_note(
+                    "results may be incomplete (other users' sockets are hidden)",
                 );
             }
         }
 
+        // Alert check
+        if over_threshold.is_some() {
+            println!();
+            println!(
+                "{}",
+                format!(
+                    "  ALERT: Bandwidth usage ({}) exceeds threshold!",
+                    format_rate((total_down + total_up) as f64)
+                )
+                .red()
+                .bold()
+            );
+        }
+
         if !watch {
             break;
         }
 
         println!();
-        println!("{}", "Refreshing every 2s... (Ctrl+C to stop)".dimmed());
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        println!(
+            "{}",
+            format!("Refreshing every {}s... (Ctrl+C to stop)", interval).dimmed()
+        );
+        display::end_frame();
     }
 
     println!();
@@ -1,4 +1,9 @@
+use crate::display;
+use crate::dns;
+use crate::utils::{catch_interrupt, is_elevated, parse_duration, sleep_unless_interrupted};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike};
 use colored::Colorize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -6,51 +11,386 @@ const HOSTS_PATH: &str = "/etc/hosts";
 const BACKUP_PATH: &str = "/etc/hosts.netctl.bak";
 const MARKER_BEGIN: &str = "# >>> netctl block begin";
 const MARKER_END: &str = "# <<< netctl block end";
+/// Where root keeps block state; blocks need root, so this is the usual one.
+const SYSTEM_STATE_DIR: &str = "/var/lib/netctl";
+/// Where block state lived in older versions.
+const LEGACY_STATE_PATH: &str = "/tmp/netctl_blocks.json";
+/// Hostnames blocked alongside each domain unless `--subdomains` says
+/// otherwise, since /etc/hosts has no wildcards: web, mobile, and the API
+/// and CDN hosts apps load content from.
+const DEFAULT_SUBDOMAINS: &[&str] = &["www", "m", "mobile", "app", "api", "cdn"];
+/// How long finished blocks are kept for `--stats`, which reports this week.
+const HISTORY_DAYS: i64 = 28;
+/// Schedule days in storage order: a schedule's `days` index into this.
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
 
 use serde::{Deserialize, Serialize};
 
-/// Return the path to the block state file (~/.netctl/blocks.json).
-/// Creates the ~/.netctl directory if it does not exist.
+/// Built-in lists of distracting domains for `--preset`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Preset {
+    Social,
+    News,
+    Video,
+    Shopping,
+}
+
+impl Preset {
+    fn domains(self) -> &'static [&'static str] {
+        match self {
+            Preset::Social => &[
+                "twitter.com",
+                "x.com",
+                "facebook.com",
+                "instagram.com",
+                "reddit.com",
+                "tiktok.com",
+                "linkedin.com",
+            ],
+            Preset::News => &[
+                "news.ycombinator.com",
+                "cnn.com",
+                "bbc.com",
+                "nytimes.com",
+                "theguardian.com",
+            ],
+            Preset::Video => &["youtube.com", "netflix.com", "twitch.tv", "vimeo.com"],
+            Preset::Shopping => &["amazon.com", "ebay.com", "aliexpress.com", "etsy.com"],
+        }
+    }
+}
+
+/// Print every preset and the domains it blocks, for `--list-categories`.
+pub fn list_presets() -> Result<(), Box<dyn std::error::Error>> {
+    println!();
+    println!("{}", "Block categories".bold());
+    println!();
+    for preset in <Preset as clap::ValueEnum>::value_variants() {
+        let name = clap::ValueEnum::to_possible_value(preset)
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default();
+        println!("  {:<10} {}", name.cyan(), preset.domains().join(", "));
+    }
+    println!();
+    println!(
+        "{}",
+        "Block one with: netctl block --add-category <name>".dimmed()
+    );
+    println!();
+    Ok(())
+}
+
+/// Combine `--add` domains with a preset's into one comma-separated list.
+pub fn merge_preset(add: Option<String>, preset: Option<Preset>) -> Option<String> {
+    let Some(preset) = preset else {
+        return add;
+    };
+    let mut domains: Vec<String> = add
+        .iter()
+        .flat_map(|a| a.split(','))
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    domains.extend(preset.domains().iter().map(|d| d.to_string()));
+    Some(domains.join(","))
+}
+
+/// Return the path to the block state file: block.state_path from the
+/// config if set, /var/lib/netctl/blocks.json as root, otherwise under
+/// `$XDG_STATE_HOME` (~/.local/state) so it survives reboots.
 fn block_state_path() -> PathBuf {
-    let base = dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."));
-    let dir = base.join(".netctl");
-    if !dir.exists() {
-        let _ = fs::create_dir_all(&dir);
+    if let Some(path) = &crate::config::get().block_state_path {
+        return path.clone();
     }
+    let dir = if is_elevated() {
+        PathBuf::from(SYSTEM_STATE_DIR)
+    } else {
+        dirs::state_dir()
+            .or_else(|| dirs::home_dir().map(|home| home.join(".local/state")))
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("netctl")
+    };
     dir.join("blocks.json")
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Earlier homes of the state file, read when the current one doesn't exist
+/// yet. Unprivileged runs also see the state root saved, so `--list` shows
+/// what is actually blocked.
+fn fallback_state_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if !is_elevated() {
+        paths.push(Path::new(SYSTEM_STATE_DIR).join("blocks.json"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".netctl").join("blocks.json"));
+    }
+    paths.push(PathBuf::from(LEGACY_STATE_PATH));
+    paths
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct BlockState {
     domains: Vec<BlockedDomain>,
     enabled: bool,
+    #[serde(default)]
+    schedules: Vec<Schedule>,
+    /// Hostnames never written to /etc/hosts, whatever list or category
+    /// would block them
+    #[serde(default)]
+    allowed: Vec<String>,
+    /// Finished blocks, for `--stats`
+    #[serde(default)]
+    history: Vec<BlockedPeriod>,
+    /// All-time seconds each domain has been blocked, not counting blocks
+    /// still in progress
+    #[serde(default)]
+    blocked_secs: BTreeMap<String, i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct BlockedDomain {
     domain: String,
     expires_at: Option<String>, // ISO 8601 timestamp
+    /// Labels blocked under `domain` as well, e.g. "www" for www.domain
+    #[serde(default = "legacy_subdomains")]
+    subdomains: Vec<String>,
+    /// When the current stretch of blocking began (ISO 8601); `None` while
+    /// blocking is disabled
+    #[serde(default)]
+    blocked_since: Option<String>,
+}
+
+/// A stretch of time one domain was blocked.
+#[derive(Debug, Serialize, Deserialize)]
+struct BlockedPeriod {
+    domain: String,
+    start: String, // ISO 8601 timestamps
+    end: String,
+}
+
+/// State saved before subdomains were configurable blocked only www.
+fn legacy_subdomains() -> Vec<String> {
+    vec!["www".to_string()]
+}
+
+/// Parse `--subdomains` ("m,app,api"), or the default list without it. An
+/// empty value blocks only the domains themselves.
+pub fn parse_subdomains(arg: Option<&str>) -> Vec<String> {
+    let Some(arg) = arg else {
+        return DEFAULT_SUBDOMAINS.iter().map(|s| s.to_string()).collect();
+    };
+    let mut subdomains: Vec<String> = Vec::new();
+    for label in arg.split(',') {
+        let label = label.trim().trim_end_matches('.');
+        if !label.is_empty() && !subdomains.iter().any(|s| s == label) {
+            subdomains.push(label.to_string());
+        }
+    }
+    subdomains
+}
+
+/// A recurring window, e.g. Mon-Fri 09:00-17:00, during which `domains`
+/// are blocked. A window that ends before it starts runs past midnight and
+/// belongs to the day it starts on.
+#[derive(Debug, Serialize, Deserialize)]
+struct Schedule {
+    /// Days the window starts on, 0 = Monday
+    days: Vec<u8>,
+    /// Local times as "HH:MM"
+    start: String,
+    end: String,
+    domains: Vec<String>,
+    subdomains: Vec<String>,
+}
+
+/// A day name ("mon", "Monday") as an index into `WEEKDAYS`.
+fn parse_weekday(name: &str) -> Option<u8> {
+    let name = name.to_ascii_lowercase();
+    if name.len() < 3 {
+        return None;
+    }
+    WEEKDAYS
+        .iter()
+        .position(|day| day.starts_with(&name))
+        .map(|i| i as u8)
+}
+
+/// Parse the days half of `--schedule`: "Mon-Fri", "Sat,Sun",
+/// "Mon,Wed-Fri", "weekdays", "weekends", or "daily". Ranges may wrap
+/// around the weekend ("Fri-Mon").
+fn parse_days(spec: &str) -> Result<Vec<u8>, String> {
+    let mut days = Vec::new();
+    for part in spec.split(',') {
+        let part = part.trim();
+        let range = match part.to_ascii_lowercase().as_str() {
+            "daily" | "everyday" | "*" => (0, 6),
+            "weekdays" => (0, 4),
+            "weekends" => (5, 6),
+            _ => {
+                let (from, to) = part.split_once('-').unwrap_or((part, part));
+                let day = |name: &str| {
+                    parse_weekday(name.trim()).ok_or_else(|| format!("unknown day '{}'", name))
+                };
+                (day(from)?, day(to)?)
+            }
+        };
+        let mut day = range.0;
+        loop {
+            if !days.contains(&day) {
+                days.push(day);
+            }
+            if day == range.1 {
+                break;
+            }
+            day = (day + 1) % 7;
+        }
+    }
+    days.sort();
+    Ok(days)
+}
+
+/// Parse `--schedule` ("Mon-Fri 09:00-17:00", or just the times for every
+/// day) into a schedule blocking `domains`.
+fn parse_schedule(
+    spec: &str,
+    domains: Vec<String>,
+    subdomains: Vec<String>,
+) -> Result<Schedule, Box<dyn std::error::Error>> {
+    let invalid = |why: String| format!("invalid --schedule '{}': {}", spec, why);
+    let parts: Vec<&str> = spec.split_whitespace().collect();
+    let (days, times) = match parts[..] {
+        [times] => ((0..7).collect(), times),
+        [days, times] => (parse_days(days).map_err(invalid)?, times),
+        _ => return Err(invalid("expected \"<days> HH:MM-HH:MM\"".to_string()).into()),
+    };
+    let (start, end) = times
+        .split_once('-')
+        .ok_or_else(|| invalid(format!("'{}' is not a HH:MM-HH:MM range", times)))?;
+    let time = |t: &str| {
+        NaiveTime::parse_from_str(t, "%H:%M")
+            .map(|t| t.format("%H:%M").to_string())
+            .map_err(|_| invalid(format!("'{}' is not a HH:MM time", t)))
+    };
+    let (start, end) = (time(start)?, time(end)?);
+    if start == end {
+        return Err(invalid("the window starts and ends at the same time".to_string()).into());
+    }
+    Ok(Schedule {
+        days,
+        start,
+        end,
+        domains,
+        subdomains,
+    })
+}
+
+impl Schedule {
+    fn times(&self) -> Option<(NaiveTime, NaiveTime)> {
+        let parse = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").ok();
+        Some((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn is_active(&self, at: NaiveDateTime) -> bool {
+        let Some((start, end)) = self.times() else {
+            return false;
+        };
+        let today = at.weekday().num_days_from_monday() as u8;
+        let yesterday = (today + 6) % 7;
+        let time = at.time();
+        if start < end {
+            self.days.contains(&today) && start <= time && time < end
+        } else {
+            (self.days.contains(&today) && time >= start)
+                || (self.days.contains(&yesterday) && time < end)
+        }
+    }
+
+    /// When the window next opens or closes after `now`. Windows start and
+    /// end on the minute, so stepping by minutes over a week finds it.
+    fn next_transition(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let active = self.is_active(now);
+        let mut at = now.with_second(0)?.with_nanosecond(0)?;
+        for _ in 0..8 * 24 * 60 {
+            at += chrono::Duration::minutes(1);
+            if self.is_active(at) != active {
+                return Some(at);
+            }
+        }
+        None
+    }
+
+    /// "Mon-Fri 09:00-17:00", the way `--schedule` takes it.
+    fn describe(&self) -> String {
+        let name = |day: u8| {
+            let name = WEEKDAYS[day as usize % 7];
+            format!("{}{}", name[..1].to_uppercase(), &name[1..3])
+        };
+        let days = if self.days.len() == 7 {
+            "Daily".to_string()
+        } else {
+            // Collapse consecutive days into ranges
+            let mut runs: Vec<(u8, u8)> = Vec::new();
+            for &day in &self.days {
+                match runs.last_mut() {
+                    Some(run) if run.1 + 1 == day => run.1 = day,
+                    _ => runs.push((day, day)),
+                }
+            }
+            // ...including across the weekend, so Fri-Mon reads that way
+            if runs.len() > 1 && runs[0].0 == 0 && runs[runs.len() - 1].1 == 6 {
+                let first = runs.remove(0);
+                runs.last_mut().unwrap().1 = first.1;
+            }
+            runs.iter()
+                .map(|&(from, to)| match (to + 7 - from) % 7 {
+                    0 => name(from),
+                    1 => format!("{},{}", name(from), name(to)),
+                    _ => format!("{}-{}", name(from), name(to)),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!("{} {}-{}", days, self.start, self.end)
+    }
 }
 
 impl BlockState {
     fn load() -> Self {
         let path = block_state_path();
-        if let Ok(data) = fs::read_to_string(&path) {
-            serde_json::from_str(&data).unwrap_or(BlockState {
-                domains: Vec::new(),
-                enabled: false,
-            })
+        let data = fs::read_to_string(&path).or_else(|e| {
+            fallback_state_paths()
+                .iter()
+                .find_map(|old| {
+                    let data = fs::read_to_string(old).ok()?;
+                    log::debug!(
+                        "no block state at {}, migrating {}",
+                        path.display(),
+                        old.display()
+                    );
+                    Some(data)
+                })
+                .ok_or(e)
+        });
+        if let Ok(data) = data {
+            serde_json::from_str(&data).unwrap_or_default()
         } else {
-            BlockState {
-                domains: Vec::new(),
-                enabled: false,
-            }
+            BlockState::default()
         }
     }
 
     fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
         let path = block_state_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
         let json = serde_json::to_string_pretty(self)?;
         fs::write(&path, json)?;
         Ok(())
@@ -58,28 +398,104 @@ impl BlockState {
 
     fn remove_expired(&mut self) {
         let now = chrono::Local::now();
-        self.domains.retain(|d| {
-            if let Some(ref exp) = d.expires_at {
-                if let Ok(exp_time) = chrono::DateTime::parse_from_rfc3339(exp) {
-                    return exp_time > now;
-                }
+        let expiry = |d: &BlockedDomain| {
+            d.expires_at
+                .as_deref()
+                .and_then(|exp| chrono::DateTime::parse_from_rfc3339(exp).ok())
+                .map(|exp| exp.with_timezone(&Local))
+        };
+        // no expiry = keep
+        for domain in self.take_domains(|d| expiry(d).is_some_and(|exp| exp <= now)) {
+            let until = expiry(&domain).unwrap_or(now);
+            self.end_period(&domain.domain, domain.blocked_since.as_deref(), until);
+        }
+    }
+
+    /// Take the domains matching `pred` off the block list.
+    fn take_domains(&mut self, pred: impl Fn(&BlockedDomain) -> bool) -> Vec<BlockedDomain> {
+        let (taken, kept) = std::mem::take(&mut self.domains)
+            .into_iter()
+            .partition(|d| pred(d));
+        self.domains = kept;
+        taken
+    }
+
+    /// Close a stretch of blocking `domain` that began at `since`, adding
+    /// it to the history and all-time totals.
+    fn end_period(&mut self, domain: &str, since: Option<&str>, until: DateTime<Local>) {
+        let Some(since) = since.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) else {
+            return;
+        };
+        let since = since.with_timezone(&Local);
+        if until > since {
+            *self.blocked_secs.entry(domain.to_string()).or_default() +=
+                (until - since).num_seconds();
+            self.history.push(BlockedPeriod {
+                domain: domain.to_string(),
+                start: since.to_rfc3339(),
+                end: until.to_rfc3339(),
+            });
+        }
+        let cutoff = chrono::Local::now() - chrono::Duration::days(HISTORY_DAYS);
+        self.history
+            .retain(|p| chrono::DateTime::parse_from_rfc3339(&p.end).is_ok_and(|end| end > cutoff));
+    }
+
+    /// Turn blocking on or off, pausing the clock on every domain's blocked
+    /// time while it is off.
+    fn set_enabled(&mut self, enabled: bool) {
+        let now = chrono::Local::now();
+        if enabled {
+            for domain in &mut self.domains {
+                domain.blocked_since.get_or_insert_with(|| now.to_rfc3339());
             }
-            true // no expiry = keep
-        });
+        } else {
+            let paused: Vec<(String, Option<String>)> = self
+                .domains
+                .iter_mut()
+                .map(|d| (d.domain.clone(), d.blocked_since.take()))
+                .collect();
+            for (domain, since) in paused {
+                self.end_period(&domain, since.as_deref(), now);
+            }
+        }
+        self.enabled = enabled;
     }
-}
 
-fn parse_duration(dur: &str) -> Option<chrono::Duration> {
-    let dur = dur.trim().to_lowercase();
-    if let Some(hours) = dur.strip_suffix('h') {
-        hours.parse::<i64>().ok().map(chrono::Duration::hours)
-    } else if let Some(mins) = dur.strip_suffix('m') {
-        mins.parse::<i64>().ok().map(chrono::Duration::minutes)
-    } else if let Some(secs) = dur.strip_suffix('s') {
-        secs.parse::<i64>().ok().map(chrono::Duration::seconds)
-    } else {
-        // Default to hours
-        dur.parse::<i64>().ok().map(chrono::Duration::hours)
+    /// Every domain that should be in /etc/hosts at `now`, with the
+    /// subdomains blocked under it: the block list plus the domains of
+    /// schedules whose window is open, less anything allowed.
+    fn blocked_at(&self, now: NaiveDateTime) -> Vec<(&str, Vec<&str>)> {
+        let mut blocked: Vec<(&str, Vec<&str>)> = Vec::new();
+        let listed = self
+            .domains
+            .iter()
+            .map(|d| (d.domain.as_str(), d.subdomains.as_slice()));
+        let scheduled = self
+            .schedules
+            .iter()
+            .filter(|s| s.is_active(now))
+            .flat_map(|s| {
+                s.domains
+                    .iter()
+                    .map(|d| (d.as_str(), s.subdomains.as_slice()))
+            });
+        for (domain, subdomains) in listed.chain(scheduled) {
+            if self.is_allowed(domain) || blocked.iter().any(|(d, _)| *d == domain) {
+                continue;
+            }
+            let subdomains = subdomains
+                .iter()
+                .filter(|sub| !self.is_allowed(&format!("{}.{}", sub, domain)))
+                .map(String::as_str)
+                .collect();
+            blocked.push((domain, subdomains));
+        }
+        blocked
+    }
+
+    fn is_allowed(&self, host: &str) -> bool {
+        self.allowed.iter().any(|a| a.eq_ignore_ascii_case(host))
     }
 }
 
@@ -90,8 +506,56 @@ fn backup_hosts() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn apply_blocks(state: &BlockState) -> Result<(), Box<dyn std::error::Error>> {
+/// Put back the hosts file saved before netctl first changed it. The backup
+/// is removed, so the next block saves a fresh one.
+fn restore_hosts() -> Result<(), Box<dyn std::error::Error>> {
+    if !Path::new(BACKUP_PATH).exists() {
+        return Err(format!("no backup at {} to restore", BACKUP_PATH).into());
+    }
+    fs::copy(BACKUP_PATH, HOSTS_PATH)
+        .map_err(|e| format!("could not restore {}: {} (run with sudo)", HOSTS_PATH, e))?;
+    fs::remove_file(BACKUP_PATH)?;
+    Ok(())
+}
+
+/// Check that netctl's begin and end markers pair up. Rewriting a file with
+/// a begin marker but no end would drop every line after it, real entries
+/// included.
+fn check_markers(content: &str) -> Result<(), String> {
+    let mut open = None;
+    for (i, line) in content.lines().enumerate() {
+        let n = i + 1;
+        match (line.trim(), open) {
+            (MARKER_BEGIN, Some(begin)) => {
+                return Err(format!(
+                    "line {} opens a block already opened on line {}",
+                    n, begin
+                ))
+            }
+            (MARKER_BEGIN, None) => open = Some(n),
+            (MARKER_END, None) => return Err(format!("line {} closes a block never opened", n)),
+            (MARKER_END, Some(_)) => open = None,
+            _ => {}
+        }
+    }
+    match open {
+        Some(begin) => Err(format!(
+            "the block opened on line {} is never closed",
+            begin
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Rewrite netctl's section of /etc/hosts, returning whether the file changed.
+fn apply_blocks(state: &BlockState) -> Result<bool, Box<dyn std::error::Error>> {
     let content = fs::read_to_string(HOSTS_PATH)?;
+    check_markers(&content).map_err(|why| {
+        format!(
+            "{} looks damaged, not modifying it: {} (fix it by hand or run `sudo netctl block --restore`)",
+            HOSTS_PATH, why
+        )
+    })?;
 
     // Remove existing netctl block section
     let mut new_content = String::new();
@@ -112,48 +576,186 @@ fn apply_blocks(state: &BlockState) -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Add new block section if enabled and has domains
-    if state.enabled && !state.domains.is_empty() {
+    let blocked = state.blocked_at(chrono::Local::now().naive_local());
+    if state.enabled && !blocked.is_empty() {
         new_content.push_str(MARKER_BEGIN);
         new_content.push('\n');
-        for domain in &state.domains {
-            new_content.push_str(&format!("127.0.0.1 {}\n", domain.domain));
-            new_content.push_str(&format!("127.0.0.1 www.{}\n", domain.domain));
+        for (domain, subdomains) in blocked {
+            new_content.push_str(&format!("127.0.0.1 {}\n", domain));
+            for sub in subdomains {
+                new_content.push_str(&format!("127.0.0.1 {}.{}\n", sub, domain));
+            }
         }
         new_content.push_str(MARKER_END);
         new_content.push('\n');
     }
 
+    // `--apply` runs from cron every minute; only write when something changed
+    if new_content == content {
+        return Ok(false);
+    }
     fs::write(HOSTS_PATH, new_content)?;
+    Ok(true)
+}
+
+/// Drop cached DNS answers so a hosts change takes effect now, not when they
+/// expire. A cache that won't flush only gets a warning.
+async fn flush_dns() {
+    let results = dns::flush_dns_cache().await;
+    let flushed: Vec<&str> = results
+        .iter()
+        .filter(|(_, result)| result.is_ok())
+        .map(|(service, _)| *service)
+        .collect();
+    if !flushed.is_empty() {
+        println!(
+            "  {}",
+            format!("Flushed DNS cache ({})", flushed.join(", ")).dimmed()
+        );
+    } else if let Some((service, Err(e))) = results.first() {
+        println!(
+            "  {}: Could not flush the {} DNS cache: {}",
+            "Warning".yellow(),
+            service,
+            e
+        );
+        println!("  Cached lookups may ignore the change until they expire");
+    }
+}
+
+/// Save the state and rewrite /etc/hosts, warning (not failing) when the
+/// hosts file can't be written. With `flush`, a changed hosts file is
+/// followed by a DNS cache flush.
+async fn save_and_apply(state: &BlockState, flush: bool) -> Result<(), Box<dyn std::error::Error>> {
+    state.save()?;
+    // Try to apply blocks (requires sudo/root)
+    match backup_hosts().and_then(|_| apply_blocks(state)) {
+        Ok(changed) => {
+            if changed && flush {
+                flush_dns().await;
+            }
+        }
+        Err(e) => {
+            println!();
+            println!(
+                "  {}: Could not update /etc/hosts: {}",
+                "Warning".yellow(),
+                e
+            );
+            if !is_elevated() {
+                print!("  ");
+                display::print_unprivileged_note("/etc/hosts was not modified");
+                println!("  Run with {} for /etc/hosts modification", "sudo".bold());
+            }
+            println!(
+                "  Block state saved to {} for later application",
+                block_state_path().display()
+            );
+        }
+    }
     Ok(())
 }
 
+/// " (+ www, m, api)" after a domain in the list, or nothing.
+fn subdomain_note(domain: &BlockedDomain) -> String {
+    if domain.subdomains.is_empty() {
+        return String::new();
+    }
+    format!(" (+ {})", domain.subdomains.join(", "))
+        .dimmed()
+        .to_string()
+}
+
+/// When a schedule next changes: "17:00" later today, else "Mon 09:00".
+fn format_transition(at: NaiveDateTime, now: NaiveDateTime) -> String {
+    if at.date() == now.date() {
+        at.format("%H:%M").to_string()
+    } else {
+        at.format("%a %H:%M").to_string()
+    }
+}
+
+fn print_schedules(state: &BlockState) {
+    let now = chrono::Local::now().naive_local();
+    if !state.domains.is_empty() {
+        println!();
+    }
+    println!("Schedules:");
+    for (i, schedule) in state.schedules.iter().enumerate() {
+        let next = schedule.next_transition(now);
+        let when = next.map(|at| format_transition(at, now));
+        let status = if schedule.is_active(now) {
+            format!("active until {}", when.unwrap_or_default())
+                .green()
+                .to_string()
+        } else {
+            format!("next starts {}", when.unwrap_or_default())
+                .dimmed()
+                .to_string()
+        };
+        println!("  {}. {}  {}", i + 1, schedule.describe().bold(), status);
+        println!("     {}", schedule.domains.join(", "));
+    }
+    println!(
+        "{}",
+        "  Keep schedules current with `netctl block --apply` in root's crontab".dimmed()
+    );
+}
+
 fn print_status(state: &BlockState) {
     println!();
     println!("{}", "Domain Blocker".bold());
     println!();
 
-    if state.domains.is_empty() {
+    if state.domains.is_empty() && state.schedules.is_empty() {
         println!("  No domains are currently blocked.");
-    } else {
+    } else if !state.domains.is_empty() {
         println!("Currently blocked:");
         let now = chrono::Local::now();
         for domain in &state.domains {
+            if state.is_allowed(&domain.domain) {
+                println!(
+                    "  {} {} {}",
+                    "-".dimmed(),
+                    domain.domain,
+                    "(allowed, not blocked)".dimmed()
+                );
+                continue;
+            }
             if let Some(ref exp) = domain.expires_at {
                 if let Ok(exp_time) = chrono::DateTime::parse_from_rfc3339(exp) {
                     let remaining = exp_time.signed_duration_since(now);
                     let hours = remaining.num_hours();
                     let mins = remaining.num_minutes() % 60;
                     println!(
-                        "  {} {} (expires in {}h {}m)",
+                        "  {} {}{} (expires in {}h {}m)",
                         "T".yellow(),
                         domain.domain,
+                        subdomain_note(domain),
                         hours,
                         mins
                     );
                     continue;
                 }
             }
-            println!("  {} {}", "V".green(), domain.domain);
+            println!(
+                "  {} {}{}",
+                "V".green(),
+                domain.domain,
+                subdomain_note(domain)
+            );
+        }
+    }
+
+    if !state.schedules.is_empty() {
+        print_schedules(state);
+    }
+
+    if !state.allowed.is_empty() {
+        println!();
+        println!("Allowed (never blocked):");
+        for host in &state.allowed {
+            println!("  {} {}", "+".green(), host);
         }
     }
 
@@ -172,20 +774,235 @@ fn print_status(state: &BlockState) {
     println!();
 }
 
-pub fn run(
-    add: Option<String>,
-    remove: Option<String>,
-    list: bool,
-    enable: bool,
-    disable: bool,
-    duration: Option<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// "3h 05m", or "12m" under an hour.
+fn format_focus(secs: i64) -> String {
+    let mins = secs / 60;
+    if mins < 60 {
+        format!("{}m", mins)
+    } else {
+        format!("{}h {:02}m", mins / 60, mins % 60)
+    }
+}
+
+/// Per-domain and total blocked time this week (since Monday 00:00), plus
+/// each domain's all-time total, counting blocks still in progress.
+fn print_stats(state: &BlockState) {
+    let now = chrono::Local::now();
+    let monday = now.date_naive() - chrono::Days::new(now.weekday().num_days_from_monday() as u64);
+    let week_start = Local
+        .from_local_datetime(&monday.and_time(NaiveTime::MIN))
+        .earliest()
+        .unwrap_or(now);
+    let parse = |t: &str| {
+        chrono::DateTime::parse_from_rfc3339(t)
+            .ok()
+            .map(|t| t.with_timezone(&Local))
+    };
+
+    let finished = state
+        .history
+        .iter()
+        .filter_map(|p| Some((p.domain.as_str(), parse(&p.start)?, parse(&p.end)?)));
+    let ongoing = state
+        .domains
+        .iter()
+        .filter(|d| !state.is_allowed(&d.domain))
+        .filter_map(|d| Some((d.domain.as_str(), parse(d.blocked_since.as_deref()?)?, now)));
+    let mut week: Vec<(DateTime<Local>, DateTime<Local>)> = Vec::new();
+    let mut per_domain: BTreeMap<&str, (i64, i64)> = state
+        .blocked_secs
+        .iter()
+        .map(|(domain, secs)| (domain.as_str(), (0, *secs)))
+        .collect();
+    for (domain, start, end) in finished.clone().chain(ongoing.clone()) {
+        let (start, end) = (start.max(week_start), end.min(now));
+        if end > start {
+            per_domain.entry(domain).or_default().0 += (end - start).num_seconds();
+            week.push((start, end));
+        }
+    }
+    for (domain, start, end) in ongoing {
+        per_domain.entry(domain).or_default().1 += (end - start).num_seconds();
+    }
+
+    // Domains blocked side by side count once toward focus time
+    week.sort();
+    let mut focus_secs = 0;
+    let mut covered_until = week_start;
+    for (start, end) in week {
+        let start = start.max(covered_until);
+        if end > start {
+            focus_secs += (end - start).num_seconds();
+            covered_until = end;
+        }
+    }
+
+    println!();
+    println!("{}", "Focus statistics".bold());
+    println!();
+    let mut rows: Vec<(&str, (i64, i64))> = per_domain.into_iter().collect();
+    rows.retain(|(_, (_, all_time))| *all_time >= 60);
+    if rows.is_empty() {
+        println!("  Nothing has been blocked yet.");
+        println!();
+        return;
+    }
+    rows.sort_by_key(|(_, secs)| std::cmp::Reverse(*secs));
+    println!(
+        "  {:<28} {:>10} {:>10}",
+        "Domain".bold(),
+        "This week".bold(),
+        "All time".bold()
+    );
+    for (domain, (this_week, all_time)) in rows {
+        println!(
+            "  {:<28} {:>10} {:>10}",
+            domain,
+            format_focus(this_week),
+            format_focus(all_time)
+        );
+    }
+    println!();
+    println!(
+        "Focus time this week: {} (since {})",
+        format_focus(focus_secs).green().bold(),
+        week_start.format("%a %b %-d")
+    );
+    println!(
+        "{}",
+        "Counts time any domain was blocked; scheduled windows are not included".dimmed()
+    );
+    println!();
+}
+
+fn count_domains(n: usize) -> String {
+    format!("{} domain{}", n, if n == 1 { "" } else { "s" })
+}
+
+/// Everything `netctl block` can do in one invocation besides Pomodoro.
+pub struct BlockOptions {
+    pub add: Option<String>,
+    pub remove: Option<String>,
+    pub list: bool,
+    pub enable: bool,
+    pub disable: bool,
+    pub duration: Option<String>,
+    pub subdomains: Vec<String>,
+    /// Block `add` during this recurring window instead of permanently
+    pub schedule: Option<String>,
+    /// 1-based position of a schedule to delete, as `--list` numbers them
+    pub unschedule: Option<usize>,
+    /// Rewrite /etc/hosts for the current time, for cron
+    pub apply: bool,
+    /// Copy the backup over /etc/hosts and forget every block
+    pub restore: bool,
+    /// Flush the DNS cache after /etc/hosts changes
+    pub flush: bool,
+    /// Hostnames to keep unblocked even when listed or in a category
+    pub allow: Option<String>,
+    /// Hostname to take off the allowlist
+    pub unallow: Option<String>,
+    /// Summarize this week's blocked time instead of changing anything
+    pub stats: bool,
+}
+
+pub async fn run(opts: BlockOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let BlockOptions {
+        add,
+        remove,
+        list,
+        enable,
+        disable,
+        duration,
+        subdomains,
+        schedule,
+        unschedule,
+        apply,
+        restore,
+        flush,
+        allow,
+        unallow,
+        stats,
+    } = opts;
+    if restore {
+        restore_hosts()?;
+        // Everything goes but the record of past blocks
+        let mut state = BlockState::load();
+        state.remove_expired();
+        state.set_enabled(false);
+        BlockState {
+            history: state.history,
+            blocked_secs: state.blocked_secs,
+            ..BlockState::default()
+        }
+        .save()?;
+        println!("  Restored {} from {}", HOSTS_PATH, BACKUP_PATH.cyan());
+        println!("  Cleared all blocked domains and schedules");
+        if flush {
+            flush_dns().await;
+        }
+        return Ok(());
+    }
+
     let mut state = BlockState::load();
     state.remove_expired();
+    if stats {
+        print_stats(&state);
+        return Ok(());
+    }
 
     let mut modified = false;
 
-    if let Some(domains) = add {
+    if let Some(hosts) = allow {
+        for host in hosts.split(',') {
+            let host = host.trim().trim_end_matches('.').to_string();
+            if host.is_empty() {
+                continue;
+            }
+            if state.is_allowed(&host) {
+                println!("  {} is already allowed", host);
+                continue;
+            }
+            println!("  Allowing {}", host.cyan());
+            state.allowed.push(host);
+        }
+        modified = true;
+    }
+
+    if let Some(host) = unallow {
+        let host = host.trim().trim_end_matches('.').to_string();
+        let before = state.allowed.len();
+        state.allowed.retain(|a| !a.eq_ignore_ascii_case(&host));
+        if state.allowed.len() < before {
+            println!("  Removed {} from the allowlist", host.cyan());
+            modified = true;
+        } else {
+            println!("  {} was not on the allowlist", host);
+        }
+    }
+
+    if let Some(spec) = schedule {
+        let domains: Vec<String> = add
+            .iter()
+            .flat_map(|a| a.split(','))
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .collect();
+        if domains.is_empty() {
+            return Err(
+                "nothing to schedule: pass --add and/or --add-category with --schedule".into(),
+            );
+        }
+        let schedule = parse_schedule(&spec, domains, subdomains.clone())?;
+        println!(
+            "  Scheduling {} for {}",
+            count_domains(schedule.domains.len()),
+            schedule.describe().cyan()
+        );
+        state.schedules.push(schedule);
+        state.set_enabled(true);
+        modified = true;
+    } else if let Some(domains) = add {
         let expiry = duration.as_deref().and_then(|d| {
             parse_duration(d).map(|dur| (chrono::Local::now() + dur).to_rfc3339())
         });
@@ -195,26 +1012,41 @@ pub fn run(
             if domain.is_empty() {
                 continue;
             }
-            // Don't add duplicates
-            if state.domains.iter().any(|d| d.domain == domain) {
-                println!("  {} is already blocked", domain);
+            // Don't add duplicates, but take the new subdomain list
+            if let Some(existing) = state.domains.iter_mut().find(|d| d.domain == domain) {
+                if existing.subdomains == subdomains {
+                    println!("  {} is already blocked", domain);
+                } else {
+                    println!("  Updating subdomains blocked under {}", domain.cyan());
+                    existing.subdomains = subdomains.clone();
+                }
                 continue;
             }
             println!("  Adding block for {}", domain.cyan());
+            if state.is_allowed(&domain) {
+                println!(
+                    "    {}",
+                    format!("{} is on the allowlist, so it stays unblocked", domain).dimmed()
+                );
+            }
             state.domains.push(BlockedDomain {
                 domain,
                 expires_at: expiry.clone(),
+                subdomains: subdomains.clone(),
+                blocked_since: None,
             });
         }
-        state.enabled = true;
+        state.set_enabled(true);
         modified = true;
     }
 
     if let Some(domain) = remove {
         let domain = domain.trim().to_string();
-        let before = state.domains.len();
-        state.domains.retain(|d| d.domain != domain);
-        if state.domains.len() < before {
+        let removed = state.take_domains(|d| d.domain == domain);
+        for d in &removed {
+            state.end_period(&d.domain, d.blocked_since.as_deref(), chrono::Local::now());
+        }
+        if !removed.is_empty() {
             println!("  Removed block for {}", domain.cyan());
             modified = true;
         } else {
@@ -222,31 +1054,42 @@ pub fn run(
         }
     }
 
+    if let Some(n) = unschedule {
+        if (1..=state.schedules.len()).contains(&n) {
+            let removed = state.schedules.remove(n - 1);
+            println!("  Removed schedule {}", removed.describe().cyan());
+            modified = true;
+        } else {
+            println!("  There is no schedule {} (see --list)", n);
+        }
+    }
+
+    if apply {
+        let now = chrono::Local::now().naive_local();
+        let active = state.schedules.iter().filter(|s| s.is_active(now)).count();
+        println!(
+            "  Blocking {} ({} of {} schedules active)",
+            count_domains(state.blocked_at(now).len()),
+            active,
+            state.schedules.len()
+        );
+        modified = true;
+    }
+
     if enable {
-        state.enabled = true;
+        state.set_enabled(true);
         modified = true;
         println!("  Domain blocking {}", "enabled".green());
     }
 
     if disable {
-        state.enabled = false;
+        state.set_enabled(false);
         modified = true;
         println!("  Domain blocking {}", "disabled".red());
     }
 
     if modified {
-        state.save()?;
-        // Try to apply blocks (requires sudo/root)
-        if let Err(e) = backup_hosts().and_then(|_| apply_blocks(&state)) {
-            println!();
-            println!(
-                "  {}: Could not update /etc/hosts: {}",
-                "Warning".yellow(),
-                e
-            );
-            println!("  Run with {} for /etc/hosts modification", "sudo".bold());
-            println!("  Block state saved to {} for later application", block_state_path().display());
-        }
+        save_and_apply(&state, flush).await?;
     }
 
     if list || (!modified && !enable && !disable) {
@@ -255,3 +1098,129 @@ pub fn run(
 
     Ok(())
 }
+
+/// Block `domains` until `until`, returning the ones this call added so
+/// domains that were already blocked stay blocked afterwards.
+async fn start_focus(
+    domains: &[String],
+    subdomains: &[String],
+    until: chrono::DateTime<chrono::Local>,
+    flush: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut state = BlockState::load();
+    state.remove_expired();
+    let mut added = Vec::new();
+    for domain in domains {
+        if state.domains.iter().any(|d| &d.domain == domain) {
+            continue;
+        }
+        // The expiry lets a crashed session clean up after itself
+        state.domains.push(BlockedDomain {
+            domain: domain.clone(),
+            expires_at: Some(until.to_rfc3339()),
+            subdomains: subdomains.to_vec(),
+            blocked_since: None,
+        });
+        added.push(domain.clone());
+    }
+    state.set_enabled(true);
+    save_and_apply(&state, flush).await?;
+    Ok(added)
+}
+
+async fn end_focus(added: &[String], flush: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut state = BlockState::load();
+    state.remove_expired();
+    for d in state.take_domains(|d| added.contains(&d.domain)) {
+        state.end_period(&d.domain, d.blocked_since.as_deref(), chrono::Local::now());
+    }
+    save_and_apply(&state, flush).await
+}
+
+/// Print a phase change and ring the terminal bell.
+fn announce(message: &str) {
+    println!();
+    println!(
+        "\x07{} {}",
+        chrono::Local::now().format("%H:%M").to_string().dimmed(),
+        message
+    );
+}
+
+/// Alternate blocked work intervals with unblocked breaks. Ctrl+C ends the
+/// session and removes every block it added.
+pub async fn pomodoro(
+    domains: Option<String>,
+    subdomains: Vec<String>,
+    work_mins: u64,
+    break_mins: u64,
+    cycles: u32,
+    flush: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let domains: Vec<String> = domains
+        .iter()
+        .flat_map(|d| d.split(','))
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    if domains.is_empty() {
+        return Err("nothing to block: pass --add and/or --preset with --pomodoro".into());
+    }
+
+    catch_interrupt();
+    println!();
+    println!(
+        "{} {} x {}m focus / {}m break, blocking {} domains",
+        "Pomodoro:".bold(),
+        cycles,
+        work_mins,
+        break_mins,
+        domains.len()
+    );
+    println!(
+        "{}",
+        "Press Ctrl+C to end the session and unblock.".dimmed()
+    );
+
+    let mut completed = 0;
+    for cycle in 1..=cycles {
+        let until = chrono::Local::now() + chrono::Duration::minutes(work_mins as i64);
+        let added = start_focus(&domains, &subdomains, until, flush).await?;
+        announce(&format!(
+            "{} {}/{} until {}",
+            "Focus".red().bold(),
+            cycle,
+            cycles,
+            until.format("%H:%M")
+        ));
+        let finished =
+            sleep_unless_interrupted(std::time::Duration::from_secs(work_mins * 60)).await;
+        end_focus(&added, flush).await?;
+        if !finished {
+            break;
+        }
+        completed += 1;
+
+        if cycle == cycles {
+            break;
+        }
+        let until = chrono::Local::now() + chrono::Duration::minutes(break_mins as i64);
+        announce(&format!(
+            "{} until {} (sites unblocked)",
+            "Break".green().bold(),
+            until.format("%H:%M")
+        ));
+        if !sleep_unless_interrupted(std::time::Duration::from_secs(break_mins * 60)).await {
+            break;
+        }
+    }
+
+    announce(&format!(
+        "{} {}/{} focus intervals completed, all session blocks removed",
+        "Done:".bold(),
+        completed,
+        cycles
+    ));
+    println!();
+    Ok(())
+}
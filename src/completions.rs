@@ -0,0 +1,252 @@
+use clap::Command;
+
+/// Shells `netctl completions` can write a script for.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// A completion candidate and its one-line description.
+struct Word {
+    text: String,
+    help: String,
+}
+
+/// Every spot where TAB can offer something, keyed like "netctl dns" for a
+/// command's subcommands and flags or "netctl dns resolve --type" for a
+/// flag's possible values.
+fn table(cmd: &Command, path: &str, out: &mut Vec<(String, Vec<Word>)>) {
+    let first_line = |s: Option<&clap::builder::StyledStr>| {
+        s.map(|s| s.to_string().lines().next().unwrap_or("").to_string())
+            .unwrap_or_default()
+    };
+    let mut words = Vec::new();
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        words.push(Word {
+            text: sub.get_name().to_string(),
+            help: first_line(sub.get_about()),
+        });
+    }
+    for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+        let values: Vec<Word> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| Word {
+                text: v.get_name().to_string(),
+                help: first_line(v.get_help()),
+            })
+            .collect();
+        match arg.get_long() {
+            Some(long) => {
+                let flag = format!("--{}", long);
+                if !values.is_empty() {
+                    out.push((format!("{} {}", path, flag), values));
+                }
+                words.push(Word {
+                    text: flag,
+                    help: first_line(arg.get_help()),
+                });
+            }
+            // Positionals with a fixed set of values, like the shell here
+            None if arg.is_positional() => words.extend(values),
+            None => {}
+        }
+    }
+    out.push((path.to_string(), words));
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        table(sub, &format!("{} {}", path, sub.get_name()), out);
+    }
+}
+
+/// Quote `s` for a POSIX shell or fish, which both take `'\''`.
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn bash(name: &str, entries: &[(String, Vec<Word>)]) -> String {
+    let mut cases = String::new();
+    for (key, words) in entries {
+        let list: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        cases += &format!(
+            "        {}) echo {} ;;\n",
+            sh_quote(key),
+            sh_quote(&list.join(" "))
+        );
+    }
+    format!(
+        "\
+_{name}_words() {{
+    case \"$1\" in
+{cases}    esac
+}}
+
+_{name}() {{
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    local cmd={name} word i candidates
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word=\"${{COMP_WORDS[i]}}\"
+        if [[ $word != -* && -n $(_{name}_words \"$cmd $word\") ]]; then
+            cmd=\"$cmd $word\"
+        fi
+    done
+    candidates=$(_{name}_words \"$cmd $prev\")
+    [[ -z $candidates ]] && candidates=$(_{name}_words \"$cmd\")
+    COMPREPLY=($(compgen -W \"$candidates\" -- \"$cur\"))
+}}
+
+complete -F _{name} {name}
+"
+    )
+}
+
+fn zsh(name: &str, entries: &[(String, Vec<Word>)]) -> String {
+    let mut cases = String::new();
+    for (key, words) in entries {
+        let list: Vec<String> = words
+            .iter()
+            .map(|w| sh_quote(&format!("{}:{}", w.text.replace(':', "\\:"), w.help)))
+            .collect();
+        cases += &format!("        {}) reply=({}) ;;\n", sh_quote(key), list.join(" "));
+    }
+    format!(
+        "\
+#compdef {name}
+
+_{name}_words() {{
+    case \"$1\" in
+{cases}    esac
+}}
+
+_{name}() {{
+    local cmd={name} word i
+    local -a reply
+    for ((i = 2; i < CURRENT; i++)); do
+        word=${{words[i]}}
+        reply=()
+        [[ $word == -* ]] || _{name}_words \"$cmd $word\"
+        (( ${{#reply}} )) && cmd=\"$cmd $word\"
+    done
+    reply=()
+    _{name}_words \"$cmd ${{words[CURRENT-1]}}\"
+    (( ${{#reply}} )) || _{name}_words \"$cmd\"
+    _describe {name} reply
+}}
+
+if [ \"$funcstack[1]\" = \"_{name}\" ]; then
+    _{name} \"$@\"
+else
+    compdef _{name} {name}
+fi
+"
+    )
+}
+
+fn fish(name: &str, entries: &[(String, Vec<Word>)]) -> String {
+    // Fish single quotes only treat \\ and \' specially
+    let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut cases = String::new();
+    for (key, words) in entries {
+        let list: Vec<String> = words
+            .iter()
+            .map(|w| format!("{} {}", quote(&w.text), quote(&w.help)))
+            .collect();
+        cases += &format!(
+            "        case {}\n            printf '%s\\t%s\\n' {}\n",
+            quote(key),
+            list.join(" ")
+        );
+    }
+    format!(
+        "\
+function __{name}_words
+    switch $argv[1]
+{cases}    end
+end
+
+function __{name}_complete
+    set -l tokens (commandline -opc)
+    set -l prev $tokens[-1]
+    set -e tokens[1]
+    set -l cmd {name}
+    for word in $tokens
+        if not string match -q -- '-*' $word
+            set -l found (__{name}_words \"$cmd $word\")
+            if test (count $found) -gt 0
+                set cmd \"$cmd $word\"
+            end
+        end
+    end
+    set -l candidates (__{name}_words \"$cmd $prev\")
+    if test (count $candidates) -eq 0
+        set candidates (__{name}_words $cmd)
+    end
+    printf '%s\\n' $candidates
+end
+
+complete -c {name} -f -a '(__{name}_complete)'
+"
+    )
+}
+
+fn powershell(name: &str, entries: &[(String, Vec<Word>)]) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let mut table = String::new();
+    for (key, words) in entries {
+        table += &format!("        {} = @(\n", quote(key));
+        for w in words {
+            // The leading comma keeps each pair from being flattened
+            table += &format!("            ,@({}, {})\n", quote(&w.text), quote(&w.help));
+        }
+        table += "        )\n";
+    }
+    format!(
+        "\
+Register-ArgumentCompleter -Native -CommandName '{name}' -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @{{
+{table}    }}
+    $tokens = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete) {{
+        $tokens = @($tokens | Select-Object -SkipLast 1)
+    }}
+    $cmd = '{name}'
+    foreach ($word in $tokens) {{
+        if (-not $word.StartsWith('-') -and $words.ContainsKey(\"$cmd $word\")) {{
+            $cmd = \"$cmd $word\"
+        }}
+    }}
+    $prev = if ($tokens.Count) {{ $tokens[-1] }} else {{ '' }}
+    $candidates = $words[\"$cmd $prev\"]
+    if (-not $candidates) {{
+        $candidates = $words[$cmd]
+    }}
+    $candidates | Where-Object {{ $_[0] -like \"$wordToComplete*\" }} | ForEach-Object {{
+        $help = if ($_[1]) {{ $_[1] }} else {{ $_[0] }}
+        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterValue', $help)
+    }}
+}}
+"
+    )
+}
+
+/// Print a completion script for `shell` covering every subcommand, long
+/// flag, and fixed flag value in `cmd`.
+pub fn run(mut cmd: Command, shell: Shell) -> Result<(), Box<dyn std::error::Error>> {
+    // Building propagates global flags and adds --help/--version everywhere
+    cmd.build();
+    let name = cmd.get_name().to_string();
+    let mut entries = Vec::new();
+    table(&cmd, &name, &mut entries);
+    let script = match shell {
+        Shell::Bash => bash(&name, &entries),
+        Shell::Zsh => zsh(&name, &entries),
+        Shell::Fish => fish(&name, &entries),
+        Shell::Powershell => powershell(&name, &entries),
+    };
+    print!("{}", script);
+    Ok(())
+}
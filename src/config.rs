@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Keys the config file understands, as `section.key`. Each can also be set
+/// with an environment variable named after it, e.g. `NETCTL_PING_COUNT`.
+const KEYS: [&str; 4] = [
+    "speed.server",
+    "ping.count",
+    "bandwidth.interval",
+    "block.state_path",
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Defaults from the config file and `NETCTL_*` environment variables.
+/// Flags given on the command line win over both.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Server `speed` tests against when none is chosen
+    pub speed_server: Option<String>,
+    /// Packets `ping` sends without `--count`
+    pub ping_count: Option<u32>,
+    /// Seconds between `bandwidth` samples without `--interval`
+    pub bandwidth_interval: Option<u64>,
+    /// Where `block` keeps its state instead of the default location
+    pub block_state_path: Option<PathBuf>,
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("'{}' is not a positive whole number", value))
+}
+
+impl Config {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "speed.server" => self.speed_server = Some(value.to_string()),
+            "ping.count" => self.ping_count = Some(number(value)?),
+            "bandwidth.interval" => self.bandwidth_interval = Some(number(value)?),
+            "block.state_path" => self.block_state_path = Some(expand_home(value)),
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+/// The environment variable that overrides `key`: "ping.count" is
+/// `NETCTL_PING_COUNT`.
+fn env_name(key: &str) -> String {
+    format!("NETCTL_{}", key.replace('.', "_").to_uppercase())
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `$NETCTL_CONFIG` if set, otherwise `config.toml` under
+/// `$XDG_CONFIG_HOME/netctl` (~/.config/netctl). The flag says whether the
+/// path was given explicitly, so a missing file is an error.
+fn config_path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = std::env::var_os("NETCTL_CONFIG").filter(|p| !p.is_empty()) {
+        return Some((PathBuf::from(path), true));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some((base.join("netctl").join("config.toml"), false))
+}
+
+/// Parse one TOML value: a basic ("...") or literal ('...') string, or a
+/// bare integer or boolean. Anything after it must be a comment.
+fn parse_value(raw: &str) -> Result<String, String> {
+    let comment_only = |rest: &str| {
+        let rest = rest.trim();
+        if rest.is_empty() || rest.starts_with('#') {
+            Ok(())
+        } else {
+            Err(format!("unexpected '{}' after the value", rest))
+        }
+    };
+    if let Some(body) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = body.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    comment_only(&body[i + 1..])?;
+                    return Ok(value);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    _ => return Err("unsupported escape in string".to_string()),
+                },
+                c => value.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    } else if let Some(body) = raw.strip_prefix('\'') {
+        let (value, rest) = body
+            .split_once('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        comment_only(rest)?;
+        Ok(value.to_string())
+    } else {
+        let value = raw.split('#').next().unwrap_or("").trim();
+        if value.parse::<i64>().is_ok() || value == "true" || value == "false" {
+            Ok(value.to_string())
+        } else {
+            Err(format!("'{}' is not a number (quote strings)", value))
+        }
+    }
+}
+
+/// The `(line, section.key, value)` settings in a config file. Only the flat
+/// subset of TOML the keys need is understood: `[section]` headers,
+/// `key = value` pairs, and `#` comments.
+fn parse(content: &str) -> Result<Vec<(usize, String, String)>, String> {
+    let mut settings = Vec::new();
+    let mut section = String::new();
+    for (n, line) in content.lines().enumerate() {
+        let n = n + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.split('#').next().unwrap_or("").trim_end();
+            section = header
+                .strip_suffix(']')
+                .ok_or_else(|| format!("line {}: unclosed section header", n))?
+                .trim()
+                .to_string();
+            continue;
+        }
+        let (key, raw) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected key = value", n))?;
+        let value = parse_value(raw.trim()).map_err(|e| format!("line {}: {}", n, e))?;
+        let key = key.trim().trim_matches('"');
+        let key = if section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", section, key)
+        };
+        settings.push((n, key, value));
+    }
+    Ok(settings)
+}
+
+fn load() -> Result<Config, String> {
+    let mut config = Config::default();
+    if let Some((path, explicit)) = config_path() {
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let settings = parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+                for (n, key, value) in settings {
+                    config
+                        .set(&key, &value)
+                        .map_err(|e| format!("{}: line {}: {}", path.display(), n, e))?;
+                }
+                log::debug!("loaded config from {}", path.display());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {}
+            Err(e) => return Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+    for key in KEYS {
+        let name = env_name(key);
+        if let Some(value) = std::env::var(&name).ok().filter(|v| !v.is_empty()) {
+            config
+                .set(key, &value)
+                .map_err(|e| format!("{}: {}", name, e))?;
+        }
+    }
+    Ok(config)
+}
+
+/// Read the config file and environment once at startup.
+pub fn init() -> Result<(), Box<dyn std::error::Error>> {
+    let config = load()?;
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// The settings read by `init`, or built-in defaults before it runs.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
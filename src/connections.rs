@@ -1,8 +1,29 @@
 use colored::Colorize;
+use futures_util::StreamExt;
 use std::collections::HashMap;
-use tabled::{Table, settings::Style};
 use serde::Serialize;
-use crate::utils::get_process_name;
+use crate::display::{self, TableStyle};
+use crate::geoip::{self, GeoDb};
+use crate::output::{self, OutputMode, RecordSink};
+use crate::utils::{
+    get_process_info, host_of, is_elevated, port_of, port_service, run_command, AppFilter,
+    COMMAND_TIMEOUT,
+};
+
+/// Columns hidden first when the table doesn't fit the terminal.
+const NARROW_DROP_ORDER: &[&str] = &["User", "State", "Protocol", "PID"];
+/// The same, for the `--geo` table; the location goes last.
+const GEO_DROP_ORDER: &[&str] = &["User", "State", "Protocol", "PID", "Location"];
+/// Reverse lookups in flight at once with `--resolve`.
+const RESOLVE_CONCURRENCY: usize = 16;
+/// Longest wait for one PTR lookup before showing the bare IP.
+const RESOLVE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+/// Opened and closed connections listed under the watch-mode delta line.
+const CHANGES_SHOWN: usize = 5;
+
+/// How watch mode tells connections apart between refreshes: PID and
+/// remote address, mapped to the application name.
+type ConnectionSet = HashMap<(String, String), String>;
 
 #[derive(Debug, Clone, Serialize, tabled::Tabled)]
 struct Connection {
@@ -10,42 +31,297 @@ struct Connection {
     pid: String,
     #[tabled(rename = "Application")]
     application: String,
+    #[tabled(rename = "User")]
+    user: String,
     #[tabled(rename = "Remote Address")]
     remote_address: String,
     #[tabled(rename = "Protocol")]
     protocol: String,
     #[tabled(rename = "State")]
     state: String,
+    /// Smoothed TCP round-trip time from `ss -ti`, when collected
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rtt_ms: Option<f64>,
+    /// Reverse-DNS name of the remote host, with `--resolve`
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote_host: Option<String>,
+    /// GeoIP city/country of an external peer, with `--geo`
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+}
+
+/// A table row with the `--geo` location column appended.
+#[derive(tabled::Tabled)]
+struct GeoRow {
+    #[tabled(inline)]
+    connection: Connection,
+    #[tabled(rename = "Location")]
+    location: String,
+}
+
+/// Transport selected with `connections --proto`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl Transport {
+    /// Whether a protocol label such as "TCP/HTTPS" or "udp" uses this transport.
+    fn matches(self, protocol: &str) -> bool {
+        let prefix = match self {
+            Transport::Tcp => "TCP",
+            Transport::Udp => "UDP",
+        };
+        protocol.to_uppercase().starts_with(prefix)
+    }
+}
+
+/// Which connections to keep: by application, local vs. external, owning
+/// user, remote port and transport, and (with `--min-rtt`) by TCP round-trip time.
+pub struct ConnectionFilter {
+    pub apps: AppFilter,
+    pub external_only: bool,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub transport: Option<Transport>,
+    pub min_rtt_ms: Option<f64>,
+}
+
+/// How `run` presents connections: once or in watch mode, as a table,
+/// per remote host, or as JSON.
+pub struct ConnectionsOptions {
+    pub watch: bool,
+    pub interval: u64,
+    pub output: Option<String>,
+    pub by_remote: bool,
+    /// One summary row per application (`--group`)
+    pub group: bool,
+    /// JSON prints the full filtered list once, without the table's limits
+    pub mode: OutputMode,
+    /// Most table rows to print; 0 shows every row
+    pub limit: usize,
+    pub resolve: bool,
+    /// Annotate external peers with their location (`--geo`)
+    pub geo: bool,
+    /// MaxMind database to use instead of the default locations
+    pub geoip_db: Option<String>,
+}
+
+/// One watch-mode snapshot streamed to `--output` as NDJSON.
+#[derive(Serialize)]
+struct ConnectionsRecord<'a> {
+    timestamp: String,
+    total: usize,
+    external: usize,
+    local: usize,
+    connections: &'a [Connection],
+}
+
+/// Connections grouped by the remote host they talk to.
+#[derive(tabled::Tabled)]
+struct RemoteGroup {
+    #[tabled(rename = "Remote Host")]
+    remote: String,
+    #[tabled(rename = "Connections")]
+    count: usize,
+    #[tabled(rename = "Applications")]
+    applications: String,
+}
+
+/// Per-application totals for `--group`.
+#[derive(tabled::Tabled)]
+struct AppSummary {
+    #[tabled(rename = "Application")]
+    application: String,
+    #[tabled(rename = "Connections")]
+    count: usize,
+    #[tabled(rename = "Remote Hosts")]
+    remote_hosts: usize,
+    #[tabled(rename = "Protocols")]
+    protocols: String,
+}
+
+/// Summarize connections by application: how many sockets each has, how
+/// many distinct peers it talks to, and which protocols it uses.
+fn group_by_app(connections: &[Connection]) -> Vec<AppSummary> {
+    #[derive(Default)]
+    struct Totals<'a> {
+        count: usize,
+        hosts: Vec<&'a str>,
+        protocols: Vec<&'a str>,
+    }
+
+    let mut apps: HashMap<&str, Totals> = HashMap::new();
+    for conn in connections {
+        let totals = apps.entry(&conn.application).or_default();
+        totals.count += 1;
+        // Listening sockets have no peer
+        if conn.state != "LISTEN" && !conn.remote_address.ends_with(":*") {
+            let host = host_of(&conn.remote_address);
+            if !totals.hosts.contains(&host) {
+                totals.hosts.push(host);
+            }
+        }
+        if !totals.protocols.contains(&conn.protocol.as_str()) {
+            totals.protocols.push(&conn.protocol);
+        }
+    }
+
+    let mut rows: Vec<AppSummary> = apps
+        .into_iter()
+        .map(|(app, mut totals)| {
+            totals.protocols.sort();
+            AppSummary {
+                application: app.to_string(),
+                count: totals.count,
+                remote_hosts: totals.hosts.len(),
+                protocols: totals.protocols.join(", "),
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.application.cmp(&b.application))
+    });
+    rows
+}
+
+/// Group connections by remote host, listing the local apps (and PIDs) using
+/// each one. Listening sockets have no peer and are left out.
+fn group_by_remote(connections: &[Connection]) -> Vec<RemoteGroup> {
+    let mut groups: HashMap<&str, (usize, Vec<String>)> = HashMap::new();
+    for conn in connections {
+        if conn.state == "LISTEN" || conn.remote_address.ends_with(":*") {
+            continue;
+        }
+        let entry = groups.entry(host_of(&conn.remote_address)).or_default();
+        entry.0 += 1;
+        let app = format!("{} ({})", conn.application, conn.pid);
+        if !entry.1.contains(&app) {
+            entry.1.push(app);
+        }
+    }
+
+    let mut rows: Vec<RemoteGroup> = groups
+        .into_iter()
+        .map(|(remote, (count, mut apps))| {
+            apps.sort();
+            RemoteGroup {
+                remote: remote.to_string(),
+                count,
+                applications: apps.join(", "),
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.remote.cmp(&b.remote)));
+    rows
+}
+
+/// Smoothed RTT per peer address from `ss -tin` (Linux only). Each socket
+/// is followed by an indented detail line containing e.g. "rtt:12.5/3.1".
+async fn read_rtts() -> Option<HashMap<String, f64>> {
+    let output = run_command("ss", &["-tin", "state", "established"], COMMAND_TIMEOUT)
+        .await
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut rtts: HashMap<String, f64> = HashMap::new();
+    let mut peer: Option<String> = None;
+    for line in stdout.lines().skip(1) {
+        if !line.starts_with(char::is_whitespace) {
+            // With a state filter the columns are: Recv-Q Send-Q Local Peer
+            peer = line.split_whitespace().nth(3).map(|p| p.to_string());
+            continue;
+        }
+        let rtt = line
+            .split_whitespace()
+            .find_map(|t| t.strip_prefix("rtt:"))
+            .and_then(|t| t.split('/').next())
+            .and_then(|t| t.parse::<f64>().ok());
+        if let (Some(peer), Some(rtt)) = (peer.take(), rtt) {
+            // Several sockets to one peer: keep the slowest
+            let entry = rtts.entry(peer).or_insert(rtt);
+            *entry = entry.max(rtt);
+        }
+    }
+    Some(rtts)
 }
 
+/// Whether a peer address stays on this host or network: loopback,
+/// unspecified/wildcard, RFC 1918, CGNAT (100.64.0.0/10), link-local, and
+/// IPv6 unique-local (fc00::/7). Hostnames other than localhost are external.
 fn is_local_address(addr: &str) -> bool {
-    addr.starts_with("127.")
-        || addr.starts_with("0.0.0.0")
-        || addr.starts_with("[::1]")
-        || addr.starts_with("*:")
-        || addr.starts_with("localhost")
-        || addr.starts_with("192.168.")
-        || addr.starts_with("10.")
-        || addr.starts_with("172.16.")
+    use std::net::IpAddr;
+
+    // Drop the port and brackets, then any zone: "[fe80::1%en0]" (lsof)
+    // or "[fe80::1]%eth0" (ss)
+    let host = host_of(addr);
+    let host = host.split('%').next().unwrap_or(host).trim_end_matches(']');
+    if host == "*" || host == "localhost" {
+        return true;
+    }
+    let ip = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(v6)) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        Ok(ip) => ip,
+        Err(_) => return false,
+    };
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_unspecified()
+                || v4.is_private()
+                || v4.is_link_local()
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+        }
+    }
 }
 
-fn parse_connections() -> Vec<Connection> {
+/// Label a transport protocol with the service on the address's port,
+/// e.g. "TCP/HTTPS" or "UDP/DNS".
+fn classify_protocol(transport: &str, addr: &str) -> String {
+    match port_of(addr) {
+        // HTTPS over UDP is HTTP/3
+        Some(443) if transport == "UDP" => "UDP/QUIC".to_string(),
+        Some(port) => match port_service(port) {
+            Some(service) => format!("{}/{}", transport, service),
+            None => transport.to_string(),
+        },
+        None => transport.to_string(),
+    }
+}
+
+async fn parse_connections() -> Vec<Connection> {
     let mut connections = Vec::new();
 
     // Try lsof first (works on macOS and Linux)
-    if let Ok(output) = std::process::Command::new("lsof")
-        .args(["-i", "-n", "-P"])
-        .output()
-    {
+    if let Ok(output) = run_command("lsof", &["-i", "-n", "-P"], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines().skip(1) {
             let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() < 9 {
+                log::trace!("skipping short lsof line: {}", line);
                 continue;
             }
 
             let app = parts[0].to_string();
             let pid = parts[1].to_string();
+            // lsof already reports the owner: COMMAND PID USER ...
+            let user = parts[2].to_string();
 
             // Parse the connection details
             let name_field = parts.last().unwrap_or(&"");
@@ -62,22 +338,6 @@ fn parse_connections() -> Vec<Connection> {
                 name_field.to_string()
             };
 
-            // Determine protocol
-            let proto_field = parts.get(7).unwrap_or(&"");
-            let protocol = if proto_field.contains("TCP") {
-                if conn_info.contains(":443") {
-                    "TCP/HTTPS".to_string()
-                } else if conn_info.contains(":80") {
-                    "TCP/HTTP".to_string()
-                } else {
-                    "TCP".to_string()
-                }
-            } else if proto_field.contains("UDP") {
-                "UDP".to_string()
-            } else {
-                proto_field.to_string()
-            };
-
             // Extract remote address from the connection info
             let remote = if conn_info.contains("->") {
                 conn_info.split("->").nth(1).unwrap_or(&conn_info).to_string()
@@ -85,6 +345,16 @@ fn parse_connections() -> Vec<Connection> {
                 conn_info.to_string()
             };
 
+            // Determine protocol from the remote port (or the listening port)
+            let proto_field = parts.get(7).unwrap_or(&"");
+            let protocol = if proto_field.contains("TCP") {
+                classify_protocol("TCP", &remote)
+            } else if proto_field.contains("UDP") {
+                classify_protocol("UDP", &remote)
+            } else {
+                proto_field.to_string()
+            };
+
             let state_clean = state
                 .trim_start_matches('(')
                 .trim_end_matches(')')
@@ -101,19 +371,21 @@ fn parse_connections() -> Vec<Connection> {
             connections.push(Connection {
                 pid,
                 application: app,
+                user,
                 remote_address: remote,
                 protocol,
                 state: state_abbr,
+                rtt_ms: None,
+                remote_host: None,
+                location: None,
             });
         }
     }
 
     // Fallback to ss / netstat on Linux if lsof returned nothing
     if connections.is_empty() {
-        if let Ok(output) = std::process::Command::new("ss")
-            .args(["-tunap"])
-            .output()
-        {
+        log::debug!("lsof returned no connections, falling back to ss");
+        if let Ok(output) = run_command("ss", &["-tunap"], COMMAND_TIMEOUT).await {
             let stdout = String::from_utf8_lossy(&output.stdout);
             for line in stdout.lines().skip(1) {
                 let parts: Vec<&str> = line.split_whitespace().collect();
@@ -124,7 +396,12 @@ fn parse_connections() -> Vec<Connection> {
                 let state = parts[1].to_string();
                 let local_addr = parts[4].to_string();
                 let remote_addr = parts[5].to_string();
-                let protocol = parts[0].to_uppercase();
+                // Listening sockets have no remote port; classify by the local one
+                let protocol = if port_of(&remote_addr).is_some() {
+                    classify_protocol(&parts[0].to_uppercase(), &remote_addr)
+                } else {
+                    classify_protocol(&parts[0].to_uppercase(), &local_addr)
+                };
 
                 // Extract PID from the last column
                 let pid_info = parts.get(6).unwrap_or(&"");
@@ -139,8 +416,6 @@ fn parse_connections() -> Vec<Connection> {
                     "-".to_string()
                 };
 
-                let app_name = get_process_name(&pid);
-
                 let state_abbr = match state.as_str() {
                     "ESTAB" => "ESTAB",
                     "LISTEN" => "LISTEN",
@@ -150,85 +425,418 @@ fn parse_connections() -> Vec<Connection> {
                 }
                 .to_string();
 
-                let _ = local_addr; // used implicitly for context
-
                 connections.push(Connection {
                     pid,
-                    application: app_name,
+                    application: String::new(),
+                    user: String::new(),
                     remote_address: remote_addr,
                     protocol,
                     state: state_abbr,
+                    rtt_ms: None,
+                    remote_host: None,
+                    location: None,
                 });
             }
+
+            // Name every socket's process and owner with one `ps` call
+            let pids: Vec<&str> = connections.iter().map(|c| c.pid.as_str()).collect();
+            let (names, users) = get_process_info(&pids).await;
+            for conn in &mut connections {
+                conn.application = names[&conn.pid].clone();
+                conn.user = users[&conn.pid].clone();
+            }
         }
     }
 
     connections
 }
 
+/// Print the default per-application table, limited to `limit` rows
+/// (0 = all).
+fn print_by_app(
+    connections: &[Connection],
+    total: usize,
+    external_count: usize,
+    local_count: usize,
+    geo: bool,
+    limit: usize,
+    style: TableStyle,
+) {
+    // Deduplicate by aggregating similar connections
+    let mut seen: HashMap<String, Connection> = HashMap::new();
+    for conn in connections {
+        let key = format!("{}:{}:{}", conn.pid, conn.remote_address, conn.state);
+        seen.entry(key).or_insert_with(|| conn.clone());
+    }
+    let mut display_conns: Vec<Connection> = seen.into_values().collect();
+    display_conns.sort_by(|a, b| a.application.cmp(&b.application));
+
+    // Limit display
+    let rows = display_conns.len();
+    let shown = if limit == 0 { rows } else { rows.min(limit) };
+    display_conns.truncate(shown);
+
+    println!();
+    println!("{}", "Active Network Connections:".bold());
+    println!();
+
+    if display_conns.is_empty() {
+        println!("  No active connections found.");
+    } else {
+        let table = if geo {
+            let rows: Vec<GeoRow> = display_conns
+                .into_iter()
+                .map(|connection| GeoRow {
+                    location: connection.location.as_deref().unwrap_or("-").to_string(),
+                    connection,
+                })
+                .collect();
+            display::render_table(&rows, style, GEO_DROP_ORDER)
+        } else {
+            display::render_table(&display_conns, style, NARROW_DROP_ORDER)
+        };
+        println!("{}", table);
+        if rows > shown {
+            println!(
+                "{}",
+                format!("... and {} more (use --limit 0 to show all)", rows - shown).yellow()
+            );
+        }
+        println!();
+        println!(
+            "Total connections: {} ({} shown)",
+            total.to_string().bold(),
+            shown
+        );
+        println!(
+            "External: {} | Local: {}",
+            external_count.to_string().cyan(),
+            local_count.to_string().dimmed()
+        );
+    }
+}
+
+/// The connections in `from` that `other` lacks, ordered by application.
+fn missing_from<'a>(
+    from: &'a ConnectionSet,
+    other: &ConnectionSet,
+) -> Vec<(&'a (String, String), &'a String)> {
+    let mut missing: Vec<_> = from
+        .iter()
+        .filter(|(key, _)| !other.contains_key(*key))
+        .collect();
+    missing.sort_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0)));
+    missing
+}
+
+/// Print what changed since the previous watch-mode refresh: a "+N new,
+/// -M closed" line, then the first few connections on each side.
+fn print_changes(previous: &ConnectionSet, current: &ConnectionSet) {
+    let opened = missing_from(current, previous);
+    let closed = missing_from(previous, current);
+
+    println!();
+    if opened.is_empty() && closed.is_empty() {
+        println!(
+            "{}",
+            "No connections opened or closed since the last refresh".dimmed()
+        );
+        return;
+    }
+    println!(
+        "Since the last refresh: {}, {}",
+        format!("+{} new", opened.len()).green().bold(),
+        format!("-{} closed", closed.len()).red()
+    );
+    for (sign, changes) in [("+", &opened), ("-", &closed)] {
+        for ((pid, remote), app) in changes.iter().take(CHANGES_SHOWN) {
+            let line = format!("  {} {} ({}) -> {}", sign, app, pid, remote);
+            if sign == "+" {
+                println!("{}", line.green());
+            } else {
+                println!("{}", line.dimmed());
+            }
+        }
+        if changes.len() > CHANGES_SHOWN {
+            println!(
+                "{}",
+                format!("    ... and {} more", changes.len() - CHANGES_SHOWN).dimmed()
+            );
+        }
+    }
+}
+
+/// Reverse-resolve the remote host of each connection, filling in
+/// `remote_host`. Names (or their absence) are kept in `cache` so watch
+/// mode only looks up new peers.
+async fn resolve_hostnames(
+    connections: &mut [Connection],
+    cache: &mut HashMap<String, Option<String>>,
+) {
+    let mut pending: Vec<String> = Vec::new();
+    for conn in connections.iter() {
+        let host = host_of(&conn.remote_address);
+        let Ok(ip) = host.parse::<std::net::IpAddr>() else {
+            continue;
+        };
+        if !ip.is_unspecified() && !cache.contains_key(host) && !pending.iter().any(|p| p == host) {
+            pending.push(host.to_string());
+        }
+    }
+
+    let lookups = futures_util::stream::iter(pending).map(|host| async move {
+        let addr: std::net::IpAddr = host.parse().ok()?;
+        let lookup = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&addr));
+        let name = match tokio::time::timeout(RESOLVE_TIMEOUT, lookup).await {
+            Ok(Ok(Ok(name))) if name != host => Some(name),
+            _ => None,
+        };
+        Some((host, name))
+    });
+    let resolved: Vec<_> = lookups
+        .buffer_unordered(RESOLVE_CONCURRENCY)
+        .collect()
+        .await;
+    cache.extend(resolved.into_iter().flatten());
+
+    for conn in connections.iter_mut() {
+        conn.remote_host = cache.get(host_of(&conn.remote_address)).cloned().flatten();
+    }
+}
+
+/// Open the `--geoip-db` database, or the first one installed in a
+/// standard location.
+fn open_geoip(path: Option<&str>) -> Result<GeoDb, Box<dyn std::error::Error>> {
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => geoip::default_path().ok_or(
+            "no GeoLite2 database found; download GeoLite2-City.mmdb from MaxMind \
+             into ~/.netctl/ or pass --geoip-db PATH",
+        )?,
+    };
+    log::debug!("using GeoIP database {}", path.display());
+    GeoDb::open(&path)
+}
+
+/// Fill in `location` for external peers the database knows about.
+fn locate(connections: &mut [Connection], db: &GeoDb) {
+    for conn in connections.iter_mut() {
+        if is_local_address(&conn.remote_address) {
+            continue;
+        }
+        conn.location = host_of(&conn.remote_address)
+            .parse()
+            .ok()
+            .and_then(|ip| db.lookup(ip))
+            .map(|loc| loc.to_string());
+    }
+}
+
+/// Copies of `connections` with resolved hostnames shown in place of IPs.
+fn with_hostnames(connections: &[Connection]) -> Vec<Connection> {
+    connections
+        .iter()
+        .map(|conn| {
+            let mut conn = conn.clone();
+            if let Some(ref host) = conn.remote_host {
+                conn.remote_address = match port_of(&conn.remote_address) {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host.clone(),
+                };
+            }
+            conn
+        })
+        .collect()
+}
+
 pub async fn run(
-    app_filter: Option<String>,
-    external_only: bool,
-    watch: bool,
-    interval: u64,
+    filter: ConnectionFilter,
+    opts: ConnectionsOptions,
+    style: TableStyle,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let ConnectionsOptions {
+        watch,
+        interval,
+        output: output_path,
+        by_remote,
+        group,
+        mode,
+        limit,
+        resolve,
+        geo,
+        geoip_db,
+    } = opts;
+    let geo_db = geo.then(|| open_geoip(geoip_db.as_deref())).transpose()?;
+    let mut sink = output_path.as_deref().map(RecordSink::open).transpose()?;
+    let quiet = output::is_stdout(output_path.as_deref());
+    let mut warned_no_rtt = false;
+    let mut hostnames: HashMap<String, Option<String>> = HashMap::new();
+    let mut previous: Option<ConnectionSet> = None;
+
     loop {
         // Clear screen in watch mode
-        if watch {
-            print!("\x1B[2J\x1B[H");
+        if watch && !quiet {
+            display::begin_frame();
         }
 
-        let mut connections = parse_connections();
+        let mut connections = parse_connections().await;
 
         // Apply filters
-        if let Some(ref app) = app_filter {
-            let app_lower = app.to_lowercase();
-            connections.retain(|c| c.application.to_lowercase().contains(&app_lower));
-        }
+        connections.retain(|c| filter.apps.allows(&c.application));
 
-        if external_only {
+        if filter.external_only {
             connections.retain(|c| !is_local_address(&c.remote_address));
         }
 
+        if let Some(ref user) = filter.user {
+            connections.retain(|c| &c.user == user);
+        }
+
+        if let Some(port) = filter.port {
+            connections.retain(|c| port_of(&c.remote_address) == Some(port));
+        }
+
+        if let Some(transport) = filter.transport {
+            connections.retain(|c| transport.matches(&c.protocol));
+        }
+
+        if let Some(min_rtt) = filter.min_rtt_ms {
+            match read_rtts().await {
+                Some(rtts) => {
+                    for conn in connections.iter_mut().filter(|c| c.state != "LISTEN") {
+                        conn.rtt_ms = rtts.get(&conn.remote_address).copied();
+                    }
+                    // Sockets without an RTT (UDP, listeners) can't be slow TCP peers
+                    connections.retain(|c| c.rtt_ms.is_some_and(|rtt| rtt >= min_rtt));
+                }
+                None if !warned_no_rtt => {
+                    eprintln!(
+                        "{}",
+                        "Per-connection RTT needs `ss -ti` (Linux); ignoring --min-rtt.".yellow()
+                    );
+                    warned_no_rtt = true;
+                }
+                None => {}
+            }
+        }
+
+        if resolve {
+            resolve_hostnames(&mut connections, &mut hostnames).await;
+        }
+        if let Some(ref db) = geo_db {
+            locate(&mut connections, db);
+        }
+
+        // The full filtered list, without the table's dedup and row limit
+        if mode.is_json() {
+            println!("{}", output::to_json(&connections)?);
+            return Ok(());
+        }
+
         // Count totals before truncating display
         let total = connections.len();
         let external_count = connections.iter().filter(|c| !is_local_address(&c.remote_address)).count();
         let local_count = total - external_count;
 
-        // Deduplicate by aggregating similar connections
-        let mut seen: HashMap<String, Connection> = HashMap::new();
-        for conn in &connections {
-            let key = format!("{}:{}:{}", conn.pid, conn.remote_address, conn.state);
-            seen.entry(key).or_insert_with(|| conn.clone());
+        if let Some(ref mut sink) = sink {
+            let record = ConnectionsRecord {
+                timestamp: output::timestamp(),
+                total,
+                external: external_count,
+                local: local_count,
+                connections: &connections,
+            };
+            if !sink.write(&record)? {
+                eprintln!("{}", "Output reader disconnected, stopping.".dimmed());
+                break;
+            }
         }
-        let mut display_conns: Vec<Connection> = seen.into_values().collect();
-        display_conns.sort_by(|a, b| a.application.cmp(&b.application));
 
-        // Limit display
-        let shown = display_conns.len().min(30);
-        display_conns.truncate(shown);
+        // With `--output -` stdout carries only the records
+        if quiet {
+            if !watch {
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+            continue;
+        }
 
-        println!();
-        println!("{}", "Active Network Connections:".bold());
-        println!();
+        let shown = if resolve {
+            with_hostnames(&connections)
+        } else {
+            connections.clone()
+        };
 
-        if display_conns.is_empty() {
-            println!("  No active connections found.");
+        if group {
+            let summary = group_by_app(&shown);
+            println!();
+            println!("{}", "Connections by Application:".bold());
+            println!();
+            if summary.is_empty() {
+                println!("  No active connections found.");
+            } else {
+                println!("{}", display::render_table(&summary, style, &["Protocols"]));
+                println!();
+                println!(
+                    "Applications: {} | Connections: {} (External: {} | Local: {})",
+                    summary.len().to_string().bold(),
+                    total,
+                    external_count.to_string().cyan(),
+                    local_count.to_string().dimmed()
+                );
+            }
+        } else if by_remote {
+            let groups = group_by_remote(&shown);
+            println!();
+            println!("{}", "Connections by Remote Host:".bold());
+            println!();
+            if groups.is_empty() {
+                println!("  No remote peers found.");
+            } else {
+                println!(
+                    "{}",
+                    display::render_table(&groups, style, &["Applications"])
+                );
+                println!();
+                println!(
+                    "Remote hosts: {} | Connections: {}",
+                    groups.len().to_string().bold(),
+                    groups.iter().map(|g| g.count).sum::<usize>()
+                );
+            }
         } else {
-            let table = Table::new(&display_conns)
-                .with(Style::modern())
-                .to_string();
-            println!("{}", table);
+            print_by_app(
+                &shown,
+                total,
+                external_count,
+                local_count,
+                geo,
+                limit,
+                style,
+            );
+        }
+
+        if let (Some(min_rtt), false) = (filter.min_rtt_ms, warned_no_rtt) {
             println!();
+            let slowest = connections
+                .iter()
+                .filter_map(|c| c.rtt_ms)
+                .fold(0.0, f64::max);
             println!(
-                "Total connections: {} ({} shown)",
-                total.to_string().bold(),
-                shown
+                "{}",
+                format!(
+                    "Only connections with RTT >= {:.0} ms (slowest {:.0} ms); per-connection RTT is in --output",
+                    min_rtt, slowest
+                )
+                .dimmed()
             );
-            println!(
-                "External: {} | Local: {}",
-                external_count.to_string().cyan(),
-                local_count.to_string().dimmed()
+        }
+
+        // Without root, lsof/ss only report sockets owned by the current user
+        if !is_elevated() {
+            println!();
+            display::print_unprivileged_note(
+                "results may be incomplete (other users' processes are hidden)",
             );
         }
 
@@ -236,11 +844,26 @@ pub async fn run(
             break;
         }
 
+        let current: ConnectionSet = connections
+            .iter()
+            .map(|c| {
+                (
+                    (c.pid.clone(), c.remote_address.clone()),
+                    c.application.clone(),
+                )
+            })
+            .collect();
+        if let Some(ref previous) = previous {
+            print_changes(previous, &current);
+        }
+        previous = Some(current);
+
         println!();
         println!(
             "{}",
             format!("Refreshing every {}s... (Ctrl+C to stop)", interval).dimmed()
         );
+        display::end_frame();
         tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
     }
 
@@ -1,11 +1,146 @@
 #![allow(dead_code)]
 use colored::Colorize;
-pub use crate::utils::format_rate;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tabled::settings::location::ByColumnName;
+use tabled::settings::Remove;
+use tabled::{Table, Tabled, settings::Style};
+use unicode_width::UnicodeWidthChar;
+
+static NO_CLEAR: AtomicBool = AtomicBool::new(false);
+static BINARY_UNITS: AtomicBool = AtomicBool::new(false);
+
+/// How byte counts and rates are scaled: powers of 1000 (KB, MB, GB) or
+/// of 1024 (KiB, MiB, GiB).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitMode {
+    Decimal,
+    Binary,
+}
+
+impl UnitMode {
+    pub fn from_binary_flag(binary: bool) -> Self {
+        if binary {
+            UnitMode::Binary
+        } else {
+            UnitMode::Decimal
+        }
+    }
+
+    /// The mode set by `--binary`, decimal otherwise.
+    pub fn current() -> Self {
+        Self::from_binary_flag(BINARY_UNITS.load(Ordering::Relaxed))
+    }
+
+    /// Decimal places shown at `step` units up from bytes: one from MB up,
+    /// whole numbers below.
+    fn decimals(step: usize) -> usize {
+        if step >= 2 {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Scale `value` bytes to the largest unit it reaches, returning the
+    /// scaled value, the unit, and how many steps up from bytes it went.
+    /// A value that would round up to the base (999.96 MB) moves to the next
+    /// unit, so it prints as 1.0 GB rather than 1000.0 MB.
+    pub fn scale(self, value: f64) -> (f64, &'static str, usize) {
+        let (base, units) = match self {
+            UnitMode::Decimal => (1000.0, ["B", "KB", "MB", "GB"]),
+            UnitMode::Binary => (1024.0, ["B", "KiB", "MiB", "GiB"]),
+        };
+        let mut step = 0;
+        let mut scaled = value;
+        while step + 1 < units.len() {
+            let factor = f64::powi(10.0, Self::decimals(step) as i32);
+            if (scaled * factor).round() / factor < base {
+                break;
+            }
+            step += 1;
+            scaled = value / f64::powi(base, step as i32);
+        }
+        (scaled, units[step], step)
+    }
+
+    /// Format `value` bytes in this mode's units followed by `suffix`.
+    pub fn format(self, value: f64, suffix: &str) -> String {
+        let (scaled, unit, step) = self.scale(value);
+        format!("{:.*} {}{}", Self::decimals(step), scaled, unit, suffix)
+    }
+}
+
+/// Show byte counts and rates in `mode`'s units (`--binary`).
+pub fn set_unit_mode(mode: UnitMode) {
+    BINARY_UNITS.store(mode == UnitMode::Binary, Ordering::Relaxed);
+}
+
+/// Redraw watch-mode frames in place instead of clearing the screen.
+pub fn set_no_clear(no_clear: bool) {
+    NO_CLEAR.store(no_clear, Ordering::Relaxed);
+}
+
+/// Turn colors off for `--no-color`, `NO_COLOR` (see no-color.org), or
+/// stdout that isn't a terminal, so redirected output has no ANSI escapes.
+/// A `CLICOLOR_FORCE` other than 0 keeps them in pipes.
+pub fn init_color(no_color: bool) {
+    let forced = std::env::var_os("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0");
+    let piped = !std::io::stdout().is_terminal() && !forced;
+    if no_color || std::env::var_os("NO_COLOR").is_some() || piped {
+        colored::control::set_override(false);
+    }
+}
+
+/// Start a watch-mode frame: clear the screen, or with `--no-clear` just
+/// move the cursor home so the new frame overwrites the old one.
+pub fn begin_frame() {
+    if NO_CLEAR.load(Ordering::Relaxed) {
+        print!("\x1B[H");
+    } else {
+        print!("\x1B[2J\x1B[H");
+    }
+}
+
+/// Finish a watch-mode frame, erasing whatever the previous (possibly
+/// taller) frame left below the cursor.
+pub fn end_frame() {
+    if NO_CLEAR.load(Ordering::Relaxed) {
+        print!("\x1B[J");
+    }
+    let _ = std::io::stdout().flush();
+}
+
+/// Width of the terminal stdout is attached to, or `None` when output is
+/// piped. `COLUMNS` overrides the detected size.
+pub fn terminal_width() -> Option<usize> {
+    if let Some(cols) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .filter(|&c| c > 0)
+    {
+        return Some(cols);
+    }
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe {
+        libc::isatty(libc::STDOUT_FILENO) == 1
+            && libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0
+    };
+    (ok && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+/// Inner width of the boxed sections: 57, or less on a narrow terminal.
+fn box_width() -> usize {
+    match terminal_width() {
+        Some(cols) => cols.saturating_sub(2).clamp(20, 57),
+        None => 57,
+    }
+}
 
 /// Print a boxed header section.
 pub fn print_header(title: &str) {
-    let width = 57;
-    let pad_total = width - 2 - title.len();
+    let width = box_width();
+    let pad_total = width.saturating_sub(visible_width(title));
     let pad_left = pad_total / 2;
     let pad_right = pad_total - pad_left;
 
@@ -37,25 +172,23 @@ pub fn print_header(title: &str) {
     );
 }
 
-/// Print a row inside a box.
+/// Print a row inside a box. `value` may be colored; the border after it
+/// is colored on its own so the value's reset code doesn't strip it.
 pub fn print_row(label: &str, value: &str) {
-    let width = 57;
-    let content = format!(" {:<17}{}", label, value);
-    let pad = width - content.len();
+    let width = box_width();
+    let label = format!(" {:<17}", label);
+    let pad = width.saturating_sub(visible_width(&label) + visible_width(value));
     println!(
-        "{}",
-        format!(
-            "\u{2502}{}{}\u{2502}",
-            content,
-            " ".repeat(pad)
-        )
-        .cyan()
+        "{}{}{}",
+        format!("\u{2502}{}", label).cyan(),
+        value.cyan(),
+        format!("{}\u{2502}", " ".repeat(pad)).cyan()
     );
 }
 
 /// Print an empty row inside a box.
 pub fn print_empty_row() {
-    let width = 57;
+    let width = box_width();
     println!(
         "{}",
         format!(
@@ -68,7 +201,7 @@ pub fn print_empty_row() {
 
 /// Print the bottom border of a box.
 pub fn print_footer() {
-    let width = 57;
+    let width = box_width();
     println!(
         "{}",
         format!(
@@ -79,18 +212,24 @@ pub fn print_footer() {
     );
 }
 
+/// Print a consistent note that the command lacks the privileges it needs.
+pub fn print_unprivileged_note(impact: &str) {
+    println!(
+        "{}",
+        format!("Note: running unprivileged \u{2014} {}", impact).yellow()
+    );
+}
 
-/// Format bytes into a human-readable size string.
+/// Format bytes into a human-readable size string, in decimal or (with
+/// `--binary`) binary units.
 pub fn format_bytes(bytes: u64) -> String {
-    if bytes >= 1_000_000_000 {
-        format!("{:.1} GB", bytes as f64 / 1_000_000_000.0)
-    } else if bytes >= 1_000_000 {
-        format!("{:.1} MB", bytes as f64 / 1_000_000.0)
-    } else if bytes >= 1_000 {
-        format!("{:.0} KB", bytes as f64 / 1_000.0)
-    } else {
-        format!("{} B", bytes)
-    }
+    format_scaled(bytes as f64, "")
+}
+
+/// Format `value` bytes with the current unit mode and `suffix` (such as
+/// "/s"): one decimal from MB up, whole numbers below.
+pub fn format_scaled(value: f64, suffix: &str) -> String {
+    UnitMode::current().format(value, suffix)
 }
 
 /// Format megabits per second.
@@ -128,6 +267,21 @@ pub fn quality_assessment(latency_ms: f64) -> (&'static str, String) {
     }
 }
 
+/// Combine latency, jitter, and packet loss into a 0-100 quality score.
+///
+/// The score starts at 100 and subtracts three capped penalties:
+/// - latency: 0.25 per ms above 20 ms, at most 40 (reached at 180 ms)
+/// - jitter: 1 per ms, at most 30
+/// - packet loss: 10 per percent, at most 30
+///
+/// Metrics that were not measured contribute no penalty.
+pub fn quality_score(latency_ms: f64, jitter_ms: Option<f64>, loss_pct: Option<f64>) -> u8 {
+    let latency_penalty = ((latency_ms - 20.0).max(0.0) * 0.25).min(40.0);
+    let jitter_penalty = jitter_ms.unwrap_or(0.0).clamp(0.0, 30.0);
+    let loss_penalty = (loss_pct.unwrap_or(0.0) * 10.0).clamp(0.0, 30.0);
+    (100.0 - latency_penalty - jitter_penalty - loss_penalty).round() as u8
+}
+
 /// Print a simple progress bar.
 pub fn print_progress_bar(fraction: f64, width: usize) -> String {
     let filled = (fraction * width as f64).round() as usize;
@@ -139,3 +293,166 @@ pub fn print_progress_bar(fraction: f64, width: usize) -> String {
         fraction * 100.0
     )
 }
+
+/// Table rendering style, selected with the global `--style` option.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum TableStyle {
+    /// Header separator only, no outer border
+    Compact,
+    /// Box-drawing borders around every cell
+    #[default]
+    Modern,
+    /// No borders at all, for small screens
+    Minimal,
+    /// Markdown table, for pasting into issues
+    Markdown,
+}
+
+fn styled_table<T: Tabled>(rows: &[T], style: TableStyle) -> Table {
+    let mut table = Table::new(rows);
+    match style {
+        TableStyle::Compact => table.with(Style::psql()),
+        TableStyle::Modern => table.with(Style::modern()),
+        TableStyle::Minimal => table.with(Style::blank()),
+        TableStyle::Markdown => table.with(Style::markdown()),
+    };
+    table
+}
+
+/// Columns a rendered line takes up on screen: color escape codes take
+/// none, and wide characters such as CJK and most emoji take two.
+fn visible_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1B' {
+            // Skip to the end of the escape sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            width += c.width().unwrap_or(0);
+        }
+    }
+    width
+}
+
+fn widest_line(text: &str) -> usize {
+    text.lines().map(visible_width).max().unwrap_or(0)
+}
+
+/// One "Header: value" block per row, for terminals too narrow for a table.
+fn render_stacked<T: Tabled>(rows: &[T]) -> String {
+    let headers = T::headers();
+    let label_width = headers.iter().map(|h| h.len()).max().unwrap_or(0) + 1;
+    let blocks: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            headers
+                .iter()
+                .zip(row.fields())
+                .map(|(header, value)| {
+                    format!(
+                        "{:<width$} {}",
+                        format!("{}:", header),
+                        value,
+                        width = label_width
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+    blocks.join("\n\n")
+}
+
+/// Render rows as a table in the given style.
+///
+/// When the table is wider than the terminal, columns named in
+/// `drop_order` are hidden one at a time, first to last. If it still
+/// doesn't fit, rows are printed as stacked key/value blocks instead.
+/// Markdown and piped output are never narrowed.
+pub fn render_table<T: Tabled>(rows: &[T], style: TableStyle, drop_order: &[&str]) -> String {
+    let mut table = styled_table(rows, style);
+    let rendered = table.to_string();
+    let cols = match (terminal_width(), style) {
+        (_, TableStyle::Markdown) | (None, _) => return rendered,
+        (Some(cols), _) => cols,
+    };
+    if widest_line(&rendered) <= cols {
+        return rendered;
+    }
+
+    let mut hidden = Vec::new();
+    for &column in drop_order {
+        table.with(Remove::column(ByColumnName::new(column)));
+        hidden.push(column);
+        let rendered = table.to_string();
+        if widest_line(&rendered) <= cols {
+            return format!(
+                "{}\n{}",
+                rendered,
+                format!(
+                    "Hidden to fit {} columns: {}. Widen the terminal to see all columns.",
+                    cols,
+                    hidden.join(", ")
+                )
+                .dimmed()
+            );
+        }
+    }
+
+    format!(
+        "{}\n\n{}",
+        format!(
+            "Terminal is {} columns wide; showing one entry per block.",
+            cols
+        )
+        .dimmed(),
+        render_stacked(rows)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: u64) -> String {
+        UnitMode::Decimal.format(value as f64, "")
+    }
+
+    fn binary(value: u64) -> String {
+        UnitMode::Binary.format(value as f64, "")
+    }
+
+    #[test]
+    fn decimal_units_step_at_the_rounded_base() {
+        assert_eq!(decimal(999), "999 B");
+        assert_eq!(decimal(1000), "1 KB");
+        assert_eq!(decimal(1023), "1 KB");
+        assert_eq!(decimal(1024), "1 KB");
+        assert_eq!(decimal(999_999), "1.0 MB");
+        assert_eq!(decimal(1_048_575), "1.0 MB");
+        assert_eq!(decimal(1_048_576), "1.0 MB");
+        assert_eq!(decimal(999_960_000), "1.0 GB");
+    }
+
+    #[test]
+    fn binary_units_step_at_the_rounded_base() {
+        assert_eq!(binary(999), "999 B");
+        assert_eq!(binary(1000), "1000 B");
+        assert_eq!(binary(1023), "1023 B");
+        assert_eq!(binary(1024), "1 KiB");
+        assert_eq!(binary(999_999), "977 KiB");
+        assert_eq!(binary(1_048_575), "1.0 MiB");
+        assert_eq!(binary(1_048_576), "1.0 MiB");
+    }
+
+    #[test]
+    fn largest_unit_does_not_step_further() {
+        assert_eq!(decimal(2_000_000_000_000), "2000.0 GB");
+        assert_eq!(UnitMode::Decimal.format(1_500_000.0, "/s"), "1.5 MB/s");
+    }
+}
@@ -1,6 +1,102 @@
+use crate::display::{self, TableStyle};
+use crate::dns_query;
+use crate::output::OutputMode;
+use crate::utils::{run_command, COMMAND_TIMEOUT};
 use colored::Colorize;
-use std::time::Instant;
-use tabled::{Table, settings::Style};
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Lookups running at once in batch resolve mode.
+const RESOLVE_CONCURRENCY: usize = 8;
+/// How long a single batch lookup may take before it counts as a timeout.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// DoH endpoint used by `dns resolve --doh` without `--doh-server`.
+pub const DEFAULT_DOH_SERVER: &str = "https://cloudflare-dns.com/dns-query";
+/// How long a benchmark query may take before it counts as a failure.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Record type queried by `dns benchmark`. Larger answers (TXT, DNSKEY)
+/// exercise UDP truncation and TCP fallback that tiny A lookups never hit.
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum QueryType {
+    #[default]
+    A,
+    Aaaa,
+    Txt,
+    Mx,
+    Dnskey,
+}
+
+/// Record type requested with `dns resolve --type`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Mx,
+    Txt,
+    Cname,
+    Ns,
+    Ptr,
+}
+
+impl RecordType {
+    fn code(self) -> u16 {
+        match self {
+            RecordType::A => dns_query::TYPE_A,
+            RecordType::Aaaa => dns_query::TYPE_AAAA,
+            RecordType::Mx => dns_query::TYPE_MX,
+            RecordType::Txt => dns_query::TYPE_TXT,
+            RecordType::Cname => dns_query::TYPE_CNAME,
+            RecordType::Ns => dns_query::TYPE_NS,
+            RecordType::Ptr => dns_query::TYPE_PTR,
+        }
+    }
+}
+
+impl QueryType {
+    /// The record type's number on the wire.
+    fn code(self) -> u16 {
+        match self {
+            QueryType::A => dns_query::TYPE_A,
+            QueryType::Aaaa => dns_query::TYPE_AAAA,
+            QueryType::Txt => dns_query::TYPE_TXT,
+            QueryType::Mx => dns_query::TYPE_MX,
+            QueryType::Dnskey => dns_query::TYPE_DNSKEY,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            QueryType::A => "A",
+            QueryType::Aaaa => "AAAA",
+            QueryType::Txt => "TXT",
+            QueryType::Mx => "MX",
+            QueryType::Dnskey => "DNSKEY",
+        }
+    }
+
+    /// Domains that publish this record type.
+    fn test_domains(self) -> &'static [&'static str] {
+        match self {
+            // Only DNSSEC-signed zones have DNSKEY records
+            QueryType::Dnskey => &[
+                "cloudflare.com",
+                "ietf.org",
+                "isc.org",
+                "nic.cz",
+                "internetsociety.org",
+            ],
+            _ => &[
+                "google.com",
+                "github.com",
+                "cloudflare.com",
+                "amazon.com",
+                "microsoft.com",
+            ],
+        }
+    }
+}
 
 #[derive(tabled::Tabled)]
 struct BenchmarkRow {
@@ -12,18 +108,487 @@ struct BenchmarkRow {
     success: String,
 }
 
+/// One resolver's results for `dns benchmark --json`. Latency fields are
+/// absent when no query succeeded.
+#[derive(Serialize)]
+struct BenchmarkResult {
+    server: String,
+    name: String,
+    avg_ms: Option<f64>,
+    min_ms: Option<f64>,
+    max_ms: Option<f64>,
+    stddev_ms: Option<f64>,
+    success_rate: f64,
+}
+
+impl BenchmarkResult {
+    fn new(server: &str, name: &str, latencies: &[f64], queries: usize) -> Self {
+        let n = latencies.len();
+        let avg = (n > 0).then(|| latencies.iter().sum::<f64>() / n as f64);
+        let stddev = avg.map(|avg| {
+            if n < 2 {
+                return 0.0;
+            }
+            let variance =
+                latencies.iter().map(|l| (l - avg).powi(2)).sum::<f64>() / (n - 1) as f64;
+            variance.sqrt()
+        });
+        BenchmarkResult {
+            server: server.to_string(),
+            name: name.to_string(),
+            avg_ms: avg,
+            min_ms: latencies.iter().cloned().reduce(f64::min),
+            max_ms: latencies.iter().cloned().reduce(f64::max),
+            stddev_ms: stddev,
+            success_rate: n as f64 / queries.max(1) as f64 * 100.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BenchmarkReport {
+    query_type: &'static str,
+    domains: Vec<String>,
+    results: Vec<BenchmarkResult>,
+    recommended: Option<String>,
+}
+
+/// Outcome of one lookup in batch resolve mode.
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum ResolveStatus {
+    Resolved { addresses: Vec<String> },
+    Nxdomain,
+    Timeout,
+    Error { message: String },
+}
+
+#[derive(Serialize)]
+struct ResolveResult {
+    domain: String,
+    #[serde(flatten)]
+    status: ResolveStatus,
+    elapsed_ms: f64,
+}
+
+/// Resolve through the system resolver, telling a missing name apart from
+/// other failures.
+fn lookup_classified(domain: &str) -> ResolveStatus {
+    use dns_lookup::{getaddrinfo, AddrInfoHints, LookupErrorKind};
+
+    let hints = AddrInfoHints {
+        socktype: libc::SOCK_STREAM,
+        ..AddrInfoHints::default()
+    };
+    match getaddrinfo(Some(domain), None, Some(hints)) {
+        Ok(addrs) => {
+            let mut addresses: Vec<String> = addrs
+                .filter_map(|a| a.ok())
+                .map(|a| a.sockaddr.ip().to_string())
+                .collect();
+            addresses.dedup();
+            ResolveStatus::Resolved { addresses }
+        }
+        Err(e) => match e.kind() {
+            LookupErrorKind::NoName | LookupErrorKind::NoData => ResolveStatus::Nxdomain,
+            _ => ResolveStatus::Error {
+                message: std::io::Error::from(e).to_string(),
+            },
+        },
+    }
+}
+
+async fn resolve_one(domain: String) -> ResolveResult {
+    let start = Instant::now();
+    let name = domain.clone();
+    let status = match tokio::time::timeout(
+        RESOLVE_TIMEOUT,
+        tokio::task::spawn_blocking(move || lookup_classified(&name)),
+    )
+    .await
+    {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => ResolveStatus::Error {
+            message: e.to_string(),
+        },
+        Err(_) => ResolveStatus::Timeout,
+    };
+    ResolveResult {
+        domain,
+        status,
+        elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+/// Resolve several domains concurrently and report each one's outcome.
+async fn resolve_batch(
+    domains: Vec<String>,
+    mode: OutputMode,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !mode.is_json() {
+        println!();
+        println!(
+            "{} {} domains...",
+            "Resolving".dimmed(),
+            domains.len().to_string().cyan()
+        );
+        println!();
+    }
+
+    let results: Vec<ResolveResult> = stream::iter(domains)
+        .map(resolve_one)
+        .buffered(RESOLVE_CONCURRENCY)
+        .collect()
+        .await;
+
+    if mode.is_json() {
+        println!("{}", crate::output::to_json(&results)?);
+        return Ok(());
+    }
+
+    let width = results.iter().map(|r| r.domain.len()).max().unwrap_or(0);
+    for result in &results {
+        let (icon, detail) = match result.status {
+            ResolveStatus::Resolved { ref addresses } => ("OK".green(), addresses.join(", ")),
+            ResolveStatus::Nxdomain => ("--".red(), "NXDOMAIN (no such domain)".to_string()),
+            ResolveStatus::Timeout => (
+                "!!".yellow(),
+                format!("timed out after {}s", RESOLVE_TIMEOUT.as_secs()),
+            ),
+            ResolveStatus::Error { ref message } => ("!!".red(), message.clone()),
+        };
+        println!(
+            "  {} {:<width$}  {} {}",
+            icon,
+            result.domain,
+            detail,
+            format!("({:.0} ms)", result.elapsed_ms).dimmed(),
+            width = width
+        );
+    }
+
+    let resolved = results
+        .iter()
+        .filter(|r| matches!(r.status, ResolveStatus::Resolved { .. }))
+        .count();
+    println!();
+    println!(
+        "  {} of {} domains resolved",
+        resolved.to_string().bold(),
+        results.len()
+    );
+    println!();
+    Ok(())
+}
+
+/// Search domains and the `ndots` threshold from /etc/resolv.conf.
+fn search_config() -> (Vec<String>, usize) {
+    let mut search = Vec::new();
+    let mut ndots = 1;
+    if let Ok(content) = std::fs::read_to_string("/etc/resolv.conf") {
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                // The last search/domain line wins, as in the resolver
+                Some("search") | Some("domain") => search = parts.map(|s| s.to_string()).collect(),
+                Some("options") => {
+                    for opt in parts {
+                        if let Some(n) = opt.strip_prefix("ndots:").and_then(|n| n.parse().ok()) {
+                            ndots = n;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    (search, ndots)
+}
+
+/// The fully qualified names the resolver tries for `name`, in order.
+/// Names with fewer than `ndots` dots go through the search list first.
+fn search_candidates(name: &str, search: &[String], ndots: usize) -> Vec<String> {
+    if name.ends_with('.') {
+        return vec![name.to_string()];
+    }
+    let absolute = format!("{}.", name);
+    let expanded = search
+        .iter()
+        .map(|s| format!("{}.{}.", name, s.trim_end_matches('.')));
+    if name.matches('.').count() >= ndots {
+        std::iter::once(absolute).chain(expanded).collect()
+    } else {
+        expanded.chain(std::iter::once(absolute)).collect()
+    }
+}
+
+/// Replay the resolver's search-list expansion one query at a time, showing
+/// the failed lookups spent before the real answer.
+async fn trace_search(domains: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let (search, ndots) = search_config();
+    println!();
+    println!(
+        "{} {} (ndots:{})",
+        "Search path:".bold(),
+        if search.is_empty() {
+            "(none)".to_string()
+        } else {
+            search.join(" ")
+        },
+        ndots
+    );
+
+    for domain in domains {
+        println!();
+        println!("{} {}", "Tracing".dimmed(), domain.cyan());
+        let candidates = search_candidates(&domain, &search, ndots);
+        let mut wasted_ms = 0.0;
+        let mut failures = 0;
+        let mut answered = false;
+        for (i, candidate) in candidates.iter().enumerate() {
+            let result = resolve_one(candidate.clone()).await;
+            let (icon, detail) = match result.status {
+                ResolveStatus::Resolved { ref addresses } => ("OK".green(), addresses.join(", ")),
+                ResolveStatus::Nxdomain => ("--".red(), "NXDOMAIN".to_string()),
+                ResolveStatus::Timeout => ("!!".yellow(), "timeout".to_string()),
+                ResolveStatus::Error { ref message } => ("!!".red(), message.clone()),
+            };
+            println!(
+                "  {}. {} {}  {} {}",
+                i + 1,
+                icon,
+                candidate,
+                detail,
+                format!("({:.0} ms)", result.elapsed_ms).dimmed()
+            );
+            if matches!(result.status, ResolveStatus::Resolved { .. }) {
+                answered = true;
+                break;
+            }
+            wasted_ms += result.elapsed_ms;
+            failures += 1;
+        }
+
+        println!();
+        if !answered {
+            println!("  {} No candidate resolved", "!!".red());
+        } else if failures == 0 {
+            println!("  {} Answered on the first query", "OK".green());
+        } else {
+            println!(
+                "  {} {} failed lookup(s) cost {:.0} ms before the answer",
+                "!!".yellow(),
+                failures,
+                wasted_ms
+            );
+            println!(
+                "  {}",
+                format!(
+                    "Query \"{}.\" with a trailing dot, or lower ndots, to skip the search list.",
+                    domain.trim_end_matches('.')
+                )
+                .dimmed()
+            );
+        }
+    }
+    println!();
+    Ok(())
+}
+
+/// Resolve `domain` over DNS-over-HTTPS, asking for A and AAAA at once.
+async fn resolve_doh(
+    client: &reqwest::Client,
+    url: &str,
+    domain: &str,
+) -> Result<Vec<std::net::IpAddr>, Box<dyn std::error::Error>> {
+    let (v4, v6) = futures_util::future::join(
+        dns_query::query_doh(client, url, domain, dns_query::TYPE_A),
+        dns_query::query_doh(client, url, domain, dns_query::TYPE_AAAA),
+    )
+    .await;
+    let (v4_answer, mut records) = v4?;
+    records.extend(v6?.1);
+    let ips: Vec<std::net::IpAddr> = records
+        .iter()
+        .filter(|r| r.rtype == dns_query::TYPE_A || r.rtype == dns_query::TYPE_AAAA)
+        .filter_map(|r| r.data.parse().ok())
+        .collect();
+    if ips.is_empty() && v4_answer == dns_query::Answer::NxDomain {
+        return Err("no such domain (NXDOMAIN)".into());
+    }
+    Ok(ips)
+}
+
+/// Query one record type for `domain`, over DoH when `doh` is set and
+/// otherwise from the first system nameserver. PTR lookups take an IP.
+async fn lookup_records(
+    domain: &str,
+    rtype: RecordType,
+    doh: Option<(&reqwest::Client, &str)>,
+) -> Result<Vec<dns_query::Record>, Box<dyn std::error::Error>> {
+    let name = match (rtype, domain.parse::<std::net::IpAddr>()) {
+        (RecordType::Ptr, Ok(ip)) => dns_query::reverse_name(ip),
+        (RecordType::Ptr, Err(_)) if !domain.ends_with(".arpa") => {
+            return Err("PTR lookups take an IP address".into())
+        }
+        _ => domain.to_string(),
+    };
+    let (answer, records) = match doh {
+        Some((client, url)) => dns_query::query_doh(client, url, &name, rtype.code()).await?,
+        None => {
+            let server = get_system_dns()
+                .and_then(|s| s.parse().ok())
+                .ok_or("no nameserver found in /etc/resolv.conf")?;
+            dns_query::query_records(server, &name, rtype.code(), RESOLVE_TIMEOUT).await?
+        }
+    };
+    match answer {
+        dns_query::Answer::NxDomain => Err("no such domain (NXDOMAIN)".into()),
+        dns_query::Answer::Rcode(code) => Err(format!("server returned rcode {}", code).into()),
+        _ => Ok(records),
+    }
+}
+
+/// Print records grouped by type, in the order each type first appears
+/// (so a CNAME chain is listed before the records it leads to).
+fn print_records(
+    domain: &str,
+    rtype: RecordType,
+    result: Result<Vec<dns_query::Record>, String>,
+    elapsed: Duration,
+) {
+    let records = match result {
+        Ok(records) => records,
+        Err(e) => {
+            println!("  {} Could not resolve {}: {}", "Error:".red(), domain, e);
+            return;
+        }
+    };
+    println!(
+        "{} {} ({}) -> ",
+        "DNS Records:".bold(),
+        domain.cyan(),
+        dns_query::type_name(rtype.code())
+    );
+    println!();
+
+    let mut types: Vec<u16> = Vec::new();
+    for record in &records {
+        if !types.contains(&record.rtype) {
+            types.push(record.rtype);
+        }
+    }
+    if types.is_empty() {
+        println!("  {}", "No records of this type".yellow());
+    }
+    for rtype in types {
+        println!("  {}:", dns_query::type_name(rtype));
+        for record in records.iter().filter(|r| r.rtype == rtype) {
+            println!(
+                "    {}  {}",
+                record.data.green(),
+                format!("{} ttl {}s", record.name, record.ttl).dimmed()
+            );
+        }
+    }
+
+    println!();
+    println!("  Resolved in {:.1} ms", elapsed.as_secs_f64() * 1000.0);
+    println!("  Records: {}", records.len());
+}
+
 /// Resolve a domain using the system resolver and display results.
-pub async fn resolve(domain: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// A comma-separated list (or `--json`) switches to batch mode, `trace`
+/// walks the search list instead, and `rtype` queries one record type.
+pub async fn resolve(
+    domain: &str,
+    mode: OutputMode,
+    trace: bool,
+    doh: Option<&str>,
+    rtype: Option<RecordType>,
+) -> Result<(), Box<dyn std::error::Error>> {
     use dns_lookup::lookup_host;
 
+    let domains: Vec<String> = domain
+        .split(',')
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    if trace {
+        return trace_search(domains).await;
+    }
+    if let Some(rtype) = rtype {
+        let client = reqwest::Client::builder()
+            .timeout(RESOLVE_TIMEOUT)
+            .build()?;
+        for domain in &domains {
+            let via = match doh {
+                Some(url) => url.to_string(),
+                None => get_system_dns().unwrap_or_default(),
+            };
+            println!();
+            println!(
+                "{} {} {}...",
+                "Resolving".dimmed(),
+                domain.cyan(),
+                format!("via {}", via).dimmed()
+            );
+            println!();
+            let start = Instant::now();
+            let result = lookup_records(domain, rtype, doh.map(|url| (&client, url)))
+                .await
+                .map_err(|e| e.to_string());
+            print_records(domain, rtype, result, start.elapsed());
+        }
+        println!();
+        return Ok(());
+    }
+    if let Some(url) = doh {
+        let client = reqwest::Client::builder()
+            .timeout(RESOLVE_TIMEOUT)
+            .build()?;
+        for domain in &domains {
+            println!();
+            println!(
+                "{} {} {}...",
+                "Resolving".dimmed(),
+                domain.cyan(),
+                format!("via {}", url).dimmed()
+            );
+            println!();
+            let start = Instant::now();
+            let result = resolve_doh(&client, url, domain)
+                .await
+                .map_err(|e| e.to_string());
+            print_resolution(domain, result, start.elapsed());
+        }
+        println!();
+        return Ok(());
+    }
+    if domains.len() > 1 || mode.is_json() {
+        return resolve_batch(domains, mode).await;
+    }
+
     println!();
     println!("{} {}...", "Resolving".dimmed(), domain.cyan());
     println!();
 
     let start = Instant::now();
-    match lookup_host(domain) {
+    let result = lookup_host(domain).map_err(|e| e.to_string());
+    print_resolution(domain, result, start.elapsed());
+
+    println!();
+    Ok(())
+}
+
+/// Print the IPv4/IPv6 breakdown and timing of one lookup.
+fn print_resolution(
+    domain: &str,
+    result: Result<Vec<std::net::IpAddr>, String>,
+    elapsed: Duration,
+) {
+    match result {
         Ok(ips) => {
-            let elapsed = start.elapsed();
             println!("{} {} -> ", "DNS Resolution:".bold(), domain.cyan());
             println!();
 
@@ -63,47 +628,315 @@ pub async fn resolve(domain: &str) -> Result<(), Box<dyn std::error::Error>> {
             );
         }
     }
+}
+
+/// Look up the hostname(s) published for an IP address. Queries PTR
+/// records from the system nameserver, falling back to the system
+/// resolver (which also consults /etc/hosts) when DNS has none.
+pub async fn reverse(ip: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let addr: std::net::IpAddr = ip
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid IPv4 or IPv6 address", ip))?;
+    let name = dns_query::reverse_name(addr);
+
+    println!();
+    println!(
+        "{} {}...",
+        "Reverse lookup".dimmed(),
+        addr.to_string().cyan()
+    );
+    println!();
+
+    let start = Instant::now();
+    let server: Option<std::net::IpAddr> = get_system_dns().and_then(|s| s.parse().ok());
+    let mut hostnames: Vec<String> = Vec::new();
+    let mut error = None;
+    match server {
+        Some(server) => {
+            match dns_query::query_records(server, &name, dns_query::TYPE_PTR, RESOLVE_TIMEOUT)
+                .await
+            {
+                Ok((dns_query::Answer::Rcode(code), _)) => {
+                    error = Some(format!("server returned rcode {}", code))
+                }
+                Ok((_, records)) => hostnames.extend(
+                    records
+                        .into_iter()
+                        .filter(|r| r.rtype == dns_query::TYPE_PTR)
+                        .map(|r| r.data),
+                ),
+                Err(e) => error = Some(e.to_string()),
+            }
+        }
+        None => error = Some("no nameserver found in /etc/resolv.conf".to_string()),
+    }
+    if hostnames.is_empty() {
+        if let Ok(host) = dns_lookup::lookup_addr(&addr) {
+            hostnames.push(host);
+            error = None;
+        }
+    }
+    let elapsed = start.elapsed();
+
+    if let Some(e) = error {
+        return Err(format!("could not reverse-resolve {}: {}", addr, e).into());
+    }
 
+    println!("{} {} -> ", "Reverse DNS:".bold(), addr.to_string().cyan());
+    println!();
+    if hostnames.is_empty() {
+        println!("  {} No PTR record for {}", "--".yellow(), addr);
+        println!(
+            "  {}",
+            "Many residential, CGNAT, and cloud addresses have no reverse DNS.".dimmed()
+        );
+    }
+    for host in &hostnames {
+        println!("  {}", host.trim_end_matches('.').green());
+    }
+    println!();
+    println!("  Resolved in {:.1} ms", elapsed.as_secs_f64() * 1000.0);
+    println!("  {}", format!("Queried {}", name).dimmed());
     println!();
     Ok(())
 }
 
+/// (service, program, args) for each DNS cache this platform may run.
+fn flush_methods() -> &'static [(&'static str, &'static str, &'static [&'static str])] {
+    if cfg!(target_os = "macos") {
+        &[
+            ("Directory Services", "dscacheutil", &["-flushcache"]),
+            ("mDNSResponder", "killall", &["-HUP", "mDNSResponder"]),
+        ]
+    } else {
+        &[
+            ("systemd-resolved", "resolvectl", &["flush-caches"]),
+            // Pre-247 systemd only ships the old name
+            ("systemd-resolved", "systemd-resolve", &["--flush-caches"]),
+            ("nscd", "nscd", &["-i", "hosts"]),
+        ]
+    }
+}
+
+/// Flush every local DNS cache that is installed, returning each attempt's
+/// service and outcome in order. Empty when no cache is installed.
+pub async fn flush_dns_cache() -> Vec<(&'static str, Result<(), String>)> {
+    use crate::error::NetctlError;
+
+    let mut results: Vec<(&'static str, Result<(), String>)> = Vec::new();
+    for (service, program, args) in flush_methods() {
+        if results.iter().any(|(s, r)| s == service && r.is_ok()) {
+            continue;
+        }
+        match run_command(program, args, COMMAND_TIMEOUT).await {
+            Ok(_) => results.push((service, Ok(()))),
+            // Not every system runs every cache; only report real failures
+            Err(NetctlError::NotFound { .. }) => {
+                log::debug!("{} not installed, skipping {}", program, service)
+            }
+            Err(e) => results.push((service, Err(e.to_string()))),
+        }
+    }
+    results
+}
+
 /// Flush the DNS cache (platform-specific).
 pub async fn flush() -> Result<(), Box<dyn std::error::Error>> {
     println!();
     println!("{}", "Flushing DNS cache...".dimmed());
+    println!();
 
-    // macOS
-    let macos_result = std::process::Command::new("dscacheutil")
-        .args(["-flushcache"])
-        .output();
+    let results = flush_dns_cache().await;
+    for (service, result) in &results {
+        match result {
+            Ok(()) => println!("  {} Flushed via {}", "OK".green(), service),
+            Err(e) => println!("  {} {}: {}", "!!".yellow(), service, e),
+        }
+    }
 
-    let _ = std::process::Command::new("killall")
-        .args(["-HUP", "mDNSResponder"])
-        .output();
+    if !results.iter().any(|(_, r)| r.is_ok()) {
+        println!("  {} Could not flush DNS cache", "!!".yellow());
+        if results.is_empty() {
+            let tried: Vec<&str> = flush_methods()
+                .iter()
+                .map(|(_, program, _)| *program)
+                .collect();
+            println!(
+                "  {}",
+                format!("No local DNS cache found (tried {})", tried.join(", ")).dimmed()
+            );
+        } else if !crate::utils::is_elevated() {
+            print!("  ");
+            crate::display::print_unprivileged_note("flushing the system cache requires sudo");
+        }
+        println!();
+        return Err("no DNS cache flush method succeeded".into());
+    }
 
-    // Linux (systemd-resolved)
-    let linux_result = std::process::Command::new("resolvectl")
-        .args(["flush-caches"])
-        .output();
+    println!();
+    Ok(())
+}
 
-    // Linux alternative
-    let _ = std::process::Command::new("systemd-resolve")
-        .args(["--flush-caches"])
-        .output();
+/// Cached entries listed before the rest are summarized as a count.
+const CACHE_ENTRIES_SHOWN: usize = 20;
 
-    let success = macos_result.map(|o| o.status.success()).unwrap_or(false)
-        || linux_result.map(|o| o.status.success()).unwrap_or(false);
+/// What `resolvectl statistics` reports about the resolver cache.
+#[derive(Default)]
+struct CacheStats {
+    size: Option<u64>,
+    hits: Option<u64>,
+    misses: Option<u64>,
+}
 
-    if success {
-        println!("  {} DNS cache flushed successfully", "OK".green());
-    } else {
-        println!(
-            "  {} Could not flush DNS cache (may require sudo)",
-            "!!".yellow()
-        );
+/// Parse the Cache section of `resolvectl statistics`, e.g.
+/// "  Current Cache Size: 42" and "          Cache Hits: 100".
+fn parse_resolved_stats(stdout: &str) -> CacheStats {
+    let mut stats = CacheStats::default();
+    for line in stdout.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().parse().ok();
+        match key.trim() {
+            "Current Cache Size" => stats.size = value,
+            "Cache Hits" => stats.hits = value,
+            "Cache Misses" => stats.misses = value,
+            _ => {}
+        }
     }
+    stats
+}
 
+/// Entries from `resolvectl show-cache` (systemd 252+), which groups them
+/// under "Scope ..." headers.
+async fn resolved_cache_entries() -> Option<Vec<String>> {
+    let output = run_command("resolvectl", &["show-cache"], COMMAND_TIMEOUT)
+        .await
+        .map_err(|e| log::debug!("{}", e))
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(
+        stdout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with("Scope") && *l != "No entries.")
+            .map(|l| l.to_string())
+            .collect(),
+    )
+}
+
+/// Name the resolver in use, for the "not supported" message.
+fn resolver_name() -> String {
+    if cfg!(target_os = "macos") {
+        return "mDNSResponder".to_string();
+    }
+    match get_system_dns() {
+        Some(ip) if ip == "127.0.0.53" => "systemd-resolved".to_string(),
+        Some(ip) if ip.starts_with("127.") => format!("the local resolver at {}", ip),
+        Some(ip) => format!("{} {}", ip, identify_dns_server(&ip)),
+        None => "this system".to_string(),
+    }
+}
+
+/// Show what the system resolver currently has cached, where it says.
+pub async fn cache() -> Result<(), Box<dyn std::error::Error>> {
+    println!();
+    println!("{}", "Inspecting DNS cache...".dimmed());
+
+    let stats = match run_command("resolvectl", &["statistics"], COMMAND_TIMEOUT).await {
+        Ok(output) => parse_resolved_stats(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => {
+            log::debug!("{}", e);
+            println!();
+            println!(
+                "  {} Cache inspection not supported for {}.",
+                "--".yellow(),
+                resolver_name()
+            );
+            if cfg!(target_os = "macos") {
+                println!(
+                    "{}",
+                    "  mDNSResponder only dumps its cache to the system log: run \
+                     `sudo killall -INFO mDNSResponder`, then \
+                     `log show --last 1m --predicate 'process == \"mDNSResponder\"'`."
+                        .dimmed()
+                );
+            }
+            println!();
+            return Ok(());
+        }
+    };
+
+    println!();
+    println!("{}", "DNS Cache (systemd-resolved):".bold());
+    println!();
+    let show = |n: Option<u64>| n.map_or_else(|| "-".to_string(), |n| n.to_string());
+    println!("  Entries:     {}", show(stats.size));
+    println!("  Hits:        {}", show(stats.hits));
+    println!("  Misses:      {}", show(stats.misses));
+    let hit_rate = match (stats.hits, stats.misses) {
+        (Some(hits), Some(misses)) if hits + misses > 0 => {
+            Some(hits as f64 / (hits + misses) as f64 * 100.0)
+        }
+        _ => None,
+    };
+    if let Some(rate) = hit_rate {
+        println!("  Hit rate:    {:.1}%", rate);
+    }
+
+    match resolved_cache_entries().await {
+        Some(entries) if !entries.is_empty() => {
+            println!();
+            println!(
+                "{}",
+                format!(
+                    "Cached entries (showing {} of {}):",
+                    entries.len().min(CACHE_ENTRIES_SHOWN),
+                    entries.len()
+                )
+                .bold()
+            );
+            for entry in entries.iter().take(CACHE_ENTRIES_SHOWN) {
+                println!("  {}", entry);
+            }
+        }
+        Some(_) => {}
+        None => {
+            println!();
+            let reason = if crate::utils::is_elevated() {
+                "listing entries needs systemd 252 or newer"
+            } else {
+                "listing entries requires sudo"
+            };
+            println!(
+                "{}",
+                format!("  Cached entries unavailable: {}.", reason).dimmed()
+            );
+        }
+    }
+
+    println!();
+    match (stats.size, hit_rate) {
+        (Some(0), _) => println!(
+            "{}",
+            "The cache is empty, so flushing would change nothing.".dimmed()
+        ),
+        (Some(size), Some(rate)) => println!(
+            "{}",
+            format!(
+                "A flush drops these {} entries. With a {:.0}% hit rate, lookups will be \
+                 slower until the cache refills; it only helps if an entry above is stale.",
+                size, rate
+            )
+            .dimmed()
+        ),
+        _ => println!(
+            "{}",
+            "A flush only helps if a cached answer is stale; compare with `netctl dns resolve`."
+                .dimmed()
+        ),
+    }
     println!();
     Ok(())
 }
@@ -131,10 +964,7 @@ pub async fn servers() -> Result<(), Box<dyn std::error::Error>> {
 
     // macOS: scutil --dns
     if !found {
-        if let Ok(output) = std::process::Command::new("scutil")
-            .args(["--dns"])
-            .output()
-        {
+        if let Ok(output) = run_command("scutil", &["--dns"], COMMAND_TIMEOUT).await {
             let stdout = String::from_utf8_lossy(&output.stdout);
             for line in stdout.lines() {
                 if line.contains("nameserver[") {
@@ -158,80 +988,130 @@ pub async fn servers() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 /// Benchmark multiple well-known DNS resolvers.
-pub async fn benchmark() -> Result<(), Box<dyn std::error::Error>> {
-    let resolvers = vec![
+pub async fn benchmark(
+    explain: bool,
+    mode: OutputMode,
+    query_type: QueryType,
+    servers: &[String],
+    domains: &[String],
+    style: TableStyle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let resolvers = [
         ("1.1.1.1", "Cloudflare"),
         ("8.8.8.8", "Google"),
         ("208.67.222.222", "Cisco/OpenDNS"),
         ("9.9.9.9", "Quad9"),
     ];
+    if let Some(bad) = servers
+        .iter()
+        .find(|s| s.parse::<std::net::IpAddr>().is_err())
+    {
+        return Err(format!("'{}' is not a resolver IP address", bad).into());
+    }
 
     // Also include system default
     let system_dns = get_system_dns();
 
-    println!();
-    println!("{}", "Running DNS benchmark...".dimmed());
-    println!();
+    if !mode.is_json() {
+        println!();
+        println!("{}", "Running DNS benchmark...".dimmed());
+        println!();
+    }
 
-    let test_domains = vec![
-        "google.com",
-        "github.com",
-        "cloudflare.com",
-        "amazon.com",
-        "microsoft.com",
-    ];
+    let test_domains: Vec<String> = if domains.is_empty() {
+        query_type
+            .test_domains()
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    } else {
+        domains.to_vec()
+    };
+    let test_domains = std::sync::Arc::new(test_domains);
+
+    // Resolvers in list order, then the system default last
+    let mut targets: Vec<(String, String)> = if servers.is_empty() {
+        resolvers
+            .iter()
+            .map(|(ip, name)| (ip.to_string(), name.to_string()))
+            .collect()
+    } else {
+        servers
+            .iter()
+            .map(|ip| {
+                let name = identify_dns_server(ip);
+                (ip.clone(), name.trim_matches(['(', ')']).to_string())
+            })
+            .collect()
+    };
+    if let Some(sys_dns) = system_dns {
+        targets.push((sys_dns, "System".to_string()));
+    }
+
+    // Every server is independent, so test them all at once
+    let handles: Vec<_> = targets
+        .iter()
+        .map(|(ip, _)| {
+            let ip = ip.clone();
+            let test_domains = test_domains.clone();
+            tokio::spawn(async move {
+                let domains: Vec<&str> = test_domains.iter().map(String::as_str).collect();
+                benchmark_dns_server(&ip, &domains, query_type).await
+            })
+        })
+        .collect();
+    let results = futures_util::future::join_all(handles).await;
 
     let mut rows: Vec<BenchmarkRow> = Vec::new();
+    let mut json_results: Vec<BenchmarkResult> = Vec::new();
     let mut best_latency = f64::MAX;
     let mut best_server = String::new();
+    let mut system_result: Option<(String, f64)> = None;
 
-    for (server_ip, server_name) in &resolvers {
-        let (avg, success_rate) =
-            benchmark_dns_server(server_ip, &test_domains).await;
+    for ((server_ip, server_name), result) in targets.iter().zip(results) {
+        let latencies = result.unwrap_or_default();
+        let stats = BenchmarkResult::new(server_ip, server_name, &latencies, test_domains.len());
+        let avg = stats.avg_ms.unwrap_or(0.0);
+        let server = format!("{} ({})", server_ip, server_name);
 
         if avg < best_latency && avg > 0.0 {
             best_latency = avg;
-            best_server = format!("{} ({})", server_ip, server_name);
+            best_server = server.clone();
+        }
+        if server_name == "System" && avg > 0.0 {
+            system_result = Some((server.clone(), avg));
         }
 
         rows.push(BenchmarkRow {
-            server: format!("{} ({})", server_ip, server_name),
+            server,
             avg_latency: if avg > 0.0 {
                 format!("{:.0} ms", avg)
             } else {
                 "timeout".to_string()
             },
-            success: format!("{:.0}%", success_rate),
+            success: format!("{:.0}%", stats.success_rate),
         });
+        json_results.push(stats);
     }
 
-    // Test system DNS if available
-    if let Some(sys_dns) = system_dns {
-        let (avg, success_rate) =
-            benchmark_dns_server(&sys_dns, &test_domains).await;
-
-        if avg < best_latency && avg > 0.0 {
-            let _ = best_latency;
-            best_server = format!("{} (System)", sys_dns);
-        }
-
-        rows.push(BenchmarkRow {
-            server: format!("{} (System)", sys_dns),
-            avg_latency: if avg > 0.0 {
-                format!("{:.0} ms", avg)
-            } else {
-                "timeout".to_string()
-            },
-            success: format!("{:.0}%", success_rate),
-        });
+    if mode.is_json() {
+        let report = BenchmarkReport {
+            query_type: query_type.as_str(),
+            domains: test_domains.to_vec(),
+            results: json_results,
+            recommended: (!best_server.is_empty()).then_some(best_server),
+        };
+        println!("{}", crate::output::to_json(&report)?);
+        return Ok(());
     }
 
-    println!("{}", "DNS Benchmark Results:".bold());
+    println!(
+        "{}",
+        format!("DNS Benchmark Results ({} queries):", query_type.as_str()).bold()
+    );
     println!();
 
-    let table = Table::new(&rows)
-        .with(Style::modern())
-        .to_string();
+    let table = display::render_table(&rows, style, &["Success"]);
     println!("{}", table);
 
     println!();
@@ -239,60 +1119,213 @@ pub async fn benchmark() -> Result<(), Box<dyn std::error::Error>> {
         "Recommendation: Use {} for best performance",
         best_server.green()
     );
+
+    if explain {
+        let best = (!best_server.is_empty()).then_some((best_server.as_str(), best_latency));
+        let system = system_result.as_ref().map(|(name, ms)| (name.as_str(), *ms));
+        crate::explain::print(&crate::explain::dns_benchmark(best, system));
+    }
     println!();
 
     Ok(())
 }
 
-async fn benchmark_dns_server(server: &str, domains: &[&str]) -> (f64, f64) {
-    let mut latencies = Vec::new();
-    let mut successes = 0;
-    let total = domains.len();
-
-    for domain in domains {
-        // Use dig/nslookup to query the specific DNS server
+/// Query each domain through `server` and return the latency (ms) of every
+/// query that came back with records.
+async fn benchmark_dns_server(server: &str, domains: &[&str], query_type: QueryType) -> Vec<f64> {
+    let Ok(ip) = server.parse::<std::net::IpAddr>() else {
+        log::debug!("{} is not an IP address, skipping", server);
+        return Vec::new();
+    };
+    let lookups = domains.iter().map(|domain| async move {
         let start = Instant::now();
-        let result = std::process::Command::new("dig")
-            .args([format!("@{}", server), domain.to_string(), "+short".to_string(), "+time=2".to_string(), "+tries=1".to_string()])
-            .output();
-
+        let result = dns_query::query(ip, domain, query_type.code(), QUERY_TIMEOUT).await;
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
         match result {
-            Ok(output) => {
-                let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                if output.status.success() && !stdout.trim().is_empty() {
-                    latencies.push(elapsed);
-                    successes += 1;
-                }
+            Ok(answer) if answer.has_records() => Some(elapsed),
+            Ok(answer) => {
+                log::debug!(
+                    "{} {} via {}: {:?}",
+                    query_type.as_str(),
+                    domain,
+                    server,
+                    answer
+                );
+                None
             }
-            Err(_) => {
-                // Try nslookup as fallback
-                let start2 = Instant::now();
-                if let Ok(output) = std::process::Command::new("nslookup")
-                    .args([domain, server])
-                    .output()
-                {
-                    let elapsed = start2.elapsed().as_secs_f64() * 1000.0;
-                    if output.status.success() {
-                        latencies.push(elapsed);
-                        successes += 1;
-                    }
-                }
+            Err(e) => {
+                log::debug!("{} {} via {}: {}", query_type.as_str(), domain, server, e);
+                None
             }
         }
-    }
+    });
+    futures_util::future::join_all(lookups)
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
 
-    let avg = if latencies.is_empty() {
-        0.0
-    } else {
-        latencies.iter().sum::<f64>() / latencies.len() as f64
+/// Names whose authoritative servers answer with the address of the
+/// resolver that asked them, revealing where queries really egress.
+const EGRESS_PROBES: [(&str, u16); 2] = [
+    ("whoami.akamai.net", dns_query::TYPE_A),
+    ("o-o.myaddr.l.google.com", dns_query::TYPE_TXT),
+];
+/// Rounds of egress probes; large resolver pools may answer from several IPs.
+const EGRESS_ROUNDS: usize = 3;
+
+/// Upstream servers behind a local stub resolver, per `resolvectl dns`
+/// (e.g. "Link 5 (wg0): 10.2.0.1").
+async fn stub_upstreams() -> Vec<String> {
+    let Ok(output) = run_command("resolvectl", &["dns"], COMMAND_TIMEOUT).await else {
+        return Vec::new();
     };
-    let success_rate = (successes as f64 / total as f64) * 100.0;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut servers: Vec<String> = Vec::new();
+    for line in stdout.lines() {
+        let Some((_, list)) = line.split_once(": ") else {
+            continue;
+        };
+        for server in list.split_whitespace() {
+            // Strip "%ifindex" and "#server-name" decorations
+            let server = server.split(['%', '#']).next().unwrap_or(server);
+            if !servers.iter().any(|s| s == server) {
+                servers.push(server.to_string());
+            }
+        }
+    }
+    servers
+}
+
+/// Resolver egress addresses reported by the whoami services.
+async fn egress_resolvers(server: std::net::IpAddr) -> Vec<String> {
+    let probes = (0..EGRESS_ROUNDS)
+        .flat_map(|_| EGRESS_PROBES)
+        .map(|(name, qtype)| async move {
+            dns_query::query_records(server, name, qtype, RESOLVE_TIMEOUT)
+                .await
+                .map(|(_, records)| records)
+                .unwrap_or_default()
+        });
+    let mut seen: Vec<String> = Vec::new();
+    for record in futures_util::future::join_all(probes)
+        .await
+        .into_iter()
+        .flatten()
+    {
+        // TXT answers are quoted and may add an "edns0-client-subnet" string
+        for value in record.data.split_whitespace() {
+            let value = value.trim_matches('"');
+            if value.parse::<std::net::IpAddr>().is_ok() && !seen.iter().any(|s| s == value) {
+                seen.push(value.to_string());
+            }
+        }
+    }
+    seen
+}
 
-    (avg, success_rate)
+/// Check whether DNS queries leave through the VPN tunnel. Compares the
+/// interface each configured resolver is routed over with the tunnel, and
+/// lists the resolver egress IPs that public whoami services observe.
+pub async fn leak_test() -> Result<(), Box<dyn std::error::Error>> {
+    println!();
+    println!("{}", "Running DNS leak test...".dimmed());
+
+    let tunnel = crate::vpn::active_interface().await;
+    let system = get_system_dns().ok_or("no nameserver found in /etc/resolv.conf")?;
+    let system_ip: std::net::IpAddr = system
+        .parse()
+        .map_err(|_| format!("nameserver '{}' is not an IP address", system))?;
+
+    // A local stub (systemd-resolved, dnsmasq) hides the real upstreams
+    let mut upstreams = vec![system.clone()];
+    if system_ip.is_loopback() {
+        let behind_stub = stub_upstreams().await;
+        if !behind_stub.is_empty() {
+            upstreams = behind_stub;
+        }
+    }
+    let mut routes: Vec<(String, Option<String>)> = Vec::new();
+    for server in upstreams {
+        let iface = crate::utils::route_interface(&server).await;
+        routes.push((server, iface));
+    }
+    let egress = egress_resolvers(system_ip).await;
+
+    println!();
+    println!("{}", "DNS Leak Test:".bold());
+    println!();
+    match &tunnel {
+        Some(iface) => {
+            let ip = crate::vpn::get_interface_ip(iface)
+                .await
+                .unwrap_or_default();
+            println!("  VPN tunnel:  {} {}", iface.cyan(), ip.dimmed());
+        }
+        None => println!("  VPN tunnel:  {}", "none detected".dimmed()),
+    }
+    println!();
+    println!("  Resolvers:");
+    let mut leaking: Vec<&str> = Vec::new();
+    for (server, iface) in &routes {
+        let via = iface.as_deref().unwrap_or("unknown");
+        let status = match (&tunnel, iface) {
+            (Some(tunnel), Some(iface)) if iface == tunnel => "tunnel".green().to_string(),
+            // A stub whose upstreams are unknown can't be judged either way
+            (_, Some(iface)) if iface == "lo" || iface == "lo0" => "local".dimmed().to_string(),
+            (Some(_), _) => {
+                leaking.push(server);
+                "outside tunnel".red().to_string()
+            }
+            (None, _) => "direct".yellow().to_string(),
+        };
+        println!(
+            "    {} {} {:<8} {}",
+            format!("{:<18}", server).cyan(),
+            "via".dimmed(),
+            via,
+            status
+        );
+    }
+    println!();
+    println!("  Egress seen by DNS services:");
+    if egress.is_empty() {
+        println!("    {}", "no answer from whoami services".dimmed());
+    }
+    for ip in &egress {
+        println!("    {} {}", ip, identify_dns_server(ip).dimmed());
+    }
+
+    println!();
+    match (&tunnel, leaking.is_empty()) {
+        (Some(iface), true) => println!(
+            "Verdict: {} No leak detected: DNS goes through {}",
+            "OK".green(),
+            iface
+        ),
+        (Some(_), false) => {
+            println!(
+                "Verdict: {} DNS LEAK: {} reached outside the tunnel",
+                "!!".red(),
+                leaking.join(", ")
+            );
+            println!(
+                "{}",
+                "Your ISP (or network operator) can see the names you look up.".dimmed()
+            );
+        }
+        (None, _) => println!(
+            "Verdict: {} No VPN active: DNS goes directly to your configured resolver",
+            "--".yellow()
+        ),
+    }
+    println!();
+    Ok(())
 }
 
-fn get_system_dns() -> Option<String> {
+/// First nameserver in /etc/resolv.conf.
+pub fn get_system_dns() -> Option<String> {
     if let Ok(content) = std::fs::read_to_string("/etc/resolv.conf") {
         for line in content.lines() {
             if line.starts_with("nameserver") {
@@ -0,0 +1,358 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// UDP payload size advertised with EDNS0, the widely used safe default that
+/// avoids IP fragmentation. Larger answers come back truncated and are
+/// retried over TCP.
+const EDNS_UDP_SIZE: u16 = 1232;
+
+/// What a server said about one query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Answer {
+    /// NOERROR with this many answer records
+    Records(u16),
+    /// NOERROR but no records of the requested type
+    NoData,
+    NxDomain,
+    /// Any other response code (SERVFAIL, REFUSED, ...)
+    Rcode(u8),
+}
+
+impl Answer {
+    pub fn has_records(self) -> bool {
+        matches!(self, Answer::Records(n) if n > 0)
+    }
+}
+
+/// Record types this module can decode from answers.
+pub const TYPE_A: u16 = 1;
+pub const TYPE_NS: u16 = 2;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_MX: u16 = 15;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+/// Queried by the benchmark, which only counts answers; never decoded.
+pub const TYPE_DNSKEY: u16 = 48;
+
+/// One decoded answer record.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub rtype: u16,
+    pub name: String,
+    pub ttl: u32,
+    /// Presentation form: an address, a name, "10 mx.example.com", or the
+    /// quoted strings of a TXT record
+    pub data: String,
+}
+
+/// Mnemonic for a record type number.
+pub fn type_name(rtype: u16) -> String {
+    match rtype {
+        TYPE_A => "A".to_string(),
+        TYPE_NS => "NS".to_string(),
+        TYPE_CNAME => "CNAME".to_string(),
+        TYPE_PTR => "PTR".to_string(),
+        TYPE_MX => "MX".to_string(),
+        TYPE_TXT => "TXT".to_string(),
+        TYPE_AAAA => "AAAA".to_string(),
+        other => format!("TYPE{}", other),
+    }
+}
+
+/// The name queried for reverse lookups of `ip`, e.g. "4.3.2.1.in-addr.arpa".
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(v6) => {
+            let mut name = String::new();
+            for byte in v6.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0x0f, byte >> 4));
+            }
+            name + "ip6.arpa"
+        }
+    }
+}
+
+/// Build a recursive query for `name` with an EDNS0 OPT record.
+fn build_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    let mut packet = Vec::with_capacity(64);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // RD set; one question, one additional (OPT) record
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 1]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("invalid domain name '{}'", name));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    // OPT: root name, type 41, class = UDP size, zero TTL and RDATA
+    packet.push(0);
+    packet.extend_from_slice(&41u16.to_be_bytes());
+    packet.extend_from_slice(&EDNS_UDP_SIZE.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    Ok(packet)
+}
+
+/// Header fields of a response that answers query `id`, or `None` if the
+/// packet isn't one.
+fn parse_header(packet: &[u8], id: u16) -> Option<(Answer, bool)> {
+    if packet.len() < 12 || u16::from_be_bytes([packet[0], packet[1]]) != id {
+        return None;
+    }
+    let flags = u16::from_be_bytes([packet[2], packet[3]]);
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let truncated = flags & 0x0200 != 0;
+    let ancount = u16::from_be_bytes([packet[6], packet[7]]);
+    let answer = match (flags & 0x000f) as u8 {
+        0 if ancount > 0 => Answer::Records(ancount),
+        0 => Answer::NoData,
+        3 => Answer::NxDomain,
+        rcode => Answer::Rcode(rcode),
+    };
+    Some((answer, truncated))
+}
+
+/// Decode the (possibly compressed) name at `pos`, returning it and the
+/// offset just past it in the original position.
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Bound pointer chasing so a malicious packet can't loop forever
+    for _ in 0..128 {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            let name = if labels.is_empty() {
+                ".".to_string()
+            } else {
+                labels.join(".")
+            };
+            return Some((name, end.unwrap_or(pos + 1)));
+        }
+        if len & 0xc0 == 0xc0 {
+            let target = ((len & 0x3f) << 8) | *packet.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = target;
+            continue;
+        }
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+    None
+}
+
+/// Render record data in presentation form.
+fn format_rdata(packet: &[u8], rtype: u16, start: usize, rdata: &[u8]) -> String {
+    let name_at = |pos: usize| read_name(packet, pos).map(|(n, _)| n).unwrap_or_default();
+    match rtype {
+        TYPE_A if rdata.len() == 4 => IpAddr::from(<[u8; 4]>::try_from(rdata).unwrap()).to_string(),
+        TYPE_AAAA if rdata.len() == 16 => {
+            IpAddr::from(<[u8; 16]>::try_from(rdata).unwrap()).to_string()
+        }
+        TYPE_NS | TYPE_CNAME | TYPE_PTR => name_at(start),
+        TYPE_MX if rdata.len() > 2 => {
+            format!(
+                "{} {}",
+                u16::from_be_bytes([rdata[0], rdata[1]]),
+                name_at(start + 2)
+            )
+        }
+        TYPE_TXT => {
+            let mut strings = Vec::new();
+            let mut i = 0;
+            while i < rdata.len() {
+                let len = rdata[i] as usize;
+                let text = rdata.get(i + 1..i + 1 + len).unwrap_or(&rdata[i + 1..]);
+                strings.push(format!("\"{}\"", String::from_utf8_lossy(text)));
+                i += 1 + len;
+            }
+            strings.join(" ")
+        }
+        _ => format!("({} bytes)", rdata.len()),
+    }
+}
+
+/// Every record in the answer section of a response.
+fn parse_records(packet: &[u8]) -> Vec<Record> {
+    let mut records = Vec::new();
+    if packet.len() < 12 {
+        return records;
+    }
+    let count = |i: usize| u16::from_be_bytes([packet[i], packet[i + 1]]);
+    let (qdcount, ancount) = (count(4), count(6));
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        match read_name(packet, pos) {
+            Some((_, end)) => pos = end + 4,
+            None => return records,
+        }
+    }
+    for _ in 0..ancount {
+        let Some((name, end)) = read_name(packet, pos) else {
+            break;
+        };
+        if end + 10 > packet.len() {
+            break;
+        }
+        let rtype = count(end);
+        let ttl = u32::from_be_bytes([
+            packet[end + 4],
+            packet[end + 5],
+            packet[end + 6],
+            packet[end + 7],
+        ]);
+        let start = end + 10;
+        let rdata = &packet[start..(start + count(end + 8) as usize).min(packet.len())];
+        records.push(Record {
+            rtype,
+            name,
+            ttl,
+            data: format_rdata(packet, rtype, start, rdata),
+        });
+        pos = start + rdata.len();
+    }
+    records
+}
+
+/// A query ID that differs between calls without needing an RNG crate.
+fn query_id(name: &str) -> u16 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let hash = name.bytes().fold(nanos, |h, b| h.rotate_left(5) ^ b as u32);
+    (hash ^ (hash >> 16)) as u16
+}
+
+/// Send `packet` over UDP and return the first reply that answers `id`.
+async fn exchange_udp(
+    server: SocketAddr,
+    packet: &[u8],
+    id: u16,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let local: SocketAddr = match server.ip() {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(packet).await?;
+    let mut buf = vec![0u8; EDNS_UDP_SIZE as usize + 512];
+    loop {
+        let n = socket.recv(&mut buf).await?;
+        // Ignore stray datagrams that don't answer this query
+        if parse_header(&buf[..n], id).is_some() {
+            buf.truncate(n);
+            return Ok(buf);
+        }
+    }
+}
+
+async fn exchange_tcp(
+    server: SocketAddr,
+    packet: &[u8],
+    id: u16,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut stream = TcpStream::connect(server).await?;
+    let mut framed = (packet.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(packet);
+    stream.write_all(&framed).await?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).await?;
+    if parse_header(&response, id).is_none() {
+        return Err("malformed DNS response over TCP".into());
+    }
+    Ok(response)
+}
+
+/// Send a query for `name` to `server` over UDP, retrying over TCP when the
+/// answer is truncated. Returns the outcome and the raw response.
+async fn exchange(
+    server: IpAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> Result<(Answer, Vec<u8>), Box<dyn std::error::Error>> {
+    let server = SocketAddr::new(server, 53);
+    let id = query_id(name);
+    let packet = build_query(id, name, qtype)?;
+    let attempt = async {
+        let mut response = exchange_udp(server, &packet, id).await?;
+        if parse_header(&response, id).is_some_and(|(_, truncated)| truncated) {
+            log::debug!(
+                "{} answer for {} truncated, retrying over TCP",
+                server,
+                name
+            );
+            response = exchange_tcp(server, &packet, id).await?;
+        }
+        let (answer, _) = parse_header(&response, id).ok_or("malformed DNS response")?;
+        Ok((answer, response))
+    };
+    match tokio::time::timeout(timeout, attempt).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("{} did not answer within {:?}", server, timeout).into()),
+    }
+}
+
+/// Ask `server` directly for `name` and report only the outcome.
+/// `timeout` bounds the whole exchange.
+pub async fn query(
+    server: IpAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> Result<Answer, Box<dyn std::error::Error>> {
+    let (answer, _) = exchange(server, name, qtype, timeout).await?;
+    Ok(answer)
+}
+
+/// Ask `server` directly for `name` and decode the answer records.
+pub async fn query_records(
+    server: IpAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> Result<(Answer, Vec<Record>), Box<dyn std::error::Error>> {
+    let (answer, response) = exchange(server, name, qtype, timeout).await?;
+    Ok((answer, parse_records(&response)))
+}
+
+/// Resolve `name` over DNS-over-HTTPS (RFC 8484 wire format, POST) and
+/// decode the answer records.
+pub async fn query_doh(
+    client: &reqwest::Client,
+    url: &str,
+    name: &str,
+    qtype: u16,
+) -> Result<(Answer, Vec<Record>), Box<dyn std::error::Error>> {
+    // RFC 8484 recommends ID 0 so responses stay cacheable
+    let packet = build_query(0, name, qtype)?;
+    let response = client
+        .post(url)
+        .header("content-type", "application/dns-message")
+        .header("accept", "application/dns-message")
+        .body(packet)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let (answer, _) =
+        parse_header(&response, 0).ok_or("DoH server returned a malformed DNS response")?;
+    Ok((answer, parse_records(&response)))
+}
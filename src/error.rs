@@ -0,0 +1,54 @@
+use std::fmt;
+use std::process::ExitStatus;
+
+/// Why an external command didn't produce usable output.
+#[derive(Debug)]
+pub enum NetctlError {
+    /// The binary isn't installed (or isn't on PATH).
+    NotFound { command: String },
+    /// The command ran but exited unsuccessfully.
+    Failed {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+    /// The command was killed after running past its timeout.
+    TimedOut { command: String, secs: u64 },
+    /// Any other error starting or waiting for the process.
+    Io {
+        command: String,
+        source: std::io::Error,
+    },
+}
+
+impl fmt::Display for NetctlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetctlError::NotFound { command } => write!(f, "`{}` is not installed", command),
+            NetctlError::Failed {
+                command,
+                status,
+                stderr,
+            } => {
+                write!(f, "`{}` failed ({})", command, status)?;
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+            NetctlError::TimedOut { command, secs } => {
+                write!(f, "`{}` timed out after {}s", command, secs)
+            }
+            NetctlError::Io { command, source } => write!(f, "`{}`: {}", command, source),
+        }
+    }
+}
+
+impl std::error::Error for NetctlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetctlError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
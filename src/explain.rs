@@ -0,0 +1,192 @@
+use crate::display;
+use colored::Colorize;
+
+/// Width used when wrapping explanation paragraphs.
+const WRAP_WIDTH: usize = 72;
+
+/// Print a plain-language explanation paragraph under a heading.
+pub fn print(paragraph: &str) {
+    println!();
+    println!("{}", "What this means:".bold());
+    for line in wrap(paragraph, WRAP_WIDTH) {
+        println!("  {}", line);
+    }
+}
+
+/// Word-wrap a paragraph to the given width.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Interpret latency, jitter, and packet loss (and optionally throughput)
+/// as a single conclusion a non-technical user can act on.
+pub fn connection(
+    latency_ms: f64,
+    jitter_ms: Option<f64>,
+    loss_pct: Option<f64>,
+    throughput_mbps: Option<(f64, f64)>,
+) -> String {
+    let jitter = jitter_ms.unwrap_or(0.0);
+    let loss = loss_pct.unwrap_or(0.0);
+    let mut sentences = Vec::new();
+
+    if loss >= 100.0 {
+        return "No replies came back at all. The host may be down or blocking \
+                these requests, or your connection may be offline."
+            .to_string();
+    }
+
+    let (label, _) = display::quality_assessment(latency_ms);
+    sentences.push(match label {
+        "Excellent" => format!(
+            "A {:.0} ms response time is very fast; pages and calls will feel instant.",
+            latency_ms
+        ),
+        "Good" => format!(
+            "A {:.0} ms response time is fine for browsing, streaming, and calls.",
+            latency_ms
+        ),
+        "Fair" => format!(
+            "A {:.0} ms response time is noticeable in online games and may cause \
+             people to talk over each other on calls.",
+            latency_ms
+        ),
+        _ => format!(
+            "A {:.0} ms response time is slow; expect a visible delay when clicking \
+             links and awkward pauses on calls.",
+            latency_ms
+        ),
+    });
+
+    let unstable = jitter >= 30.0;
+    let lossy = loss >= 1.0;
+    if lossy && unstable {
+        sentences.push(format!(
+            "Your {:.0}% packet loss and {:.0} ms jitter will cause choppy video calls \
+             and stuttering audio.",
+            loss, jitter
+        ));
+    } else if lossy {
+        sentences.push(format!(
+            "Losing {:.0}% of packets means some data has to be re-sent, which shows up \
+             as freezes in calls and games.",
+            loss
+        ));
+    } else if unstable {
+        sentences.push(format!(
+            "Your response time jumps around by about {:.0} ms, which makes voice and \
+             video stutter even when the average looks fine.",
+            jitter
+        ));
+    }
+
+    // Combine the metrics into a likely cause
+    if (lossy || unstable) && latency_ms < 100.0 {
+        sentences.push(
+            "This pattern usually points to Wi-Fi interference or a busy local network \
+             rather than your internet provider; try moving closer to the router or \
+             using a cable."
+                .to_string(),
+        );
+    } else if latency_ms >= 100.0 && !lossy && !unstable {
+        sentences.push(
+            "Because the connection is steady, the delay most likely comes from the \
+             distance to the server or your provider's routing, not your home network."
+                .to_string(),
+        );
+    } else if latency_ms >= 100.0 {
+        sentences.push(
+            "High delay combined with instability often means the connection is \
+             overloaded; check whether other devices are downloading or streaming."
+                .to_string(),
+        );
+    }
+
+    if let Some((down, up)) = throughput_mbps {
+        sentences.push(if down < 5.0 {
+            format!(
+                "At {} download, HD video will buffer and large files will take a long time.",
+                display::format_mbps(down)
+            )
+        } else if down < 25.0 {
+            format!(
+                "{} download is enough for HD streaming on one device at a time.",
+                display::format_mbps(down)
+            )
+        } else if down < 100.0 {
+            format!(
+                "{} download comfortably covers a household streaming and browsing at once.",
+                display::format_mbps(down)
+            )
+        } else {
+            format!(
+                "{} download is plenty for 4K streaming and large downloads.",
+                display::format_mbps(down)
+            )
+        });
+        if up < 3.0 {
+            sentences.push(format!(
+                "Upload is only {}, so sharing your camera or screen on calls may look blurry.",
+                display::format_mbps(up)
+            ));
+        }
+    }
+
+    if !lossy && !unstable && latency_ms < 60.0 {
+        sentences.push("Overall, your connection is healthy.".to_string());
+    }
+
+    sentences.join(" ")
+}
+
+/// Interpret DNS benchmark results: how the system resolver compares with
+/// the fastest one tested.
+pub fn dns_benchmark(best: Option<(&str, f64)>, system: Option<(&str, f64)>) -> String {
+    let Some((best_name, best_ms)) = best else {
+        return "None of the DNS servers answered. Your connection may be offline, or a \
+                firewall may be blocking DNS traffic."
+            .to_string();
+    };
+
+    match system {
+        Some((sys_name, sys_ms)) if sys_name == best_name => format!(
+            "Your current DNS server ({}) is already the fastest one tested at {:.0} ms, \
+             so there is nothing to gain by switching.",
+            sys_name, sys_ms
+        ),
+        Some((sys_name, sys_ms)) if sys_ms - best_ms < 5.0 => format!(
+            "Your current DNS server ({}) answers in {:.0} ms, within a few milliseconds \
+             of the fastest ({}). A difference this small is not noticeable.",
+            sys_name, sys_ms, best_name
+        ),
+        Some((sys_name, sys_ms)) => format!(
+            "Your current DNS server ({}) answers in {:.0} ms, while {} answers in {:.0} ms. \
+             Switching would save about {:.0} ms each time you open a website you haven't \
+             visited recently.",
+            sys_name,
+            sys_ms,
+            best_name,
+            best_ms,
+            sys_ms - best_ms
+        ),
+        None => format!(
+            "The fastest DNS server tested was {} at {:.0} ms. DNS speed affects how \
+             quickly new websites start loading, not your download speed.",
+            best_name, best_ms
+        ),
+    }
+}
@@ -0,0 +1,514 @@
+//! Minimal reader for MaxMind DB (.mmdb) files such as GeoLite2: it
+//! walks the binary search tree for an address and decodes the record it
+//! points at. See https://maxmind.github.io/MaxMind-DB/ for the format.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// Precedes the metadata map at the end of every database file.
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+/// Zero bytes between the search tree and the data section.
+const DATA_SEPARATOR: usize = 16;
+/// Nesting limit for decoded values, so a corrupt file can't recurse forever.
+const MAX_DEPTH: usize = 32;
+
+/// Where `--geo` looks for a database when `--geoip-db` isn't given.
+fn candidate_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for name in ["GeoLite2-City.mmdb", "GeoLite2-Country.mmdb"] {
+        paths.push(crate::utils::netctl_dir().join(name));
+        for dir in [
+            "/usr/share/GeoIP",
+            "/var/lib/GeoIP",
+            "/usr/local/share/GeoIP",
+        ] {
+            paths.push(Path::new(dir).join(name));
+        }
+    }
+    paths
+}
+
+/// The first installed GeoLite2 database, if any.
+pub fn default_path() -> Option<PathBuf> {
+    candidate_paths().into_iter().find(|p| p.is_file())
+}
+
+/// A decoded data-section value. Lookups only read strings, unsigned
+/// integers, and maps; everything else is skipped over.
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Uint(u128),
+    Map(Vec<(String, Value)>),
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn path(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_uint(&self) -> Option<u128> {
+        match self {
+            Value::Uint(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes values from one section of the file. Pointers are offsets from
+/// the start of that section.
+struct Decoder<'a> {
+    section: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn byte(&self, pos: usize) -> Option<usize> {
+        self.section.get(pos).map(|&b| b as usize)
+    }
+
+    fn be_uint(&self, pos: usize, len: usize) -> Option<u128> {
+        let bytes = self.section.get(pos..pos + len)?;
+        Some(bytes.iter().fold(0u128, |n, &b| (n << 8) | b as u128))
+    }
+
+    /// Decode the value at `pos`, returning it and the offset just past it.
+    fn decode(&self, pos: usize, depth: usize) -> Option<(Value, usize)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let ctrl = self.byte(pos)?;
+        let mut pos = pos + 1;
+        let mut kind = ctrl >> 5;
+        if kind == 1 {
+            // Pointer: the value lives elsewhere, decoding resumes after it
+            let ss = (ctrl >> 3) & 0x3;
+            let vvv = (ctrl & 0x7) as u128;
+            let target = match ss {
+                0 => (vvv << 8) | self.be_uint(pos, 1)?,
+                1 => ((vvv << 16) | self.be_uint(pos, 2)?) + 2048,
+                2 => ((vvv << 24) | self.be_uint(pos, 3)?) + 526_336,
+                _ => self.be_uint(pos, 4)?,
+            };
+            let (value, _) = self.decode(target as usize, depth + 1)?;
+            return Some((value, pos + ss + 1));
+        }
+        if kind == 0 {
+            kind = 7 + self.byte(pos)?;
+            pos += 1;
+        }
+        let mut size = ctrl & 0x1f;
+        match size {
+            29 => {
+                size = 29 + self.byte(pos)?;
+                pos += 1;
+            }
+            30 => {
+                size = 285 + self.be_uint(pos, 2)? as usize;
+                pos += 2;
+            }
+            31 => {
+                size = 65_821 + self.be_uint(pos, 3)? as usize;
+                pos += 3;
+            }
+            _ => {}
+        }
+
+        match kind {
+            2 => {
+                let bytes = self.section.get(pos..pos + size)?;
+                let text = String::from_utf8_lossy(bytes).into_owned();
+                Some((Value::String(text), pos + size))
+            }
+            // double
+            3 => Some((Value::Other, pos + 8)),
+            // bytes
+            4 => Some((Value::Other, pos + size)),
+            5 | 6 | 9 | 10 => Some((Value::Uint(self.be_uint(pos, size)?), pos + size)),
+            7 => {
+                let mut entries = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (key, next) = self.decode(pos, depth + 1)?;
+                    let (value, next) = self.decode(next, depth + 1)?;
+                    entries.push((key.as_str()?.to_string(), value));
+                    pos = next;
+                }
+                Some((Value::Map(entries), pos))
+            }
+            // int32
+            8 => Some((Value::Other, pos + size)),
+            // array: decode the items only to find where it ends
+            11 => {
+                for _ in 0..size {
+                    let (_, next) = self.decode(pos, depth + 1)?;
+                    pos = next;
+                }
+                Some((Value::Other, pos))
+            }
+            // boolean (the value is the size), float
+            14 => Some((Value::Other, pos)),
+            15 => Some((Value::Other, pos + 4)),
+            _ => None,
+        }
+    }
+}
+
+/// Where an address is, as far as the database knows.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub country: Option<String>,
+    pub country_code: Option<String>,
+    pub city: Option<String>,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let country = self.country_code.as_deref().or(self.country.as_deref());
+        match (self.city.as_deref(), country) {
+            (Some(city), Some(country)) => write!(f, "{}, {}", city, country),
+            (None, Some(_)) => write!(f, "{}", self.country.as_deref().unwrap_or("-")),
+            (Some(city), None) => write!(f, "{}", city),
+            (None, None) => write!(f, "-"),
+        }
+    }
+}
+
+/// An open MaxMind database, held in memory.
+pub struct GeoDb {
+    buf: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u16,
+    data_start: usize,
+}
+
+impl GeoDb {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let buf = std::fs::read(path)
+            .map_err(|e| format!("cannot read GeoIP database {}: {}", path.display(), e))?;
+        Self::from_bytes(buf).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Parse a whole database file already read into memory.
+    fn from_bytes(buf: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        let marker = buf
+            .windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+            .ok_or("not a MaxMind DB file")?;
+        let metadata = Decoder {
+            section: &buf[marker + METADATA_MARKER.len()..],
+        };
+        let (metadata, _) = metadata.decode(0, 0).ok_or("corrupt MaxMind DB metadata")?;
+        let field = |key: &str| metadata.get(key).and_then(Value::as_uint);
+
+        let node_count = field("node_count").ok_or("MaxMind DB metadata lacks node_count")?;
+        let record_size = field("record_size").ok_or("MaxMind DB metadata lacks record_size")?;
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported MaxMind DB record size {}", record_size).into());
+        }
+        let (node_count, record_size) = (node_count as usize, record_size as usize);
+        let tree_size = node_count * record_size * 2 / 8;
+        if tree_size + DATA_SEPARATOR > marker {
+            return Err("corrupt MaxMind DB: search tree overruns the file".into());
+        }
+        Ok(GeoDb {
+            ip_version: field("ip_version").unwrap_or(6) as u16,
+            data_start: tree_size + DATA_SEPARATOR,
+            buf,
+            node_count,
+            record_size,
+        })
+    }
+
+    /// Left (bit 0) or right (bit 1) record of a search tree node.
+    fn record(&self, node: usize, bit: u8) -> Option<usize> {
+        let b = |i: usize| self.buf.get(i).map(|&b| b as usize);
+        let offset = node * self.record_size * 2 / 8;
+        let value = match (self.record_size, bit) {
+            (24, 0) => (b(offset)? << 16) | (b(offset + 1)? << 8) | b(offset + 2)?,
+            (24, _) => (b(offset + 3)? << 16) | (b(offset + 4)? << 8) | b(offset + 5)?,
+            // 28-bit records share the middle byte's nibbles
+            (28, 0) => {
+                ((b(offset + 3)? & 0xf0) << 20)
+                    | (b(offset)? << 16)
+                    | (b(offset + 1)? << 8)
+                    | b(offset + 2)?
+            }
+            (28, _) => {
+                ((b(offset + 3)? & 0x0f) << 24)
+                    | (b(offset + 4)? << 16)
+                    | (b(offset + 5)? << 8)
+                    | b(offset + 6)?
+            }
+            (_, 0) => {
+                (b(offset)? << 24) | (b(offset + 1)? << 16) | (b(offset + 2)? << 8) | b(offset + 3)?
+            }
+            (_, _) => {
+                (b(offset + 4)? << 24)
+                    | (b(offset + 5)? << 16)
+                    | (b(offset + 6)? << 8)
+                    | b(offset + 7)?
+            }
+        };
+        Some(value)
+    }
+
+    /// Look up an address, returning `None` for addresses the database
+    /// doesn't cover (private ranges, unallocated space).
+    pub fn lookup(&self, ip: IpAddr) -> Option<Location> {
+        let bits: Vec<u8> = match (ip, self.ip_version) {
+            (IpAddr::V4(v4), 4) => v4.octets().to_vec(),
+            // IPv4 addresses live under ::/96 in IPv6 databases
+            (IpAddr::V4(v4), _) => {
+                let mut octets = [0u8; 16];
+                octets[12..].copy_from_slice(&v4.octets());
+                octets.to_vec()
+            }
+            (IpAddr::V6(v6), 6) => v6.octets().to_vec(),
+            (IpAddr::V6(_), _) => return None,
+        };
+
+        let mut node = 0;
+        for i in 0..bits.len() * 8 {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bits[i / 8] >> (7 - i % 8)) & 1;
+            node = self.record(node, bit)?;
+        }
+        if node <= self.node_count {
+            return None;
+        }
+
+        let data = Decoder {
+            section: self.buf.get(self.data_start..)?,
+        };
+        let (record, _) = data.decode(node - self.node_count - DATA_SEPARATOR, 0)?;
+        let name = |keys: &[&str]| {
+            record
+                .path(keys)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let location = Location {
+            country: name(&["country", "names", "en"]),
+            country_code: name(&["country", "iso_code"]),
+            city: name(&["city", "names", "en"]),
+        };
+        log::trace!("{} -> {:?}", ip, location);
+        Some(location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Encoders for the data-section types the tests need; every size here
+    // fits in the control byte.
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = vec![(2 << 5) | s.len() as u8];
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn uint32(n: u32) -> Vec<u8> {
+        let mut out = vec![(6 << 5) | 4];
+        out.extend_from_slice(&n.to_be_bytes());
+        out
+    }
+
+    /// uint64 and arrays are extended types: zero in the control byte's type
+    /// bits, then the type minus 7 in the next byte.
+    fn uint64(n: u64) -> Vec<u8> {
+        let mut out = vec![8, 9 - 7];
+        out.extend_from_slice(&n.to_be_bytes());
+        out
+    }
+
+    fn array(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = vec![items.len() as u8, 11 - 7];
+        items.iter().for_each(|item| out.extend_from_slice(item));
+        out
+    }
+
+    /// The map's control byte only; its entries follow.
+    fn map_header(entries: usize) -> Vec<u8> {
+        vec![(7 << 5) | entries as u8]
+    }
+
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = map_header(entries.len());
+        for (key, value) in entries {
+            out.extend(string(key));
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    /// A pointer to `target` in the shortest of its four encodings, each of
+    /// which biases the offset by what the shorter ones can reach.
+    fn pointer(target: usize) -> Vec<u8> {
+        let (ss, value) = match target {
+            0..2048 => (0, target),
+            2048..526_336 => (1, target - 2048),
+            526_336..134_744_064 => (2, target - 526_336),
+            _ => (3, target),
+        };
+        let bytes = (value as u32).to_be_bytes();
+        let vvv = if ss == 3 { 0 } else { bytes[3 - ss - 1] & 0x7 };
+        let mut out = vec![(1 << 5) | ((ss as u8) << 3) | vvv];
+        out.extend_from_slice(&bytes[3 - ss..]);
+        out
+    }
+
+    /// Both records of one search tree node.
+    fn node(left: u32, right: u32, record_size: usize) -> Vec<u8> {
+        let (l, r) = (left.to_be_bytes(), right.to_be_bytes());
+        match record_size {
+            24 => vec![l[1], l[2], l[3], r[1], r[2], r[3]],
+            // The middle byte holds the top nibble of each record
+            28 => vec![l[1], l[2], l[3], (l[0] << 4) | r[0], r[1], r[2], r[3]],
+            _ => [l, r].concat(),
+        }
+    }
+
+    /// A two-node IPv4 database: 0.0.0.0/2 is Paris, 64.0.0.0/2 is Zürich
+    /// (whose country is a pointer into Paris's record), and 128.0.0.0/1 has
+    /// no data. With 28- and 32-bit records the data starts past 2^24, so
+    /// the records need their top bits.
+    fn database(record_size: usize) -> Vec<u8> {
+        let node_count = 2;
+        let pad = if record_size == 24 { 0 } else { 1 << 24 };
+
+        let mut data = vec![0; pad];
+        let paris = data.len();
+        data.extend(map_header(3));
+        data.extend(string("city"));
+        data.extend(map(&[("names", map(&[("en", string("Paris"))]))]));
+        data.extend(string("geoname_id"));
+        data.extend(uint32(2_988_507));
+        data.extend(string("country"));
+        let france = data.len();
+        data.extend(map(&[
+            ("iso_code", string("FR")),
+            ("names", map(&[("en", string("France"))])),
+        ]));
+        let zurich = data.len();
+        data.extend(map(&[
+            ("city", map(&[("names", map(&[("en", string("Zürich"))]))])),
+            ("country", pointer(france)),
+        ]));
+
+        let record = |offset: usize| (node_count + DATA_SEPARATOR + offset) as u32;
+        let mut buf = node(1, node_count as u32, record_size);
+        buf.extend(node(record(paris), record(zurich), record_size));
+        buf.extend([0; DATA_SEPARATOR]);
+        buf.extend(data);
+        buf.extend(METADATA_MARKER);
+        buf.extend(map(&[
+            ("binary_format_major_version", uint32(2)),
+            ("build_epoch", uint64(1_700_000_000)),
+            ("languages", array(&[string("en"), string("fr")])),
+            ("node_count", uint32(node_count as u32)),
+            ("record_size", uint32(record_size as u32)),
+            ("ip_version", uint32(4)),
+        ]));
+        buf
+    }
+
+    fn lookup(db: &GeoDb, ip: &str) -> Option<String> {
+        db.lookup(ip.parse().unwrap()).map(|l| l.to_string())
+    }
+
+    #[test]
+    fn looks_up_ipv4_at_every_record_size() {
+        for record_size in [24, 28, 32] {
+            let db = GeoDb::from_bytes(database(record_size)).unwrap();
+            assert_eq!(db.node_count, 2);
+            assert_eq!(db.record_size, record_size);
+            assert_eq!(db.ip_version, 4);
+            assert_eq!(lookup(&db, "1.2.3.4").as_deref(), Some("Paris, FR"));
+            assert_eq!(lookup(&db, "64.0.0.1").as_deref(), Some("Zürich, FR"));
+            assert_eq!(lookup(&db, "200.1.1.1"), None);
+            assert_eq!(lookup(&db, "::1"), None);
+        }
+    }
+
+    #[test]
+    fn follows_pointers_into_shared_values() {
+        let db = GeoDb::from_bytes(database(24)).unwrap();
+        let location = db.lookup("64.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(location.city.as_deref(), Some("Zürich"));
+        assert_eq!(location.country.as_deref(), Some("France"));
+        assert_eq!(location.country_code.as_deref(), Some("FR"));
+    }
+
+    #[test]
+    fn decodes_every_pointer_size() {
+        let value = string("here");
+        for target in [100, 3000, 600_000] {
+            let mut section = vec![0; target];
+            section.extend(&value);
+            let at = section.len();
+            section.extend(pointer(target));
+            section.extend(string("next"));
+            let decoder = Decoder { section: &section };
+            let (decoded, next) = decoder.decode(at, 0).unwrap();
+            assert_eq!(decoded.as_str(), Some("here"));
+            // Decoding resumes after the pointer, not after its target
+            let (after, _) = decoder.decode(next, 0).unwrap();
+            assert_eq!(after.as_str(), Some("next"));
+        }
+    }
+
+    #[test]
+    fn decodes_nested_maps_and_skips_other_types() {
+        let bytes = map(&[
+            ("list", array(&[uint32(1), string("two")])),
+            ("big", uint64(u64::MAX)),
+            ("inner", map(&[("name", string("São Paulo"))])),
+        ]);
+        let (value, end) = Decoder { section: &bytes }.decode(0, 0).unwrap();
+        assert_eq!(end, bytes.len());
+        assert!(matches!(value.get("list"), Some(Value::Other)));
+        assert_eq!(
+            value.get("big").and_then(Value::as_uint),
+            Some(u64::MAX as u128)
+        );
+        assert_eq!(
+            value.path(&["inner", "name"]).and_then(Value::as_str),
+            Some("São Paulo")
+        );
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_databases() {
+        let err = GeoDb::from_bytes(b"just some bytes".to_vec())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "not a MaxMind DB file");
+
+        // record_size's value sits just before the trailing ip_version entry
+        let mut buf = database(24);
+        let at = buf.len() - string("ip_version").len() - uint32(4).len() - 4;
+        buf[at..at + 4].copy_from_slice(&20u32.to_be_bytes());
+        let err = GeoDb::from_bytes(buf).err().unwrap();
+        assert_eq!(err.to_string(), "unsupported MaxMind DB record size 20");
+    }
+}
@@ -0,0 +1,299 @@
+use crate::utils::interrupted;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+const ECHO_REQUEST_V4: u8 = 8;
+const ECHO_REPLY_V4: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+const TIME_EXCEEDED_V4: u8 = 11;
+const UNREACHABLE_V4: u8 = 3;
+const TIME_EXCEEDED_V6: u8 = 3;
+const UNREACHABLE_V6: u8 = 1;
+/// Fixed IPv6 header length; ICMPv6 errors quote the original after it.
+const IPV6_HEADER_LEN: usize = 40;
+/// ICMP header plus payload, the same 64 bytes the system `ping` sends.
+const PACKET_LEN: usize = 64;
+
+/// Open an ICMP socket for `ip`'s family. Unprivileged datagram sockets come
+/// first (macOS, and Linux within `net.ipv4.ping_group_range`); raw sockets
+/// need root. Errors when neither is permitted.
+fn open(ip: IpAddr) -> std::io::Result<UdpSocket> {
+    let (domain, protocol) = family(ip);
+    let socket = Socket::new(domain, Type::DGRAM, Some(protocol)).or_else(|e| {
+        log::debug!("ICMP datagram socket unavailable ({}), trying raw", e);
+        Socket::new(domain, Type::RAW, Some(protocol))
+    })?;
+    socket.set_nonblocking(true)?;
+    // Both socket types are datagram-oriented, which is all UdpSocket needs
+    UdpSocket::from_std(socket.into())
+}
+
+fn family(ip: IpAddr) -> (Domain, Protocol) {
+    match ip {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    }
+}
+
+/// The ICMP message in a received packet. IPv4 sockets may hand over the IP
+/// header too; ICMP never starts with 0x4_.
+fn icmp_message(ip: IpAddr, packet: &[u8]) -> Option<&[u8]> {
+    match packet.first() {
+        Some(b) if ip.is_ipv4() && b >> 4 == 4 => packet.get(((b & 0x0f) as usize) * 4..),
+        _ => Some(packet),
+    }
+}
+
+/// RFC 1071 ones' complement checksum.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Build an echo request. The payload carries `token`, to tell our replies
+/// from other processes' on a raw socket, and the send time as nanoseconds
+/// since `start`. The identifier is advisory: Linux datagram sockets
+/// overwrite it with their own.
+fn echo_request(ip: IpAddr, seq: u16, token: u64, start: Instant) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = match ip {
+        IpAddr::V4(_) => ECHO_REQUEST_V4,
+        IpAddr::V6(_) => ECHO_REQUEST_V6,
+    };
+    packet[4..6].copy_from_slice(&(std::process::id() as u16).to_be_bytes());
+    packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    packet[8..16].copy_from_slice(&token.to_be_bytes());
+    let sent_at = start.elapsed().as_nanos() as u64;
+    packet[16..24].copy_from_slice(&sent_at.to_be_bytes());
+    // The kernel fills in ICMPv6 checksums, which cover a pseudo-header
+    if ip.is_ipv4() {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// The sequence number and send time of one of our echo replies, or `None`
+/// for anything else a raw socket picks up (other pings, our own requests
+/// to localhost, errors).
+fn parse_reply(ip: IpAddr, packet: &[u8], token: u64) -> Option<(u16, u64)> {
+    let icmp = icmp_message(ip, packet)?;
+    let reply_type = if ip.is_ipv4() {
+        ECHO_REPLY_V4
+    } else {
+        ECHO_REPLY_V6
+    };
+    if icmp.len() < 24 || icmp[0] != reply_type {
+        return None;
+    }
+    let be_u64 = |at: usize| u64::from_be_bytes(icmp[at..at + 8].try_into().unwrap());
+    if be_u64(8) != token {
+        return None;
+    }
+    Some((u16::from_be_bytes([icmp[6], icmp[7]]), be_u64(16)))
+}
+
+/// Send echo requests to `ip` `interval` apart, `count` of them or, with
+/// `None`, until Ctrl+C. `on_result` gets each probe's round-trip time in ms
+/// (`None` if no reply came within `timeout`) in send order, as soon as
+/// it's known. Errors only when no ICMP socket could be opened, so the
+/// caller can fall back.
+pub async fn ping(
+    ip: IpAddr,
+    count: Option<u32>,
+    interval: Duration,
+    timeout: Duration,
+    mut on_result: impl FnMut(Option<f64>),
+) -> std::io::Result<()> {
+    let socket = open(ip)?;
+    let target = SocketAddr::new(ip, 0);
+    let start = tokio::time::Instant::now();
+    let token = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+        ^ (std::process::id() as u64) << 32;
+    // Probes go out on a fixed schedule, so each one's deadline is known
+    let send_time = |n: u32| start + interval * n;
+    // Shared by the two halves below, which run interleaved on one task
+    let sent = AtomicU32::new(0);
+    let sending = AtomicBool::new(true);
+
+    let send = async {
+        let mut n = 0;
+        while count.is_none_or(|c| n < c) {
+            tokio::time::sleep_until(send_time(n)).await;
+            if interrupted() {
+                break;
+            }
+            let packet = echo_request(ip, n as u16, token, start.into_std());
+            // A failed send (no route, say) just loses that probe
+            if let Err(e) = socket.send_to(&packet, target).await {
+                log::debug!("ICMP echo {} to {} not sent: {}", n, ip, e);
+            }
+            n += 1;
+            sent.store(n, Ordering::Relaxed);
+        }
+        sending.store(false, Ordering::Relaxed);
+    };
+
+    let receive = async {
+        // The next probe to report, and replies that beat an earlier probe's
+        let mut next = 0u32;
+        let mut early: HashMap<u32, f64> = HashMap::new();
+        let mut buf = [0u8; 1500];
+        loop {
+            let now = tokio::time::Instant::now();
+            loop {
+                if let Some(rtt) = early.remove(&next) {
+                    on_result(Some(rtt));
+                } else if next < sent.load(Ordering::Relaxed) && now >= send_time(next) + timeout {
+                    on_result(None);
+                } else {
+                    break;
+                }
+                next += 1;
+            }
+            let finished = !sending.load(Ordering::Relaxed) && next >= sent.load(Ordering::Relaxed);
+            if finished || interrupted() {
+                break;
+            }
+
+            // Wake for the next timeout or send, or to notice Ctrl+C
+            let due = if next < sent.load(Ordering::Relaxed) {
+                send_time(next) + timeout
+            } else {
+                send_time(next)
+            };
+            let wake = due.min(now + Duration::from_millis(250));
+            let Ok(Ok((len, _))) = tokio::time::timeout_at(wake, socket.recv_from(&mut buf)).await
+            else {
+                continue;
+            };
+            let Some((seq, sent_at)) = parse_reply(ip, &buf[..len], token) else {
+                continue;
+            };
+            // Sequence numbers are 16 bits; widen relative to the next one due
+            let n = next.wrapping_add(seq.wrapping_sub(next as u16) as u32);
+            if n >= sent.load(Ordering::Relaxed) {
+                continue;
+            }
+            let rtt = (start.elapsed().as_nanos() as u64).saturating_sub(sent_at);
+            // Don't count replies that straggled in after their timeout
+            if Duration::from_nanos(rtt) <= timeout {
+                early.entry(n).or_insert(rtt as f64 / 1_000_000.0);
+            }
+        }
+    };
+
+    tokio::join!(send, receive);
+    Ok(())
+}
+
+/// Who answered one traceroute probe.
+pub struct HopReply {
+    pub from: IpAddr,
+    pub rtt_ms: f64,
+    /// The probe can't go further: the target replied, or `from` reported
+    /// it unreachable
+    pub last: bool,
+}
+
+/// Whether `packet` answers our echo request `seq`: `Some(true)` if the
+/// target replied or someone reported it unreachable, `Some(false)` if a
+/// router on the way reported the TTL expired. Errors quote the request's header,
+/// which is matched on identifier and sequence number.
+fn match_hop(ip: IpAddr, packet: &[u8], id: u16, seq: u16) -> Option<bool> {
+    let icmp = icmp_message(ip, packet)?;
+    let (request, reply, exceeded, unreachable) = if ip.is_ipv4() {
+        (
+            ECHO_REQUEST_V4,
+            ECHO_REPLY_V4,
+            TIME_EXCEEDED_V4,
+            UNREACHABLE_V4,
+        )
+    } else {
+        (
+            ECHO_REQUEST_V6,
+            ECHO_REPLY_V6,
+            TIME_EXCEEDED_V6,
+            UNREACHABLE_V6,
+        )
+    };
+    let ours = |header: &[u8], kind: u8| {
+        header.len() >= 8
+            && header[0] == kind
+            && header[4..6] == id.to_be_bytes()
+            && header[6..8] == seq.to_be_bytes()
+    };
+    match *icmp.first()? {
+        t if t == reply => ours(icmp, reply).then_some(true),
+        t if t == exceeded || t == unreachable => {
+            let quoted = icmp.get(8..)?;
+            let original = if ip.is_ipv4() {
+                quoted.get(((quoted.first()? & 0x0f) as usize) * 4..)?
+            } else {
+                quoted.get(IPV6_HEADER_LEN..)?
+            };
+            ours(original, request).then_some(t == unreachable)
+        }
+        _ => None,
+    }
+}
+
+/// Send one echo request to `ip` that expires after `ttl` hops and wait up
+/// to `timeout` for the router that drops it (or `ip` itself) to answer.
+/// Needs a raw socket, since datagram ICMP sockets don't deliver
+/// time-exceeded errors; errors only when one can't be opened.
+pub async fn probe_hop(
+    ip: IpAddr,
+    ttl: u32,
+    seq: u16,
+    timeout: Duration,
+) -> std::io::Result<Option<HopReply>> {
+    let (domain, protocol) = family(ip);
+    let socket = Socket::new(domain, Type::RAW, Some(protocol))?;
+    match ip {
+        IpAddr::V4(_) => socket.set_ttl_v4(ttl)?,
+        IpAddr::V6(_) => socket.set_unicast_hops_v6(ttl)?,
+    }
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket.into())?;
+
+    let start = Instant::now();
+    let packet = echo_request(ip, seq, 0, start);
+    let id = u16::from_be_bytes([packet[4], packet[5]]);
+    if let Err(e) = socket.send_to(&packet, SocketAddr::new(ip, 0)).await {
+        log::debug!("probe with TTL {} not sent: {}", ttl, e);
+        return Ok(None);
+    }
+
+    // A raw socket sees every ICMP message, so skip the ones for other probes
+    let deadline = tokio::time::Instant::from_std(start) + timeout;
+    let mut buf = [0u8; 1500];
+    loop {
+        let Ok(Ok((len, from))) =
+            tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await
+        else {
+            return Ok(None);
+        };
+        if let Some(last) = match_hop(ip, &buf[..len], id, seq) {
+            return Ok(Some(HopReply {
+                from: from.ip(),
+                rtt_ms: start.elapsed().as_secs_f64() * 1000.0,
+                last,
+            }));
+        }
+    }
+}
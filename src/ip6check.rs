@@ -0,0 +1,243 @@
+use crate::utils::{run_command, COMMAND_TIMEOUT};
+use colored::Colorize;
+use std::net::Ipv6Addr;
+
+/// Cloudflare's anycast resolver, reachable over IPv6 from anywhere.
+const V6_TARGET: &str = "2606:4700:4700::1111";
+/// A name that publishes AAAA records, used to test v6 DNS resolution.
+const V6_NAME: &str = "one.one.one.one";
+
+struct Check {
+    name: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+/// Global unicast addresses (2000::/3), excluding link-local and ULA.
+fn is_global(addr: &Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xe000 == 0x2000
+}
+
+/// Find a globally routable IPv6 address on any interface.
+async fn global_address() -> Option<(String, Ipv6Addr)> {
+    // Linux
+    if let Ok(output) = run_command(
+        "ip",
+        &["-6", "-o", "addr", "show", "scope", "global"],
+        COMMAND_TIMEOUT,
+    )
+    .await
+    {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for line in stdout.lines() {
+            // e.g. "2: eth0    inet6 2001:db8::5/64 scope global dynamic ..."
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let Some(idx) = parts.iter().position(|&p| p == "inet6") else {
+                continue;
+            };
+            let iface = parts.get(1).unwrap_or(&"").to_string();
+            let addr = parts
+                .get(idx + 1)
+                .and_then(|a| a.split('/').next())
+                .and_then(|a| a.parse::<Ipv6Addr>().ok());
+            if let Some(addr) = addr.filter(is_global) {
+                return Some((iface, addr));
+            }
+        }
+    }
+
+    // macOS
+    if let Ok(output) = run_command("ifconfig", &[], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut current_iface = String::new();
+        for line in stdout.lines() {
+            if !line.starts_with('\t') && !line.starts_with(' ') && line.contains(':') {
+                current_iface = line.split(':').next().unwrap_or("").to_string();
+            }
+            let Some(rest) = line.trim().strip_prefix("inet6 ") else {
+                continue;
+            };
+            let addr = rest
+                .split_whitespace()
+                .next()
+                .and_then(|a| a.split('%').next())
+                .and_then(|a| a.parse::<Ipv6Addr>().ok());
+            if let Some(addr) = addr.filter(is_global) {
+                return Some((current_iface.clone(), addr));
+            }
+        }
+    }
+
+    None
+}
+
+/// Describe the IPv6 default route, if one exists.
+async fn default_route() -> Option<String> {
+    // Linux
+    if let Ok(output) =
+        run_command("ip", &["-6", "route", "show", "default"], COMMAND_TIMEOUT).await
+    {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Some(line) = stdout.lines().next() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let field = |key: &str| {
+                parts
+                    .iter()
+                    .position(|&p| p == key)
+                    .and_then(|i| parts.get(i + 1))
+            };
+            return Some(match (field("via"), field("dev")) {
+                (Some(via), Some(dev)) => format!("via {} dev {}", via, dev),
+                (None, Some(dev)) => format!("dev {}", dev),
+                _ => line.trim().to_string(),
+            });
+        }
+    }
+
+    // macOS
+    if let Ok(output) = run_command("netstat", &["-rn", "-f", "inet6"], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for line in stdout.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // Skip link-local default routes such as "default fe80::%utun0"
+            if parts.first() == Some(&"default") && parts.get(1).is_some_and(|gw| !gw.contains('%'))
+            {
+                return Some(format!(
+                    "via {} dev {}",
+                    parts[1],
+                    parts.last().unwrap_or(&"")
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+/// Ping the anycast target over IPv6, falling back to a TCP connect when
+/// ICMP is unavailable. Returns the round-trip time in ms.
+async fn ping_v6() -> Option<f64> {
+    let attempts: [(&str, &[&str]); 2] = [
+        ("ping", &["-6", "-c", "3", "-W", "2", V6_TARGET]),
+        ("ping6", &["-c", "3", V6_TARGET]),
+    ];
+    for (cmd, args) in attempts {
+        let Ok(output) = run_command(cmd, args, std::time::Duration::from_secs(10)).await else {
+            continue;
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let rtt = stdout
+            .lines()
+            .filter_map(|l| l.split("time=").nth(1))
+            .filter_map(|t| t.split_whitespace().next())
+            .filter_map(|t| t.trim_end_matches("ms").parse::<f64>().ok())
+            .next();
+        if rtt.is_some() {
+            return rtt;
+        }
+    }
+
+    let target = format!("[{}]:443", V6_TARGET);
+    let start = std::time::Instant::now();
+    match tokio::time::timeout(
+        std::time::Duration::from_secs(3),
+        tokio::net::TcpStream::connect(&target),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Some(start.elapsed().as_secs_f64() * 1000.0),
+        _ => None,
+    }
+}
+
+/// Resolve a name through the system resolver and return its first AAAA record.
+fn resolve_v6() -> Option<Ipv6Addr> {
+    dns_lookup::lookup_host(V6_NAME)
+        .ok()?
+        .into_iter()
+        .find_map(|ip| match ip {
+            std::net::IpAddr::V6(v6) => Some(v6),
+            std::net::IpAddr::V4(_) => None,
+        })
+}
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    println!();
+    println!("{}", "Running IPv6 diagnostics...".dimmed());
+
+    let address = global_address().await;
+    let route = default_route().await;
+    let rtt = ping_v6().await;
+    let resolved = resolve_v6();
+
+    let checks = [
+        Check {
+            name: "Global address",
+            passed: address.is_some(),
+            detail: match address {
+                Some((ref iface, addr)) => format!("{} on {}", addr, iface),
+                None => "only link-local or ULA addresses assigned".to_string(),
+            },
+        },
+        Check {
+            name: "Default route",
+            passed: route.is_some(),
+            detail: route.unwrap_or_else(|| "no IPv6 default route".to_string()),
+        },
+        Check {
+            name: "Connectivity",
+            passed: rtt.is_some(),
+            detail: match rtt {
+                Some(ms) => format!("{} reachable ({:.0} ms)", V6_TARGET, ms),
+                None => format!("{} unreachable", V6_TARGET),
+            },
+        },
+        Check {
+            name: "DNS (AAAA)",
+            passed: resolved.is_some(),
+            detail: match resolved {
+                Some(addr) => format!("{} -> {}", V6_NAME, addr),
+                None => format!("no AAAA record returned for {}", V6_NAME),
+            },
+        },
+    ];
+
+    println!();
+    println!("{}", "IPv6 Checks:".bold());
+    println!();
+    let first_failure = checks.iter().position(|c| !c.passed);
+    for (i, check) in checks.iter().enumerate() {
+        let status = if check.passed {
+            "PASS".green()
+        } else {
+            "FAIL".red()
+        };
+        let name = format!("{:<16}", check.name);
+        let name = if Some(i) == first_failure {
+            name.red().bold().to_string()
+        } else {
+            name
+        };
+        println!("  [{}] {} {}", status, name, check.detail.dimmed());
+    }
+
+    let reachable = checks[1].passed && checks[2].passed;
+    let verdict = if checks.iter().all(|c| c.passed) {
+        format!("{} fully working", "OK".green())
+    } else if checks[0].passed && !reachable {
+        format!("{} address-only (no working IPv6 path)", "!!".yellow())
+    } else {
+        format!("{} broken", "!!".red())
+    };
+    println!();
+    println!("IPv6: {}", verdict);
+    if let Some(i) = first_failure {
+        println!(
+            "{}",
+            format!("First failing step: {}", checks[i].name).dimmed()
+        );
+    }
+    println!();
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::display::{self, TableStyle};
+use crate::ping::{self, PingStats};
+use colored::Colorize;
+
+/// Echo requests per target; the matrix is meant to be quick.
+const DEFAULT_COUNT: u32 = 5;
+
+#[derive(tabled::Tabled)]
+struct LatencyRow {
+    #[tabled(rename = "Target")]
+    target: String,
+    #[tabled(rename = "Address")]
+    address: String,
+    #[tabled(rename = "Avg")]
+    avg: String,
+    #[tabled(rename = "Loss")]
+    loss: String,
+    #[tabled(rename = "Score")]
+    score: String,
+}
+
+/// The built-in reference set: the local gateway, the configured DNS
+/// server, and two public anycast endpoints.
+async fn reference_targets() -> Vec<(String, String)> {
+    let mut targets = Vec::new();
+    if let Some(gw) = crate::utils::get_default_route().await.gateway {
+        targets.push(("Gateway".to_string(), gw));
+    }
+    if let Some(dns) = crate::dns::get_system_dns() {
+        targets.push(("DNS server".to_string(), dns));
+    }
+    targets.push(("Cloudflare".to_string(), "1.1.1.1".to_string()));
+    targets.push(("Google".to_string(), "google.com".to_string()));
+    targets
+}
+
+/// Ping every target at once, each on its own task like `ping` does, so the
+/// matrix takes as long as the slowest target.
+async fn ping_all(
+    targets: &[(String, String)],
+    count: u32,
+) -> Result<Vec<PingStats>, tokio::task::JoinError> {
+    let handles: Vec<_> = targets
+        .iter()
+        .map(|(_, host)| {
+            let host = host.clone();
+            tokio::spawn(async move { ping::do_ping(&host, count, false, None).await })
+        })
+        .collect();
+    futures_util::future::join_all(handles)
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// One-line reading of where along the path latency goes wrong.
+fn verdict(targets: &[(String, String)], stats: &[PingStats]) -> String {
+    let bad = |s: &PingStats| s.received() == 0 || s.loss_pct() > 0.0 || s.avg() > 100.0;
+    let status = |label: &str| {
+        targets
+            .iter()
+            .zip(stats)
+            .find(|((name, _), _)| name == label)
+            .map(|(_, s)| bad(s))
+    };
+    let remote_bad = targets
+        .iter()
+        .zip(stats)
+        .filter(|((name, _), _)| name != "Gateway" && name != "DNS server")
+        .all(|(_, s)| bad(s));
+
+    match (status("Gateway"), status("DNS server"), remote_bad) {
+        (Some(true), _, _) => format!(
+            "{} The gateway is slow or unreachable: look at Wi-Fi/LAN first",
+            "!!".yellow()
+        ),
+        (_, Some(true), false) => format!(
+            "{} The internet is reachable but your DNS server is not responding well",
+            "!!".yellow()
+        ),
+        (_, _, true) => format!(
+            "{} Local network is fine but public endpoints are slow: likely upstream",
+            "!!".yellow()
+        ),
+        _ => format!("{} All reference points look healthy", "OK".green()),
+    }
+}
+
+pub async fn run(
+    hosts: Option<String>,
+    count: Option<u32>,
+    style: TableStyle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let targets: Vec<(String, String)> = match hosts {
+        Some(hosts) => hosts
+            .split(',')
+            .map(|h| h.trim())
+            .filter(|h| !h.is_empty())
+            .map(|h| (h.to_string(), h.to_string()))
+            .collect(),
+        None => reference_targets().await,
+    };
+    let count = count.unwrap_or(DEFAULT_COUNT);
+
+    println!();
+    println!(
+        "{}",
+        format!("Pinging {} targets ({} each)...", targets.len(), count).dimmed()
+    );
+
+    let stats = ping_all(&targets, count).await?;
+    let rows: Vec<LatencyRow> = targets
+        .iter()
+        .zip(&stats)
+        .map(|((name, _), s)| {
+            let answered = s.received() > 0;
+            LatencyRow {
+                target: name.clone(),
+                address: s.ip().to_string(),
+                avg: if answered {
+                    format!("{:.0} ms", s.avg())
+                } else {
+                    "-".to_string()
+                },
+                loss: format!("{:.0}%", s.loss_pct()),
+                score: if answered {
+                    display::quality_score(s.avg(), Some(s.jitter(false)), Some(s.loss_pct()))
+                        .to_string()
+                } else {
+                    "-".to_string()
+                },
+            }
+        })
+        .collect();
+
+    println!();
+    println!("{}", "Latency Matrix:".bold());
+    println!();
+    println!(
+        "{}",
+        display::render_table(&rows, style, &["Address", "Score"])
+    );
+    println!();
+    println!("{}", verdict(&targets, &stats));
+    println!();
+    Ok(())
+}
@@ -1,14 +1,28 @@
 mod bandwidth;
 mod block;
+mod completions;
+mod config;
 mod connections;
 mod display;
 mod dns;
+mod dns_query;
+mod error;
+mod explain;
+mod geoip;
+mod icmp;
+mod ip6check;
+mod latency;
+mod logging;
+mod output;
 mod ping;
 mod speed;
+mod speed_server;
+mod trace;
 pub mod utils;
 mod vpn;
+mod wifi;
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(
@@ -23,9 +37,50 @@ Common workflows:
   DNS diagnostics:      netctl dns lookup google.com
   Ping with stats:      netctl ping 8.8.8.8 -c 10
   Check VPN:            netctl vpn status
-  Block distractions:   netctl block add twitter.com --duration 2h"
+  Block distractions:   netctl block add twitter.com --duration 2h
+  Wi-Fi signal:         netctl wifi
+  IPv6 health:          netctl ip6check
+  Where is it slow?:    netctl latency
+  Markdown tables:      netctl --style markdown dns benchmark
+  Overnight speed test: netctl speed --at 03:00
+  Debug a bad parse:    netctl -vv connections"
 )]
 struct Cli {
+    /// Table style for tabular output (compact, modern, minimal, markdown)
+    #[arg(long, global = true, value_enum, default_value_t = display::TableStyle::Modern)]
+    style: display::TableStyle,
+
+    /// Log subprocess calls to stderr (-v), plus parsing detail (-vv)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Wait until a local time (e.g. 14:00 or "2026-10-15 03:00") before running
+    #[arg(long, global = true)]
+    at: Option<String>,
+
+    /// Print the result as JSON instead of tables (speed, connections,
+    /// bandwidth, ping, vpn status, dns resolve/benchmark, wifi)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Print JSON results on a single line instead of pretty-printed
+    #[arg(long, global = true)]
+    compact_json: bool,
+
+    /// In watch modes, redraw in place instead of clearing the screen
+    #[arg(long, global = true)]
+    no_clear: bool,
+
+    /// Print plain text without ANSI colors (also set by NO_COLOR, and the
+    /// default when stdout isn't a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Show byte counts and rates in binary units (KiB, MiB, GiB; powers of
+    /// 1024) instead of decimal KB, MB, GB. Speed results stay in Mbps
+    #[arg(long, global = true)]
+    binary: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -42,22 +97,99 @@ over time.
 
 Examples:
   netctl speed                         Run a quick speed test (Cloudflare)
-  netctl speed --server google         Use Google as the test server
+  netctl speed --server google         Latency-only test against Google
+  netctl speed --auto                  Test against the lowest-latency server
   netctl speed --detailed              Include jitter and packet loss metrics
+  netctl speed --bufferbloat           Grade latency under load (A-F)
   netctl speed --output results.json   Save results to a JSON file
-  netctl speed --detailed --output ~/speed-log.json")]
+  netctl speed --json                  Print only the JSON result, for scripts
+  netctl speed --explain               Explain the results in plain language
+  netctl speed --detailed --simple-jitter  Use the pre-RFC 3550 jitter formula
+  netctl speed --runs 3                Run three times and report the median
+  netctl speed --connections 8         Download over 8 parallel streams
+  netctl speed --connections 1         Measure a single TCP stream
+  netctl speed --size 2MB              One small transfer for slow links
+  netctl speed --duration 15           Saturate the link for 15 seconds each way
+  netctl speed --ping-host 1.1.1.1     Judge latency against a host you care about
+  netctl speed --serve :8080           Act as a LAN speed test peer
+  netctl speed --server-url http://peer:8080   Test against that peer
+  netctl speed --download-url 'http://peer:8080/down?bytes={bytes}' --upload-url http://peer:8080/up
+  netctl speed --detailed --output ~/speed-log.json
+  netctl speed --output ~/speed-log.json --append   Build a time series")]
     Speed {
-        /// Server to use for the test (cloudflare, google)
+        /// Server to use for the test (cloudflare, google; google measures latency
+        /// only). Defaults to speed.server in the config, then cloudflare
         #[arg(long)]
         server: Option<String>,
 
+        /// Probe the built-in servers and test against the lowest-latency one
+        #[arg(
+            long,
+            conflicts_with_all = ["server", "server_url", "download_url", "upload_url"]
+        )]
+        auto: bool,
+
         /// Show detailed metrics (latency, jitter, packet loss)
         #[arg(long)]
         detailed: bool,
 
-        /// Export results to a JSON file
+        /// Export results to a JSON file ("-" writes JSON to stdout)
         #[arg(long)]
         output: Option<String>,
+
+        /// Append to the results already in the --output file (JSON array or NDJSON)
+        #[arg(long, requires = "output")]
+        append: bool,
+
+        /// Explain what the results mean in plain language
+        #[arg(long)]
+        explain: bool,
+
+        /// Report jitter as the mean consecutive difference instead of RFC 3550
+        #[arg(long)]
+        simple_jitter: bool,
+
+        /// Repeat the full test N times and report the median and range
+        #[arg(long, default_value = "1")]
+        runs: u32,
+
+        /// Measure latency, jitter, and loss against this host instead
+        #[arg(long)]
+        ping_host: Option<String>,
+
+        /// Serve download/upload endpoints for another netctl (e.g. :8080)
+        #[arg(long, value_name = "ADDR")]
+        serve: Option<String>,
+
+        /// Base URL of a `netctl speed --serve` peer; sets both endpoints
+        #[arg(long, conflicts_with_all = ["download_url", "upload_url", "server"])]
+        server_url: Option<String>,
+
+        /// Custom download URL; "{bytes}" is replaced with the payload size
+        #[arg(long)]
+        download_url: Option<String>,
+
+        /// Custom upload URL that accepts POSTed data
+        #[arg(long)]
+        upload_url: Option<String>,
+
+        /// Also measure latency while downloading and uploading, graded A-F
+        #[arg(long)]
+        bufferbloat: bool,
+
+        /// Transfer one payload of this size each way (e.g. 500KB, 50MB, 1GB);
+        /// each download stream fetches the full size
+        #[arg(long, conflicts_with = "duration")]
+        size: Option<String>,
+
+        /// Transfer continuously for this many seconds each way
+        #[arg(long, value_name = "SECS")]
+        duration: Option<u64>,
+
+        /// Parallel download connections, each fetching the full payload (1
+        /// measures a single stream)
+        #[arg(long, default_value = "4")]
+        connections: u32,
     },
 
     /// List active network connections by application
@@ -66,23 +198,54 @@ List active network connections by application
 
 Shows all active TCP/UDP connections grouped by application, including PIDs,
 remote addresses, protocols, and connection states. Supports filtering and
-continuous monitoring.
+continuous monitoring; each --watch refresh lists the connections opened and
+closed since the last one.
 
 Examples:
   netctl connections                       List all active connections
   netctl connections --external            Show only external (non-local) connections
   netctl connections --app chrome          Filter connections by application name
+  netctl connections --exclude firefox,slack  Hide noisy apps to spot the unexpected
+  netctl connections --port 443 --proto tcp  Only TCP connections to port 443
+  netctl connections --user www-data       Only connections owned by a user
   netctl connections --watch               Continuously monitor connections
-  netctl connections --watch --interval 5  Monitor with a 5-second refresh")]
+  netctl connections --watch --interval 5  Monitor with a 5-second refresh
+  netctl connections --by-remote --external  Which apps talk to each outside host
+  netctl connections --group               One summary row per application
+  netctl connections --limit 0             Show every connection, not just the first 30
+  netctl connections --external --resolve  Show remote hostnames instead of IPs
+  netctl connections --external --geo      Add each peer's city/country (GeoLite2)
+  netctl connections --min-rtt 150         Only TCP connections with RTT >= 150 ms (Linux)
+  netctl connections --external --json | jq '.[].application'
+                                           Every matching connection as JSON
+  netctl connections --watch --output conns.ndjson
+                                           Stream snapshots as NDJSON (file or FIFO)")]
     Connections {
         /// Filter by application name
         #[arg(long)]
         app: Option<String>,
 
+        /// Hide applications matching these substrings (repeatable or comma-separated;
+        /// applied after --app)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
         /// Show only external (non-local) connections
         #[arg(long)]
         external: bool,
 
+        /// Show only connections owned by this user
+        #[arg(long)]
+        user: Option<String>,
+
+        /// Show only connections to this remote port
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Show only TCP or UDP connections
+        #[arg(long, value_enum)]
+        proto: Option<connections::Transport>,
+
         /// Continuous monitoring mode
         #[arg(long)]
         watch: bool,
@@ -90,6 +253,38 @@ Examples:
         /// Refresh interval in seconds (used with --watch)
         #[arg(long, default_value = "2")]
         interval: u64,
+
+        /// Stream each snapshot as NDJSON to a file or named pipe ("-" for stdout)
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Group by remote host, showing which apps talk to each one
+        #[arg(long)]
+        by_remote: bool,
+
+        /// Keep only TCP connections whose round-trip time is at least this many ms
+        #[arg(long, value_name = "MS")]
+        min_rtt: Option<f64>,
+
+        /// Most rows to show in the table (0 = all; --json is never limited)
+        #[arg(long, default_value = "30")]
+        limit: usize,
+
+        /// One row per application: connection count, distinct remote hosts, protocols
+        #[arg(long, conflicts_with = "by_remote")]
+        group: bool,
+
+        /// Show reverse-DNS hostnames for remote IPs (IPs without a PTR record stay as-is)
+        #[arg(long)]
+        resolve: bool,
+
+        /// Add a Location column with the GeoIP city/country of external peers
+        #[arg(long)]
+        geo: bool,
+
+        /// MaxMind GeoLite2 .mmdb file (default: ~/.netctl or /usr/share/GeoIP)
+        #[arg(long, value_name = "PATH", requires = "geo")]
+        geoip_db: Option<String>,
     },
 
     /// Real-time bandwidth usage per application
@@ -97,31 +292,96 @@ Examples:
 Real-time bandwidth usage per application
 
 Displays per-application network bandwidth consumption (download and upload
-rates). Uses nettop on macOS and ss/proc on Linux. Can alert when bandwidth
-exceeds a threshold.
+rates). Uses nettop on macOS, and nethogs (when installed and run as root)
+or ss/proc on Linux. Can alert when bandwidth
+exceeds a threshold, or report rates per network interface instead.
 
 Examples:
   netctl bandwidth                     Show current bandwidth by app
   netctl bandwidth --top 5             Show only the top 5 consumers
+  netctl bandwidth --sort upload --top 1
+                                       The app uploading the most
   netctl bandwidth --app spotify       Monitor bandwidth for a specific app
+  netctl bandwidth --exclude chrome    Everything except the browser
   netctl bandwidth --watch             Continuously monitor (refreshes every 2s)
-  netctl bandwidth --alert 50MB        Alert if total bandwidth exceeds 50 MB/s")]
+  netctl bandwidth --watch --interval 5
+                                       Average rates over 5-second windows
+  netctl bandwidth --alert 50MB        Alert if total bandwidth exceeds 50 MB/s
+  netctl bandwidth --watch --alert 50MB --alert-cmd 'notify-send netctl \"$NETCTL_RATE B/s\"'
+                                       Run a command each time the threshold is crossed
+  netctl bandwidth --watch --output /tmp/netctl.fifo
+                                       Stream samples as NDJSON to a named pipe
+  netctl bandwidth --watch --record    Record samples to ~/.netctl/bandwidth.jsonl
+  netctl bandwidth --json              One sample as JSON with raw bytes/sec
+  netctl bandwidth --per-interface     Rates per interface from /proc/net/dev
+  netctl bandwidth --report --last 7d  Top consumers over the last 7 days
+  netctl bandwidth --include-virtual   Include lo/veth/docker/bridge interface totals")]
     Bandwidth {
         /// Show top N bandwidth consumers
         #[arg(long)]
         top: Option<usize>,
 
+        /// Rate to rank rows by
+        #[arg(long, value_enum, default_value = "total")]
+        sort: bandwidth::SortKey,
+
+        /// Sort lowest first (with --top, shows the quietest rows)
+        #[arg(long)]
+        reverse: bool,
+
         /// Monitor a specific application
         #[arg(long)]
         app: Option<String>,
 
+        /// Hide applications matching these substrings (repeatable or comma-separated;
+        /// applied after --app)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
         /// Alert threshold (e.g. "10MB")
         #[arg(long)]
         alert: Option<String>,
 
+        /// Shell command to run when the total rate crosses --alert (gets NETCTL_RATE)
+        #[arg(long, value_name = "COMMAND", requires = "alert")]
+        alert_cmd: Option<String>,
+
         /// Continuous monitoring mode
         #[arg(long)]
         watch: bool,
+
+        /// Seconds between samples: the rate window, and the refresh period with
+        /// --watch (default 2, or bandwidth.interval in the config)
+        #[arg(long)]
+        interval: Option<u64>,
+
+        /// Stream each sample as NDJSON to a file or named pipe ("-" for stdout)
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Record samples to the local history log
+        #[arg(long)]
+        record: bool,
+
+        /// Rates per network interface instead of per application (no root needed)
+        #[arg(long, conflicts_with_all = ["app", "exclude", "record", "report"])]
+        per_interface: bool,
+
+        /// Show top consumers from recorded history instead of live data
+        #[arg(long)]
+        report: bool,
+
+        /// Period covered by --report (e.g. "24h", "7d", "2w")
+        #[arg(long, default_value = "7d", requires = "report")]
+        last: String,
+
+        /// Show virtual interfaces (lo, veth*, docker*, br-*) in interface totals
+        #[arg(long)]
+        include_virtual: bool,
+
+        /// Also hide interfaces matching these globs (comma-separated, e.g. "tun*,wg0")
+        #[arg(long)]
+        exclude_iface: Option<String>,
     },
 
     /// Connection quality test (ping with statistics)
@@ -129,42 +389,167 @@ Examples:
 Connection quality test (ping with statistics)
 
 Sends ICMP ping packets to one or more hosts and reports detailed latency
-statistics including min/avg/max, standard deviation, jitter, and packet
-loss. Falls back to TCP-based pings when ICMP is unavailable.
+statistics including min/avg/max, standard deviation, jitter (RFC 3550), and
+packet loss. Falls back to TCP-based pings when ICMP is unavailable.
 
 Examples:
   netctl ping                              Ping google.com (default)
   netctl ping 8.8.8.8                      Ping a specific IP address
   netctl ping cloudflare.com --count 20    Send 20 ping packets
   netctl ping --hosts 1.1.1.1,8.8.8.8     Ping multiple hosts at once
-  netctl ping github.com --count 50        Extended ping for stability test")]
+  netctl ping github.com --count 50        Extended ping for stability test
+  netctl ping 1.1.1.1 --watch              Ping until Ctrl+C with live statistics
+  netctl ping 1.1.1.1 --count 100 --interval 200 --timeout 1
+                                           Fast stability test (5 probes a second)
+  sudo netctl ping 192.168.1.1 --count 1000 --interval 10
+                                           Flood-style test (below 200 ms needs root)
+  netctl ping --explain                    Explain the results in plain language
+  netctl ping --simple-jitter              Use mean consecutive difference for jitter
+  netctl ping 1.1.1.1 --tcp                Time TCP connects instead of ICMP
+  netctl ping 1.1.1.1 --source utun3       Test reachability via a specific interface
+  netctl ping example.com --all-ips        Ping every address a load-balanced name resolves to
+  netctl ping -6 google.com                Ping the host's IPv6 address
+  netctl ping example.com --index 1        Ping only the second resolved address
+  netctl ping 1.1.1.1 --with-gateway       Tell local (Wi-Fi/LAN) from upstream latency
+  netctl ping 1.1.1.1 --mtu                Find the path MTU with don't-fragment pings
+  netctl ping --hosts 1.1.1.1,8.8.8.8 --json  Stats, incl. loss bursts, as JSON
+  netctl ping --hosts 1.1.1.1,8.8.8.8 --csv >> ping.csv
+                                           One CSV row per host for monitoring pipelines")]
     Ping {
         /// Host to ping
         host: Option<String>,
 
-        /// Number of ping packets to send
-        #[arg(long, default_value = "10")]
-        count: u32,
+        /// Number of ping packets to send (default 10, or ping.count in the config)
+        #[arg(long)]
+        count: Option<u32>,
 
         /// Ping multiple hosts (comma-separated)
         #[arg(long)]
         hosts: Option<String>,
+
+        /// Explain what the results mean in plain language
+        #[arg(long)]
+        explain: bool,
+
+        /// Report jitter as the mean consecutive difference instead of RFC 3550
+        #[arg(long)]
+        simple_jitter: bool,
+
+        /// Probe with TCP connects to port 80 instead of ICMP
+        #[arg(long)]
+        tcp: bool,
+
+        /// Source IP or interface to send TCP probes from (implies --tcp)
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Ping the n-th resolved address (0-based) instead of the first
+        #[arg(long, conflicts_with = "all_ips")]
+        index: Option<usize>,
+
+        /// Ping every address the host resolves to, concurrently
+        #[arg(long)]
+        all_ips: bool,
+
+        /// Also ping the default gateway and compare its latency with the target's
+        #[arg(long)]
+        with_gateway: bool,
+
+        /// Discover the path MTU with don't-fragment pings instead of measuring latency
+        #[arg(long, conflicts_with_all = ["tcp", "source", "with_gateway"])]
+        mtu: bool,
+
+        /// Output summary statistics as CSV, one row per address after a header
+        #[arg(long, conflicts_with_all = ["mtu", "with_gateway", "explain"])]
+        csv: bool,
+
+        /// Milliseconds between probes (default: 1000 for ICMP, 200 for TCP;
+        /// below 200 needs root)
+        #[arg(long, value_name = "MS")]
+        interval: Option<u64>,
+
+        /// Seconds to wait for each reply before counting it lost
+        #[arg(long, value_name = "SECS", default_value = "2")]
+        timeout: u64,
+
+        /// Only ping IPv4 addresses
+        #[arg(short = '4', long, conflicts_with = "ipv6")]
+        ipv4: bool,
+
+        /// Only ping IPv6 addresses (default: IPv4 first, then IPv6)
+        #[arg(short = '6', long)]
+        ipv6: bool,
+
+        /// Ping until Ctrl+C with live statistics, then print a final summary
+        #[arg(
+            long,
+            conflicts_with_all = ["count", "hosts", "all_ips", "with_gateway", "mtu", "csv"]
+        )]
+        watch: bool,
+    },
+
+    /// Trace the route to a host
+    #[command(long_about = "\
+Trace the route to a host
+
+Sends ICMP echo requests with increasing TTLs and prints each router that
+answers along the way: its address, reverse DNS name, and the round-trip
+time of three probes. A hop of \"*\" means no router answered within 2
+seconds (many filter these probes, and later hops can still respond). Stops
+once the host itself replies. Raw ICMP sockets need root; without it the
+system traceroute is run instead.
+
+Examples:
+  netctl trace github.com              Show every hop to github.com
+  netctl trace 1.1.1.1 --max-hops 15   Give up after 15 hops")]
+    Trace {
+        /// Host to trace the route to
+        host: String,
+
+        /// Maximum number of hops to probe
+        #[arg(long, default_value = "30")]
+        max_hops: u8,
     },
 
     /// Domain blocker / focus mode
     #[command(long_about = "\
 Domain blocker / focus mode
 
-Blocks distracting domains by adding entries to /etc/hosts. Supports
-temporary blocks with auto-expiry, enabling/disabling all blocks at once,
-and persists state across reboots. Requires sudo to modify /etc/hosts.
+Blocks distracting domains by adding entries to /etc/hosts, along with
+common subdomains (www, m, mobile, app, api, cdn) since hosts files have no
+wildcards; --subdomains picks your own list. Supports
+temporary blocks with auto-expiry, recurring schedules (e.g. weekdays 9 to
+5), enabling/disabling all blocks at once, and persists state across reboots (in /var/lib/netctl as root, otherwise
+~/.local/state/netctl). Requires sudo to modify /etc/hosts. The original
+hosts file is saved to /etc/hosts.netctl.bak before the first change, and
+netctl refuses to rewrite a hosts file whose netctl section looks damaged.
+The system DNS cache is flushed after each change so blocks take effect
+immediately (--no-flush skips this).
 
 Examples:
   netctl block --list                              List all blocked domains
   netctl block --add twitter.com,reddit.com        Block multiple domains
   netctl block --add youtube.com --duration 2h     Block for 2 hours only
+  netctl block --add example.com --subdomains m,api
+                                                   Block example.com, m. and api. only
   netctl block --remove twitter.com                Unblock a domain
-  netctl block --disable                           Disable all blocks temporarily")]
+  netctl block --disable                           Disable all blocks temporarily
+  netctl block --add reddit.com --preset video     Block a domain plus a built-in list
+  netctl block --add-category social               Same as --preset social
+  netctl block --list-categories                   Show the built-in lists
+  netctl block --stats                             Focus time enforced this week
+  netctl block --add-category social --allow linkedin.com
+                                                   Block social sites except LinkedIn
+  netctl block --pomodoro 25 --preset social       25m focus / 5m break cycles
+  netctl block --schedule \"Mon-Fri 09:00-17:00\" --add-category social
+                                                   Block social sites during work hours
+  netctl block --unschedule 1                      Delete the first schedule in --list
+  netctl block --apply                             Re-apply schedules (run from cron)
+  sudo netctl block --restore                      Undo everything: restore the saved /etc/hosts
+
+Schedules only change /etc/hosts when netctl block runs, so keep them
+current with a root crontab entry such as:
+  * * * * * netctl block --apply")]
     Block {
         /// Add domains to block (comma-separated)
         #[arg(long)]
@@ -189,6 +574,72 @@ Examples:
         /// Duration for temporary blocks (e.g. "2h", "30m")
         #[arg(long)]
         duration: Option<String>,
+
+        /// Subdomains to block under each added domain (comma-separated;
+        /// default: www,m,mobile,app,api,cdn; empty for none)
+        #[arg(long, value_name = "LABELS")]
+        subdomains: Option<String>,
+
+        /// Also block a built-in category of domains
+        #[arg(long, value_enum, alias = "add-category")]
+        preset: Option<block::Preset>,
+
+        /// List the built-in categories and their domains
+        #[arg(long)]
+        list_categories: bool,
+
+        /// Show how long each domain has been blocked this week and overall
+        #[arg(long)]
+        stats: bool,
+
+        /// Block the added domains only during a recurring window
+        /// (e.g. "Mon-Fri 09:00-17:00", "weekends 10:00-18:00", "22:00-06:00")
+        #[arg(long, value_name = "SPEC", conflicts_with_all = ["duration", "pomodoro"])]
+        schedule: Option<String>,
+
+        /// Delete a schedule by its number in --list
+        #[arg(long, value_name = "N")]
+        unschedule: Option<usize>,
+
+        /// Rewrite /etc/hosts for the current time, opening and closing
+        /// scheduled windows (run from cron every minute)
+        #[arg(long)]
+        apply: bool,
+
+        /// Restore /etc/hosts from the backup taken before the first block
+        /// and clear all blocks and schedules
+        #[arg(long, exclusive = true)]
+        restore: bool,
+
+        /// Never block these hostnames (comma-separated), even when a
+        /// category or schedule includes them
+        #[arg(long, value_name = "DOMAINS")]
+        allow: Option<String>,
+
+        /// Take a hostname off the allowlist
+        #[arg(long, value_name = "DOMAIN")]
+        unallow: Option<String>,
+
+        /// Don't flush the DNS cache after changing /etc/hosts
+        #[arg(long)]
+        no_flush: bool,
+
+        /// Run Pomodoro cycles: block for this many minutes, then take a break
+        #[arg(long, value_name = "MINUTES")]
+        pomodoro: Option<u64>,
+
+        /// Break length in minutes between Pomodoro intervals
+        #[arg(
+            long = "break",
+            value_name = "MINUTES",
+            default_value = "5",
+            requires = "pomodoro"
+        )]
+        break_mins: u64,
+
+        /// Number of Pomodoro focus intervals
+        #[arg(long, default_value = "4", requires = "pomodoro")]
+        cycles: u32,
     },
 
     /// VPN connection status
@@ -196,13 +647,24 @@ Examples:
 VPN connection status
 
 Detects active VPN tunnels by inspecting network interfaces (tun, utun, tap,
-ppp, wg, ipsec). Shows connection details including protocol, IP addresses,
-DNS servers, and traffic statistics. Supports WireGuard detection.
+ppp, wg, ipsec), listing each one when several are up. Shows connection
+details including protocol, IP addresses, DNS servers, and traffic
+statistics. For WireGuard, --detailed lists each peer's endpoint, handshake
+age, and transfer, and flags a handshake older than 3 minutes as a likely
+dead tunnel. Also looks up the public IP your
+traffic exits from (and its country, with a GeoLite2 database installed), to
+confirm it goes through the VPN. Watch mode reports link events such as a
+tunnel going down or the default route changing.
 
 Examples:
   netctl vpn status                    Check if a VPN is connected
-  netctl vpn status --detailed         Show traffic stats and full details
-  netctl vpn watch                     Continuously monitor VPN status")]
+  netctl vpn status --detailed         Show traffic stats, tunnel latency, and full details
+  netctl vpn status --json             Machine-readable status for health checks
+  netctl vpn watch                     Continuously monitor VPN status
+  netctl vpn watch --output vpn.ndjson Stream status snapshots as NDJSON
+  netctl vpn watch --log flaps.log     Record link events to a flap log
+  netctl vpn watch --interval 1 --until-change
+                                       Block until the tunnel drops, then exit with status 3")]
     Vpn {
         #[command(subcommand)]
         action: VpnAction,
@@ -213,77 +675,510 @@ Examples:
 DNS diagnostics and benchmarking
 
 Tools for inspecting and optimizing DNS resolution. Resolve domains, view
-configured DNS servers, flush the system DNS cache, or benchmark popular
-public resolvers to find the fastest one for your network.
+configured DNS servers, inspect or flush the system DNS cache, or benchmark
+popular public resolvers to find the fastest one for your network.
 
 Examples:
   netctl dns resolve github.com        Resolve a domain to IP addresses
+  netctl dns resolve github.com,example.com  Resolve a batch of domains
+  netctl dns resolve a.com,b.com --json      Per-domain results as JSON
+  netctl dns resolve api --trace-search      Show each search-domain attempt
+  netctl dns resolve github.com --doh        Resolve over DNS-over-HTTPS
+  netctl dns resolve github.com --doh-server https://dns.quad9.net/dns-query
+  netctl dns resolve gmail.com --type mx    Show mail exchangers
+  netctl dns resolve 1.1.1.1 --type ptr     Reverse-resolve an IP address
+  netctl dns reverse 8.8.8.8           Show the hostname for an IP address
   netctl dns servers                   Show currently configured DNS servers
+  netctl dns cache                     Show cache size, hit rate, and entries
   netctl dns flush                     Flush the system DNS cache
-  netctl dns benchmark                 Benchmark Cloudflare, Google, Quad9, etc.")]
+  netctl dns leak                      Check whether DNS escapes the VPN tunnel
+  netctl dns benchmark                 Benchmark Cloudflare, Google, Quad9, etc.
+  netctl dns benchmark --explain       Explain whether switching resolvers helps
+  netctl dns benchmark --query-type dnskey   Benchmark large DNSSEC answers
+  netctl dns benchmark --json          Per-resolver latency stats as JSON
+  netctl dns benchmark --servers 10.0.0.2,1.1.1.1 --domains intranet.corp,github.com")]
     Dns {
         #[command(subcommand)]
         action: DnsAction,
     },
+
+    /// Wi-Fi connection details (SSID, signal, channel)
+    #[command(long_about = "\
+Wi-Fi connection details (SSID, signal, channel)
+
+Shows the current Wi-Fi network name, access point BSSID, channel and band,
+signal strength (RSSI), noise, and link rate. Uses airport/wdutil on macOS
+and iw/nmcli on Linux. A weak signal or crowded channel often explains poor
+speed and ping results.
+
+Examples:
+  netctl wifi                          Show the current Wi-Fi connection
+  netctl wifi --json                   Output the details as JSON")]
+    Wifi,
+
+    /// IPv6 connectivity diagnostics
+    #[command(long_about = "\
+IPv6 connectivity diagnostics
+
+Checks each layer of IPv6 in turn: a global address is assigned, a default
+IPv6 route exists, Cloudflare's anycast resolver answers over IPv6, and DNS
+returns AAAA records. Prints pass/fail for every step and an overall verdict
+of fully working, address-only, or broken.
+
+Examples:
+  netctl ip6check                      Diagnose IPv6 connectivity")]
+    Ip6check,
+
+    /// Latency matrix across reference hosts
+    #[command(long_about = "\
+Latency matrix across reference hosts
+
+Pings your default gateway, your DNS server, Cloudflare, and Google at the
+same time and prints average latency, loss, and score for each in one table.
+Comparing them shows where a problem sits: a slow gateway points at Wi-Fi or
+the LAN, a slow DNS server at the resolver, and slow public hosts upstream.
+
+Examples:
+  netctl latency                       Check the built-in reference hosts
+  netctl latency --hosts 1.1.1.1,9.9.9.9,github.com  Compare your own set
+  netctl latency --count 20            Send more pings per host")]
+    Latency {
+        /// Hosts to compare instead of the reference set (comma-separated)
+        #[arg(long)]
+        hosts: Option<String>,
+
+        /// Ping packets per host (default 5)
+        #[arg(long)]
+        count: Option<u32>,
+    },
+
+    /// Print a shell completion script
+    #[command(long_about = "\
+Print a shell completion script
+
+Writes a script to stdout that completes netctl's subcommands, flags, and
+fixed flag values (like --style and --type) in bash, zsh, fish, or
+PowerShell. Save it where your shell loads completions from.
+
+Examples:
+  netctl completions bash > ~/.local/share/bash-completion/completions/netctl
+  netctl completions zsh > ~/.zfunc/_netctl    Then add ~/.zfunc to fpath
+  netctl completions fish > ~/.config/fish/completions/netctl.fish
+  netctl completions powershell >> $PROFILE")]
+    Completions {
+        /// Shell to generate the script for
+        shell: completions::Shell,
+    },
 }
 
 #[derive(Subcommand)]
 enum VpnAction {
     /// Show VPN connection status
     Status {
-        /// Show detailed VPN information
+        /// Show detailed VPN information, including each tunnel's latency
         #[arg(long)]
         detailed: bool,
     },
     /// Monitor VPN connection continuously
-    Watch,
+    Watch {
+        /// Stream each snapshot as NDJSON to a file or named pipe ("-" for stdout)
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Append link events (tunnel up/down, IP, public IP and route changes) to a file
+        #[arg(long)]
+        log: Option<String>,
+
+        /// Seconds between refreshes
+        #[arg(long, value_name = "SECS", default_value = "5")]
+        interval: u64,
+
+        /// Exit with status 3 as soon as the VPN goes down (or comes up, if
+        /// it was down at the start)
+        #[arg(long)]
+        until_change: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum DnsAction {
     /// Resolve a domain name
     Resolve {
-        /// Domain to resolve
+        /// Domain to resolve, or several separated by commas
         domain: String,
+
+        /// Show each search-domain expansion the resolver tries, and its cost
+        #[arg(long)]
+        trace_search: bool,
+
+        /// Resolve over DNS-over-HTTPS instead of the system resolver
+        #[arg(long, conflicts_with = "trace_search")]
+        doh: bool,
+
+        /// DoH endpoint to query (implies --doh; default Cloudflare)
+        #[arg(long, value_name = "URL", conflicts_with = "trace_search")]
+        doh_server: Option<String>,
+
+        /// Query one record type (a, aaaa, mx, txt, cname, ns, ptr); ptr takes an IP
+        #[arg(long = "type", value_enum, conflicts_with = "trace_search")]
+        record_type: Option<dns::RecordType>,
+    },
+    /// Look up the hostname for an IP address (PTR record)
+    Reverse {
+        /// IPv4 or IPv6 address to look up
+        ip: String,
     },
+    /// Check whether DNS queries escape the VPN tunnel
+    #[command(name = "leak")]
+    LeakTest,
     /// Flush DNS cache
     Flush,
+    /// Show what the system resolver has cached
+    Cache,
     /// Show current DNS servers
     Servers,
     /// Benchmark DNS resolver performance
-    Benchmark,
+    Benchmark {
+        /// Explain what the results mean in plain language
+        #[arg(long)]
+        explain: bool,
+
+        /// Record type to query (a, aaaa, txt, mx, dnskey)
+        #[arg(long, value_enum, default_value_t = dns::QueryType::A)]
+        query_type: dns::QueryType,
+
+        /// Resolvers to test instead of the public defaults (comma-separated IPs)
+        #[arg(long, value_delimiter = ',', value_name = "IPS")]
+        servers: Vec<String>,
+
+        /// Domains to query instead of the built-in test set (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        domains: Vec<String>,
+    },
+}
+
+/// Why `--json` can't apply to `command`, if it can't: the command has no
+/// JSON output, or another flag changes what it prints. Checked here rather
+/// than with clap conflicts, which miss a global flag given before the
+/// subcommand.
+fn json_conflict(command: &Commands) -> Option<String> {
+    let with = |flags: &[(bool, &str)]| {
+        flags
+            .iter()
+            .find(|(set, _)| *set)
+            .map(|(_, flag)| format!("--json cannot be used with {}", flag))
+    };
+    let unsupported = |name: &str| Some(format!("`netctl {}` has no JSON output", name));
+    match command {
+        Commands::Speed { output, serve, .. } => {
+            with(&[(output.is_some(), "--output"), (serve.is_some(), "--serve")])
+        }
+        Commands::Connections {
+            watch,
+            output,
+            by_remote,
+            group,
+            ..
+        } => with(&[
+            (*watch, "--watch"),
+            (output.is_some(), "--output"),
+            (*by_remote, "--by-remote"),
+            (*group, "--group"),
+        ]),
+        Commands::Bandwidth {
+            watch,
+            output,
+            report,
+            ..
+        } => with(&[
+            (*watch, "--watch"),
+            (output.is_some(), "--output"),
+            (*report, "--report"),
+        ]),
+        Commands::Ping {
+            with_gateway,
+            mtu,
+            csv,
+            watch,
+            ..
+        } => with(&[
+            (*with_gateway, "--with-gateway"),
+            (*mtu, "--mtu"),
+            (*csv, "--csv"),
+            (*watch, "--watch"),
+        ]),
+        Commands::Trace { .. } => unsupported("trace"),
+        Commands::Block { .. } => unsupported("block"),
+        Commands::Vpn { action } => match action {
+            VpnAction::Status { .. } => None,
+            VpnAction::Watch { .. } => unsupported("vpn watch"),
+        },
+        Commands::Dns { action } => match action {
+            DnsAction::Resolve {
+                trace_search,
+                doh,
+                doh_server,
+                record_type,
+                ..
+            } => with(&[
+                (*trace_search, "--trace-search"),
+                (*doh || doh_server.is_some(), "--doh"),
+                (record_type.is_some(), "--type"),
+            ]),
+            DnsAction::Reverse { .. } => unsupported("dns reverse"),
+            DnsAction::LeakTest => unsupported("dns leak"),
+            DnsAction::Flush => unsupported("dns flush"),
+            DnsAction::Cache => unsupported("dns cache"),
+            DnsAction::Servers => unsupported("dns servers"),
+            DnsAction::Benchmark { .. } => None,
+        },
+        Commands::Wifi => None,
+        Commands::Ip6check => unsupported("ip6check"),
+        Commands::Latency { .. } => unsupported("latency"),
+        Commands::Completions { .. } => unsupported("completions"),
+    }
+}
+
+/// Sleep until the time given with `--at`, announcing on stderr when the
+/// command will run so stdout carries only its output.
+async fn wait_until(at: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let when = utils::parse_at(at)
+        .ok_or_else(|| format!("Invalid time '{}' (use e.g. 14:00 or 2026-10-15 03:00)", at))?;
+    let delay = (when - chrono::Local::now()).to_std().map_err(|_| {
+        format!(
+            "Scheduled time {} is in the past",
+            when.format("%Y-%m-%d %H:%M")
+        )
+    })?;
+
+    let mins = delay.as_secs() / 60;
+    eprintln!(
+        "Scheduled for {} (in {}h {:02}m). Press Ctrl+C to cancel.",
+        when.format("%Y-%m-%d %H:%M:%S"),
+        mins / 60,
+        mins % 60
+    );
+    tokio::time::sleep(delay).await;
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    display::init_color(cli.no_color);
+    logging::init(cli.verbose);
+    if let Err(e) = config::init() {
+        eprintln!("{}: {}", colored::Colorize::red("Error"), e);
+        std::process::exit(1);
+    }
+    let config = config::get();
+    output::set_compact_json(cli.compact_json);
+    display::set_no_clear(cli.no_clear);
+    display::set_unit_mode(display::UnitMode::from_binary_flag(cli.binary));
+
+    let mode = output::OutputMode::from_json_flag(cli.json);
+    if let Some(why) = json_conflict(&cli.command).filter(|_| mode.is_json()) {
+        Cli::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, why)
+            .exit();
+    }
+
+    if let Some(ref at) = cli.at {
+        if let Err(e) = wait_until(at).await {
+            eprintln!("{}: {}", colored::Colorize::red("Error"), e);
+            std::process::exit(1);
+        }
+    }
 
+    // Set by commands that end normally but report an outcome in the status
+    let mut exit_status = 0;
     let result = match cli.command {
         Commands::Speed {
             server,
+            auto,
             detailed,
             output,
-        } => speed::run(server, detailed, output).await,
+            append,
+            explain,
+            simple_jitter,
+            runs,
+            ping_host,
+            serve,
+            server_url,
+            download_url,
+            upload_url,
+            size,
+            duration,
+            connections,
+            bufferbloat,
+        } => {
+            if let Some(addr) = serve {
+                speed_server::serve(&addr).await
+            } else {
+                // The config's server only applies when nothing else picks one
+                let server = match (&server_url, &download_url, &upload_url, auto) {
+                    (None, None, None, false) => server.or_else(|| config.speed_server.clone()),
+                    _ => server,
+                };
+                let selected =
+                    speed::select_server(server.as_deref(), server_url, download_url, upload_url)
+                        .and_then(|server| {
+                            Ok((server, speed::payload(size.as_deref(), duration)?))
+                        });
+                match selected {
+                    Ok((server, payload)) => {
+                        let opts = speed::SpeedOptions {
+                            detailed,
+                            output,
+                            mode,
+                            append,
+                            explain,
+                            simple_jitter,
+                            runs,
+                            ping_host,
+                            connections,
+                            bufferbloat,
+                            auto,
+                            payload,
+                        };
+                        speed::run(server, opts).await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        }
 
         Commands::Connections {
             app,
             external,
+            user,
+            port,
+            proto,
             watch,
             interval,
-        } => connections::run(app, external, watch, interval).await,
+            output,
+            exclude,
+            by_remote,
+            min_rtt,
+            group,
+            limit,
+            resolve,
+            geo,
+            geoip_db,
+        } => {
+            let filter = connections::ConnectionFilter {
+                apps: utils::AppFilter::new(app, exclude),
+                external_only: external,
+                user,
+                port,
+                transport: proto,
+                min_rtt_ms: min_rtt,
+            };
+            let opts = connections::ConnectionsOptions {
+                watch,
+                interval,
+                output,
+                by_remote,
+                group,
+                mode,
+                limit,
+                resolve,
+                geo,
+                geoip_db,
+            };
+            connections::run(filter, opts, cli.style).await
+        }
 
         Commands::Bandwidth {
             top,
+            sort,
+            reverse,
             app,
+            exclude,
             alert,
+            alert_cmd,
             watch,
-        } => bandwidth::run(top, app, alert, watch).await,
+            interval,
+            output,
+            record,
+            per_interface,
+            report,
+            last,
+            include_virtual,
+            exclude_iface,
+        } => {
+            let filter = bandwidth::BandwidthFilter {
+                top,
+                apps: utils::AppFilter::new(app, exclude),
+                interfaces: utils::IfaceFilter::new(include_virtual, exclude_iface.as_deref()),
+                sort,
+                reverse,
+            };
+            if report {
+                bandwidth::report(&last, &filter, cli.style)
+            } else {
+                let opts = bandwidth::BandwidthOptions {
+                    watch,
+                    interval: interval.or(config.bandwidth_interval).unwrap_or(2),
+                    output,
+                    record,
+                    mode,
+                    per_interface,
+                    alert_cmd,
+                };
+                bandwidth::run(filter, alert, opts, cli.style).await
+            }
+        }
 
         Commands::Ping {
             host,
             count,
             hosts,
-        } => ping::run(host, count, hosts).await,
+            explain,
+            simple_jitter,
+            tcp,
+            source,
+            index,
+            all_ips,
+            with_gateway,
+            mtu,
+            csv,
+            watch,
+            interval,
+            timeout,
+            ipv4,
+            ipv6,
+        } => {
+            let family = match (ipv4, ipv6) {
+                (true, _) => Some(ping::IpFamily::V4),
+                (_, true) => Some(ping::IpFamily::V6),
+                _ => None,
+            };
+            let selection = match (index, all_ips) {
+                (_, true) => ping::IpSelection::All,
+                (Some(i), false) => ping::IpSelection::Index(i),
+                (None, false) => ping::IpSelection::First,
+            };
+            let opts = ping::PingOptions {
+                count: count.or(config.ping_count).unwrap_or(10),
+                explain,
+                simple_jitter,
+                tcp,
+                source,
+                selection,
+                with_gateway,
+                mtu,
+                mode,
+                csv,
+                watch,
+                interval,
+                timeout,
+                family,
+            };
+            ping::run(ping::targets(host, hosts), opts).await
+        }
+
+        Commands::Trace { host, max_hops } => trace::run(&host, max_hops).await,
 
         Commands::Block {
             add,
@@ -292,23 +1187,105 @@ async fn main() {
             enable,
             disable,
             duration,
-        } => block::run(add, remove, list, enable, disable, duration),
+            subdomains,
+            preset,
+            list_categories,
+            stats,
+            schedule,
+            unschedule,
+            apply,
+            restore,
+            allow,
+            unallow,
+            no_flush,
+            pomodoro,
+            break_mins,
+            cycles,
+        } => {
+            let add = block::merge_preset(add, preset);
+            let subdomains = block::parse_subdomains(subdomains.as_deref());
+            match pomodoro {
+                _ if list_categories => block::list_presets(),
+                Some(work_mins) => {
+                    block::pomodoro(add, subdomains, work_mins, break_mins, cycles, !no_flush).await
+                }
+                None => {
+                    let opts = block::BlockOptions {
+                        add,
+                        remove,
+                        list,
+                        enable,
+                        disable,
+                        duration,
+                        subdomains,
+                        schedule,
+                        unschedule,
+                        apply,
+                        restore,
+                        flush: !no_flush,
+                        allow,
+                        unallow,
+                        stats,
+                    };
+                    block::run(opts).await
+                }
+            }
+        }
 
         Commands::Vpn { action } => match action {
-            VpnAction::Status { detailed } => vpn::status(detailed).await,
-            VpnAction::Watch => vpn::watch().await,
+            VpnAction::Status { detailed } => vpn::status(detailed, mode).await,
+            VpnAction::Watch {
+                output,
+                log,
+                interval,
+                until_change,
+            } => vpn::watch(output, log, interval, until_change)
+                .await
+                .map(|end| {
+                    if end == vpn::WatchEnd::StateChanged {
+                        exit_status = vpn::STATE_CHANGED_EXIT;
+                    }
+                }),
         },
 
         Commands::Dns { action } => match action {
-            DnsAction::Resolve { domain } => dns::resolve(&domain).await,
+            DnsAction::Resolve {
+                domain,
+                trace_search,
+                doh,
+                doh_server,
+                record_type,
+            } => {
+                let doh = doh_server.or_else(|| doh.then(|| dns::DEFAULT_DOH_SERVER.to_string()));
+                dns::resolve(&domain, mode, trace_search, doh.as_deref(), record_type).await
+            }
+            DnsAction::Reverse { ip } => dns::reverse(&ip).await,
+            DnsAction::LeakTest => dns::leak_test().await,
             DnsAction::Flush => dns::flush().await,
+            DnsAction::Cache => dns::cache().await,
             DnsAction::Servers => dns::servers().await,
-            DnsAction::Benchmark => dns::benchmark().await,
+            DnsAction::Benchmark {
+                explain,
+                query_type,
+                servers,
+                domains,
+            } => dns::benchmark(explain, mode, query_type, &servers, &domains, cli.style).await,
         },
+
+        Commands::Wifi => wifi::run(mode).await,
+
+        Commands::Ip6check => ip6check::run().await,
+
+        Commands::Latency { hosts, count } => latency::run(hosts, count, cli.style).await,
+
+        Commands::Completions { shell } => completions::run(Cli::command(), shell),
     };
 
     if let Err(e) = result {
         eprintln!("{}: {}", colored::Colorize::red("Error"), e);
         std::process::exit(1);
     }
+    if exit_status != 0 {
+        std::process::exit(exit_status);
+    }
 }
@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COMPACT_JSON: AtomicBool = AtomicBool::new(false);
+
+/// `--output` path that means "write to standard output".
+pub const STDOUT_PATH: &str = "-";
+
+/// Whether an `--output` path refers to stdout. Commands suppress their
+/// decorative output in that case so the stream stays machine-readable.
+pub fn is_stdout(path: Option<&str>) -> bool {
+    path == Some(STDOUT_PATH)
+}
+
+/// How a command presents its result, chosen with the global `--json` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Tables and colored text
+    Human,
+    /// Only the result, serialized with `to_json`, so stdout can be piped
+    Json,
+}
+
+impl OutputMode {
+    pub fn from_json_flag(json: bool) -> Self {
+        if json {
+            OutputMode::Json
+        } else {
+            OutputMode::Human
+        }
+    }
+
+    pub fn is_json(self) -> bool {
+        self == OutputMode::Json
+    }
+}
+
+/// Make one-shot JSON output single-line instead of pretty-printed.
+pub fn set_compact_json(compact: bool) {
+    COMPACT_JSON.store(compact, Ordering::Relaxed);
+}
+
+/// Serialize a one-shot result (e.g. `--json` or a speed export), pretty by
+/// default or on one line with `--compact-json`. Streamed records are always
+/// compact; see `RecordSink::write`.
+pub fn to_json<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    if COMPACT_JSON.load(Ordering::Relaxed) {
+        serde_json::to_string(value)
+    } else {
+        serde_json::to_string_pretty(value)
+    }
+}
+
+/// Add a one-shot result to the history kept at `path` instead of
+/// overwriting it. A JSON array gets the value pushed onto it and an NDJSON
+/// log gets one more line; a missing or empty file becomes a one-element
+/// array, and a file holding a single earlier result becomes a two-element one.
+pub fn append_json<T: Serialize>(path: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    let value = serde_json::to_value(value)?;
+    let existing = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let not_history = || format!("{} is not a JSON array or NDJSON log; not appending", path);
+
+    let trimmed = existing.trim();
+    let history = if trimmed.is_empty() {
+        vec![value]
+    } else if let Ok(serde_json::Value::Array(mut history)) = serde_json::from_str(trimmed) {
+        history.push(value);
+        history
+    } else if let Ok(single @ serde_json::Value::Object(_)) = serde_json::from_str(trimmed) {
+        vec![single, value]
+    } else {
+        let is_ndjson = trimmed
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .all(|l| serde_json::from_str::<serde_json::Value>(l).is_ok());
+        if !is_ndjson {
+            return Err(not_history().into());
+        }
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        let separator = if existing.ends_with('\n') { "" } else { "\n" };
+        writeln!(file, "{}{}", separator, serde_json::to_string(&value)?)?;
+        return Ok(());
+    };
+    std::fs::write(path, to_json(&history)?)?;
+    Ok(())
+}
+
+/// A destination for newline-delimited JSON records, such as a log file or a
+/// named pipe read by another process.
+pub struct RecordSink {
+    writer: Box<dyn Write>,
+}
+
+impl RecordSink {
+    /// Open the sink at `path`, appending to regular files, or stdout for `-`.
+    /// Opening a FIFO blocks until a reader connects.
+    pub fn open(path: &str) -> io::Result<Self> {
+        if path == STDOUT_PATH {
+            return Ok(RecordSink {
+                writer: Box::new(io::stdout()),
+            });
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(RecordSink {
+            writer: Box::new(file),
+        })
+    }
+
+    /// Write one record as a single JSON line and flush it immediately.
+    ///
+    /// Returns `Ok(false)` when the reading end has gone away (broken pipe),
+    /// so watch loops can stop cleanly instead of failing.
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let result = self
+            .writer
+            .write_all(&line)
+            .and_then(|_| self.writer.flush());
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Current local time as an RFC 3339 timestamp for records.
+pub fn timestamp() -> String {
+    chrono::Local::now().to_rfc3339()
+}
@@ -1,16 +1,111 @@
+use crate::display;
+use crate::error::NetctlError;
+use crate::utils::{is_elevated, rfc3550_jitter, run_command, simple_jitter, COMMAND_TIMEOUT};
 use colored::Colorize;
-use std::time::Instant;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
-struct PingStats {
+pub struct PingStats {
     host: String,
     ip: String,
     sent: u32,
     received: u32,
     latencies: Vec<f64>,
+    /// Whether each probe, in send order, got a reply
+    outcomes: Vec<bool>,
+    /// What sent the probes instead when no ICMP socket was permitted
+    fallback: Option<&'static str>,
+}
+
+/// Machine-readable summary of one ping run for `--json` and `--csv`.
+#[derive(Serialize)]
+struct PingReport {
+    host: String,
+    ip: String,
+    sent: u32,
+    received: u32,
+    loss_pct: f64,
+    min_ms: Option<f64>,
+    avg_ms: Option<f64>,
+    max_ms: Option<f64>,
+    stddev_ms: Option<f64>,
+    jitter_ms: Option<f64>,
+    /// Separate stretches of consecutive lost probes
+    loss_events: usize,
+    /// Most probes lost in a row
+    longest_loss_burst: usize,
+    quality_score: u8,
+    /// Round-trip time of every reply, in the order they were sent (not in CSV)
+    latencies_ms: Vec<f64>,
+}
+
+/// Column names for `--csv`, matching `PingReport::csv_row`.
+const CSV_HEADER: &str = "host,ip,sent,received,loss_pct,min_ms,avg_ms,max_ms,stddev_ms,jitter_ms,loss_events,longest_loss_burst,quality_score";
+
+impl PingReport {
+    /// One `--csv` line. Statistics of a host that never answered are empty.
+    fn csv_row(&self) -> String {
+        let ms = |v: Option<f64>| v.map(|v| format!("{:.3}", v)).unwrap_or_default();
+        // Quote a field only if it would otherwise break the row
+        let text = |s: &str| {
+            if s.contains([',', '"', '\n']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.to_string()
+            }
+        };
+        format!(
+            "{},{},{},{},{:.1},{},{},{},{},{},{},{},{}",
+            text(&self.host),
+            text(&self.ip),
+            self.sent,
+            self.received,
+            self.loss_pct,
+            ms(self.min_ms),
+            ms(self.avg_ms),
+            ms(self.max_ms),
+            ms(self.stddev_ms),
+            ms(self.jitter_ms),
+            self.loss_events,
+            self.longest_loss_burst,
+            self.quality_score
+        )
+    }
 }
 
 impl PingStats {
-    fn loss_pct(&self) -> f64 {
+    fn new(host: &str, ip: &str) -> Self {
+        PingStats {
+            host: host.to_string(),
+            ip: ip.to_string(),
+            sent: 0,
+            received: 0,
+            latencies: Vec::new(),
+            outcomes: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    /// Add one probe's result: its round-trip time in ms, or `None` if lost.
+    fn record(&mut self, rtt: Option<f64>) {
+        self.sent += 1;
+        self.outcomes.push(rtt.is_some());
+        if let Some(ms) = rtt {
+            self.latencies.push(ms);
+            self.received += 1;
+        }
+    }
+
+    pub fn ip(&self) -> &str {
+        &self.ip
+    }
+
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    pub fn loss_pct(&self) -> f64 {
         if self.sent == 0 {
             return 0.0;
         }
@@ -25,7 +120,7 @@ impl PingStats {
         self.latencies.iter().cloned().fold(0.0_f64, f64::max)
     }
 
-    fn avg(&self) -> f64 {
+    pub fn avg(&self) -> f64 {
         if self.latencies.is_empty() {
             return 0.0;
         }
@@ -42,48 +137,372 @@ impl PingStats {
         variance.sqrt()
     }
 
-    fn jitter(&self) -> f64 {
-        if self.latencies.len() < 2 {
-            return 0.0;
+    /// Lengths of each run of consecutive lost probes.
+    fn loss_runs(&self) -> Vec<usize> {
+        self.outcomes
+            .split(|&ok| ok)
+            .map(|run| run.len())
+            .filter(|&len| len > 0)
+            .collect()
+    }
+
+    pub fn loss_events(&self) -> usize {
+        self.loss_runs().len()
+    }
+
+    pub fn longest_loss_burst(&self) -> usize {
+        self.loss_runs().into_iter().max().unwrap_or(0)
+    }
+
+    fn report(&self, simple_jitter: bool) -> PingReport {
+        let answered = !self.latencies.is_empty();
+        PingReport {
+            host: self.host.clone(),
+            ip: self.ip.clone(),
+            sent: self.sent,
+            received: self.received,
+            loss_pct: self.loss_pct(),
+            min_ms: answered.then(|| self.min()),
+            avg_ms: answered.then(|| self.avg()),
+            max_ms: answered.then(|| self.max()),
+            stddev_ms: answered.then(|| self.std_dev()),
+            jitter_ms: answered.then(|| self.jitter(simple_jitter)),
+            loss_events: self.loss_events(),
+            longest_loss_burst: self.longest_loss_burst(),
+            quality_score: display::quality_score(
+                self.avg(),
+                Some(self.jitter(simple_jitter)),
+                Some(self.loss_pct()),
+            ),
+            latencies_ms: self.latencies.clone(),
+        }
+    }
+
+    /// RFC 3550 jitter, or the mean consecutive difference when `simple`.
+    pub fn jitter(&self, simple: bool) -> f64 {
+        if simple {
+            simple_jitter(&self.latencies)
+        } else {
+            rfc3550_jitter(&self.latencies)
         }
-        let diffs: Vec<f64> = self
-            .latencies
-            .windows(2)
-            .map(|w| (w[1] - w[0]).abs())
-            .collect();
-        diffs.iter().sum::<f64>() / diffs.len() as f64
     }
 }
 
-/// Resolve a hostname to its IP address.
-fn resolve_host(host: &str) -> Option<String> {
-    use dns_lookup::lookup_host;
-    match lookup_host(host) {
-        Ok(ips) => ips.into_iter().find(|ip| ip.is_ipv4()).map(|ip| ip.to_string()),
-        Err(_) => None,
+/// Gap between probes, below which the system `ping` (and so netctl)
+/// requires root.
+const MIN_UNPRIVILEGED_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How far apart probes go out and how long each waits for a reply.
+#[derive(Debug, Clone, Copy)]
+struct Timing {
+    /// `None` keeps each method's default: 1 s for ICMP, 200 ms for TCP
+    interval: Option<Duration>,
+    timeout: Duration,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            interval: None,
+            timeout: Duration::from_secs(2),
+        }
     }
 }
 
-/// Perform ping using the system `ping` command and parse output.
-async fn do_ping(host: &str, count: u32) -> PingStats {
-    let ip = resolve_host(host).unwrap_or_else(|| host.to_string());
+impl Timing {
+    /// Build from `--interval` (ms) and `--timeout` (s), refusing values the
+    /// system `ping` would: zero, or sub-200 ms intervals without root.
+    fn from_args(interval_ms: Option<u64>, timeout_secs: u64) -> Result<Self, String> {
+        if timeout_secs == 0 {
+            return Err("--timeout must be at least 1 second".to_string());
+        }
+        let interval = interval_ms.map(Duration::from_millis);
+        match interval {
+            Some(d) if d.is_zero() => return Err("--interval must be at least 1 ms".to_string()),
+            Some(d) if d < MIN_UNPRIVILEGED_INTERVAL && !is_elevated() => {
+                return Err(format!(
+                    "--interval {} ms needs root: below {} ms counts as flooding \
+                     (ping refuses it too). Re-run with sudo or use --interval {}",
+                    d.as_millis(),
+                    MIN_UNPRIVILEGED_INTERVAL.as_millis(),
+                    MIN_UNPRIVILEGED_INTERVAL.as_millis()
+                ))
+            }
+            _ => {}
+        }
+        Ok(Timing {
+            interval,
+            timeout: Duration::from_secs(timeout_secs),
+        })
+    }
+
+    fn icmp_interval(&self) -> Duration {
+        self.interval.unwrap_or(Duration::from_secs(1))
+    }
 
-    let mut stats = PingStats {
-        host: host.to_string(),
-        ip: ip.clone(),
-        sent: count,
-        received: 0,
-        latencies: Vec::new(),
+    fn tcp_interval(&self) -> Duration {
+        self.interval.unwrap_or(Duration::from_millis(200))
+    }
+}
+
+/// Which of a host's resolved addresses to ping.
+#[derive(Debug, Clone, Copy)]
+pub enum IpSelection {
+    First,
+    Index(usize),
+    All,
+}
+
+/// Address family forced with `-4` or `-6`.
+#[derive(Debug, Clone, Copy)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn matches(self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// Settings shared by every target in one `netctl ping` invocation.
+pub struct PingOptions {
+    pub count: u32,
+    pub explain: bool,
+    pub simple_jitter: bool,
+    pub tcp: bool,
+    pub source: Option<String>,
+    pub selection: IpSelection,
+    pub with_gateway: bool,
+    pub mtu: bool,
+    pub mode: crate::output::OutputMode,
+    /// One CSV row of summary statistics per address, after a header
+    pub csv: bool,
+    /// Ping until Ctrl+C with a live summary instead of sending `count`
+    pub watch: bool,
+    /// Milliseconds between probes (`--interval`)
+    pub interval: Option<u64>,
+    /// Seconds each probe waits for a reply (`--timeout`)
+    pub timeout: u64,
+    /// Only ping addresses of this family; by default IPv4 comes first
+    pub family: Option<IpFamily>,
+}
+
+/// Resolve a hostname to all of its addresses, IPv4 first and sorted, so
+/// round-robin DNS answers always map to the same order.
+fn resolve_all(host: &str) -> Vec<IpAddr> {
+    if let Ok(ip) = host.parse() {
+        return vec![ip];
+    }
+    let mut ips = dns_lookup::lookup_host(host).unwrap_or_default();
+    ips.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+    ips.dedup();
+    log::debug!("{} resolved to {:?}", host, ips);
+    ips
+}
+
+/// Resolve `target` keeping only addresses of `family`, if one was forced.
+/// Errors when the name resolved but had none of that family.
+fn resolve_family(
+    target: &str,
+    family: Option<IpFamily>,
+) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+    let resolved = resolve_all(target);
+    let Some(family) = family else {
+        return Ok(resolved);
+    };
+    let kept: Vec<IpAddr> = resolved
+        .iter()
+        .copied()
+        .filter(|ip| family.matches(ip))
+        .collect();
+    if kept.is_empty() && !resolved.is_empty() {
+        let (name, flag) = match family {
+            IpFamily::V4 => ("IPv4", "-6"),
+            IpFamily::V6 => ("IPv6", "-4"),
+        };
+        return Err(format!(
+            "{} has no {} address (resolved to {}); try {}",
+            target,
+            name,
+            resolved
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            flag
+        )
+        .into());
+    }
+    Ok(kept)
+}
+
+/// Resolve a hostname to the IP address pinged by default.
+pub fn resolve_host(host: &str) -> Option<String> {
+    resolve_all(host).first().map(|ip| ip.to_string())
+}
+
+/// Turn `--source` into a bind address: either a literal IP or the name of
+/// an interface whose address should be used (e.g. "utun3" or "wlan0").
+async fn resolve_source(source: &str) -> Result<IpAddr, String> {
+    if let Ok(ip) = source.parse() {
+        return Ok(ip);
+    }
+    crate::vpn::get_interface_ip(source)
+        .await
+        .and_then(|ip| ip.parse().ok())
+        .ok_or_else(|| format!("'{}' is not an IP address or an interface with one", source))
+}
+
+/// Open a TCP connection, binding the local end to `source` when given so
+/// the probe leaves through that address's interface.
+async fn tcp_connect(
+    target: SocketAddr,
+    source: Option<IpAddr>,
+) -> std::io::Result<tokio::net::TcpStream> {
+    let Some(source) = source else {
+        return tokio::net::TcpStream::connect(target).await;
     };
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(target),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    socket.bind(&SocketAddr::new(source, 0).into())?;
+    socket.set_nonblocking(true)?;
+    tokio::net::TcpSocket::from_std_stream(socket.into())
+        .connect(target)
+        .await
+}
+
+/// Time one TCP connect to `target`, in ms, or `None` if it failed or
+/// took longer than `timeout`.
+async fn tcp_once(target: SocketAddr, source: Option<IpAddr>, timeout: Duration) -> Option<f64> {
+    let start = Instant::now();
+    let connected = matches!(
+        tokio::time::timeout(timeout, tcp_connect(target, source)).await,
+        Ok(Ok(_))
+    );
+    connected.then(|| start.elapsed().as_secs_f64() * 1000.0)
+}
+
+/// Time `count` TCP connects to port 80 of `ip`, recording each success.
+async fn tcp_probe(
+    stats: &mut PingStats,
+    ip: &str,
+    count: u32,
+    source: Option<IpAddr>,
+    timing: Timing,
+) {
+    let Ok(ip) = ip.parse::<IpAddr>() else {
+        stats.sent = count;
+        return;
+    };
+    let target = SocketAddr::new(ip, 80);
+    for _ in 0..count {
+        stats.record(tcp_once(target, source, timing.timeout).await);
+        tokio::time::sleep(timing.tcp_interval()).await;
+    }
+}
+
+/// Ping the first address `host` resolves to.
+pub async fn do_ping(host: &str, count: u32, tcp: bool, source: Option<IpAddr>) -> PingStats {
+    let ip = resolve_host(host).unwrap_or_else(|| host.to_string());
+    ping_ip(host, &ip, count, tcp, source, Timing::default()).await
+}
+
+/// Time `count` TCP connects to `target`, for hosts that drop ICMP but
+/// listen on a known port.
+pub async fn tcp_ping(host: &str, target: SocketAddr, count: u32) -> PingStats {
+    let mut stats = PingStats::new(host, &target.ip().to_string());
+    let timing = Timing::default();
+    for _ in 0..count {
+        stats.record(tcp_once(target, None, timing.timeout).await);
+        tokio::time::sleep(timing.tcp_interval()).await;
+    }
+    stats
+}
+
+/// Ping with native ICMP echo requests, falling back to the system `ping`
+/// command when ICMP sockets aren't permitted, and to TCP connects when
+/// that can't run either. With `tcp` set (or a source address), time TCP
+/// connects instead.
+async fn ping_ip(
+    host: &str,
+    ip: &str,
+    count: u32,
+    tcp: bool,
+    source: Option<IpAddr>,
+    timing: Timing,
+) -> PingStats {
+    let mut stats = PingStats::new(host, ip);
+
+    if tcp || source.is_some() {
+        tcp_probe(&mut stats, ip, count, source, timing).await;
+        return stats;
+    }
+
+    let mut denied = false;
+    if let Ok(addr) = ip.parse::<IpAddr>() {
+        let result = crate::icmp::ping(
+            addr,
+            Some(count),
+            timing.icmp_interval(),
+            timing.timeout,
+            |rtt| stats.record(rtt),
+        )
+        .await;
+        match result {
+            Ok(()) => return stats,
+            Err(e) => {
+                log::debug!("no ICMP socket ({}), falling back to system ping", e);
+                denied = e.kind() == std::io::ErrorKind::PermissionDenied;
+            }
+        }
+    }
 
-    // Use system ping command - works on both macOS and Linux
-    let output = std::process::Command::new("ping")
-        .args(["-c", &count.to_string(), "-W", "2", host])
-        .output();
+    // Use system ping command - works on both macOS and Linux. Ping the
+    // resolved address rather than the name so the chosen IP is the one hit.
+    // macOS takes -W in milliseconds, Linux in seconds. For IPv6, macOS has
+    // a separate ping6 (without -W); Linux ping takes -6.
+    let v6 = ip.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv6());
+    let program = if v6 && cfg!(target_os = "macos") {
+        "ping6"
+    } else {
+        "ping"
+    };
+    let count_arg = count.to_string();
+    let wait_arg = if cfg!(target_os = "macos") {
+        timing.timeout.as_millis().to_string()
+    } else {
+        timing.timeout.as_secs().to_string()
+    };
+    let mut args = vec!["-c", &count_arg];
+    if program == "ping" {
+        args.extend(["-W", wait_arg.as_str()]);
+    }
+    if v6 && program == "ping" {
+        args.push("-6");
+    }
+    let interval_arg = timing.interval.map(|d| format!("{:.3}", d.as_secs_f64()));
+    if let Some(ref interval) = interval_arg {
+        args.extend(["-i", interval.as_str()]);
+    }
+    args.push(ip);
+    // The last probe goes out after count-1 intervals, plus its timeout and slack
+    let deadline =
+        timing.icmp_interval() * count.saturating_sub(1) + timing.timeout + Duration::from_secs(2);
+    let output = run_command(program, &args, deadline).await;
 
-    match output {
+    let method = match output {
         Ok(out) => {
+            stats.sent = count;
             let stdout = String::from_utf8_lossy(&out.stdout);
+            let mut replied_seqs = Vec::new();
             for line in stdout.lines() {
                 // Parse lines like: "64 bytes from ...: icmp_seq=1 ttl=117 time=24.3 ms"
                 if line.contains("time=") {
@@ -96,36 +515,137 @@ async fn do_ping(host: &str, count: u32) -> PingStats {
                             stats.received += 1;
                         }
                     }
+                    let seq = line
+                        .split("icmp_seq=")
+                        .nth(1)
+                        .and_then(|s| s.split_whitespace().next())
+                        .and_then(|s| s.parse::<u32>().ok());
+                    replied_seqs.extend(seq);
                 }
             }
+            // macOS numbers probes from 0, Linux from 1
+            let first_seq = if cfg!(target_os = "macos") { 0 } else { 1 };
+            stats.outcomes = (0..count)
+                .map(|i| replied_seqs.contains(&(first_seq + i)))
+                .collect();
+            "the system ping"
+        }
+        // ping exits nonzero when nothing answered, so every probe was lost,
+        // unless it wasn't allowed to send at all
+        Err(NetctlError::Failed { ref stderr, .. })
+            if stderr.contains("not permitted") || stderr.contains("ermission denied") =>
+        {
+            log::debug!("system ping not permitted, probing with TCP");
+            tcp_probe(&mut stats, ip, count, None, timing).await;
+            "TCP connects"
+        }
+        Err(NetctlError::Failed { .. }) | Err(NetctlError::TimedOut { .. }) => {
+            stats.sent = count;
+            stats.outcomes = vec![false; count as usize];
+            "the system ping"
         }
-        Err(_) => {
+        Err(e) => {
             // Fall back to manual TCP-based ping if system ping is unavailable
-            for _ in 0..count {
-                let target = format!("{}:80", &ip);
-                let start = Instant::now();
-                match tokio::time::timeout(
-                    std::time::Duration::from_secs(2),
-                    tokio::net::TcpStream::connect(&target),
-                )
-                .await
-                {
-                    Ok(Ok(_)) => {
-                        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-                        stats.latencies.push(elapsed);
-                        stats.received += 1;
-                    }
-                    _ => {}
-                }
-                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            log::debug!("system ping unusable ({}), probing with TCP", e);
+            tcp_probe(&mut stats, ip, count, None, timing).await;
+            "TCP connects"
+        }
+    };
+    if denied {
+        stats.fallback = Some(method);
+    }
+
+    stats
+}
+
+/// Redraw the live `--watch` view after each probe.
+fn print_live(stats: &PingStats, last: Option<f64>, simple_jitter: bool) {
+    display::begin_frame();
+    println!(
+        "{} {} ({}) {}",
+        "Pinging".dimmed(),
+        stats.host.cyan(),
+        stats.ip.dimmed(),
+        "until Ctrl+C".dimmed()
+    );
+    let reply = match last {
+        Some(ms) => format!("{:.1} ms", ms).green(),
+        None => "timeout".red(),
+    };
+    println!("Probe {}: {}", stats.sent, reply);
+    print_ping_stats(stats, simple_jitter);
+    display::end_frame();
+}
+
+/// Ping `ip` once a second until Ctrl+C, redrawing the running statistics
+/// after every probe, then print the final summary. Uses native ICMP, or
+/// TCP connects with `tcp`/`source` or when no ICMP socket can be opened
+/// (the system `ping` only reports once it exits).
+async fn watch_ip(
+    host: &str,
+    ip: &str,
+    tcp: bool,
+    source: Option<IpAddr>,
+    timing: Timing,
+    simple_jitter: bool,
+) -> PingStats {
+    crate::utils::catch_interrupt();
+    let mut stats = PingStats::new(host, ip);
+    let Ok(addr) = ip.parse::<IpAddr>() else {
+        println!();
+        println!("{}", format!("Could not resolve {}", host).red());
+        return stats;
+    };
+
+    let mut on_result = |rtt: Option<f64>| {
+        stats.record(rtt);
+        print_live(&stats, rtt, simple_jitter);
+    };
+    let icmp = if tcp || source.is_some() {
+        None
+    } else {
+        let (interval, timeout) = (timing.icmp_interval(), timing.timeout);
+        Some(crate::icmp::ping(addr, None, interval, timeout, &mut on_result).await)
+    };
+    if !matches!(icmp, Some(Ok(()))) {
+        if let Some(Err(e)) = icmp {
+            log::debug!("no ICMP socket ({}), probing with TCP", e);
+        }
+        let target = SocketAddr::new(addr, 80);
+        loop {
+            let start = Instant::now();
+            let rtt = tcp_once(target, source, timing.timeout).await;
+            if crate::utils::interrupted() {
+                break;
+            }
+            on_result(rtt);
+            let rest = timing.icmp_interval().saturating_sub(start.elapsed());
+            if !crate::utils::sleep_unless_interrupted(rest).await {
+                break;
             }
         }
     }
 
+    // Replace the live view with the final numbers
+    display::begin_frame();
+    println!("{}", "Final summary".bold());
+    print_ping_stats(&stats, simple_jitter);
+    display::end_frame();
     stats
 }
 
-fn print_ping_stats(stats: &PingStats) {
+/// Note, once per run, that ICMP sockets were denied and what stood in.
+fn note_fallback(stats: &PingStats, noted: &mut bool) {
+    if let (Some(method), false) = (stats.fallback, *noted) {
+        display::print_unprivileged_note(&format!(
+            "no ICMP socket allowed, pinging with {} instead",
+            method
+        ));
+        *noted = true;
+    }
+}
+
+fn print_ping_stats(stats: &PingStats, simple_jitter: bool) {
     println!();
     println!(
         "{} {} ({})",
@@ -140,6 +660,20 @@ fn print_ping_stats(stats: &PingStats) {
         stats.received,
         stats.loss_pct()
     );
+    if stats.received > 0 && stats.received < stats.sent {
+        let burst = stats.longest_loss_burst();
+        let pattern = if burst >= 3 {
+            "bursty: expect stalls in calls and streams".yellow()
+        } else {
+            "isolated drops".dimmed()
+        };
+        println!(
+            "Loss pattern: {} event(s), longest burst {} ({})",
+            stats.loss_events(),
+            burst,
+            pattern
+        );
+    }
 
     if stats.latencies.is_empty() {
         println!();
@@ -156,7 +690,7 @@ fn print_ping_stats(stats: &PingStats) {
     println!("  Avg:     {:.0} ms", stats.avg());
     println!("  Max:     {:.0} ms", stats.max());
     println!("  Std Dev: {:.0} ms", stats.std_dev());
-    println!("  Jitter:  {:.0} ms", stats.jitter());
+    println!("  Jitter:  {:.0} ms", stats.jitter(simple_jitter));
 
     println!();
     let avg = stats.avg();
@@ -170,27 +704,357 @@ fn print_ping_stats(stats: &PingStats) {
         format!("{} Poor (high latency)", "!!".red())
     };
     println!("Quality: {}", quality);
+    let score = display::quality_score(
+        avg,
+        Some(stats.jitter(simple_jitter)),
+        Some(stats.loss_pct()),
+    );
+    println!("Score:   {}/100", score);
 }
 
-pub async fn run(
-    host: Option<String>,
-    count: u32,
-    hosts: Option<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let targets: Vec<String> = if let Some(hosts_str) = hosts {
+/// IPv4 + ICMP header bytes added to a ping payload.
+const ICMP_OVERHEAD: u32 = 28;
+/// Payload sizes searched for the path MTU (576..1500 byte packets).
+const MTU_PAYLOAD_RANGE: (u32, u32) = (548, 1472);
+
+/// Send one don't-fragment ping of `payload` bytes and report whether it
+/// got an answer.
+async fn ping_df(ip: &str, payload: u32) -> bool {
+    let size = payload.to_string();
+    // macOS sets DF with -D; Linux with -M do
+    let args: &[&str] = if cfg!(target_os = "macos") {
+        &["-c", "1", "-t", "2", "-D", "-s", &size, ip]
+    } else {
+        &["-c", "1", "-W", "2", "-M", "do", "-s", &size, ip]
+    };
+    run_command("ping", args, COMMAND_TIMEOUT)
+        .await
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("time="))
+}
+
+/// Binary-search the largest DF ping that gets through, returning the path
+/// MTU in bytes, or `None` if even the smallest probe is lost.
+async fn discover_mtu(ip: &str) -> Option<u32> {
+    let (mut lo, mut hi) = MTU_PAYLOAD_RANGE;
+    if ping_df(ip, hi).await {
+        return Some(hi + ICMP_OVERHEAD);
+    }
+    if !ping_df(ip, lo).await {
+        return None;
+    }
+    // Invariant: lo gets through, hi does not
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if ping_df(ip, mid).await {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+        log::debug!("mtu search: {} ok, {} lost", lo, hi);
+    }
+    Some(lo + ICMP_OVERHEAD)
+}
+
+async fn print_mtu(target: &str, ip: &str) {
+    println!();
+    println!("{} {} ({})", "Path MTU:".bold(), target.cyan(), ip.dimmed());
+    println!();
+    match discover_mtu(ip).await {
+        Some(1500) => println!("  {} 1500 bytes (standard Ethernet)", "OK".green()),
+        Some(mtu) => {
+            println!(
+                "  {} {} bytes (below the standard 1500)",
+                "!!".yellow(),
+                mtu
+            );
+            let hint = match mtu {
+                1492 => "1492 is typical for PPPoE DSL/fiber links",
+                1400..=1491 => "values in this range usually come from a VPN or tunnel",
+                _ => "something on the path is clamping packet size",
+            };
+            println!("  {}", hint.dimmed());
+            println!(
+                "  {}",
+                "If large transfers stall, lower the interface MTU or enable MSS clamping."
+                    .dimmed()
+            );
+        }
+        None => println!(
+            "  {} No don't-fragment probe was answered (ICMP may be blocked)",
+            "--".dimmed()
+        ),
+    }
+}
+
+/// Build the target list from the positional host or `--hosts`.
+pub fn targets(host: Option<String>, hosts: Option<String>) -> Vec<String> {
+    if let Some(hosts_str) = hosts {
         hosts_str.split(',').map(|s| s.trim().to_string()).collect()
     } else if let Some(h) = host {
         vec![h]
     } else {
         // Default targets
         vec!["google.com".to_string()]
+    }
+}
+
+/// Pick the addresses to ping out of everything `target` resolved to.
+fn select_ips(
+    target: &str,
+    ips: &[IpAddr],
+    selection: IpSelection,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if ips.is_empty() {
+        // Let ping report the failure against the name itself
+        return Ok(vec![target.to_string()]);
+    }
+    let chosen: Vec<IpAddr> = match selection {
+        IpSelection::First => vec![ips[0]],
+        IpSelection::All => ips.to_vec(),
+        IpSelection::Index(i) => match ips.get(i) {
+            Some(ip) => vec![*ip],
+            None => {
+                return Err(format!(
+                    "--index {} is out of range: {} resolved to {} address(es)",
+                    i,
+                    target,
+                    ips.len()
+                )
+                .into())
+            }
+        },
     };
+    Ok(chosen.iter().map(|ip| ip.to_string()).collect())
+}
+
+/// Compare gateway and target latency to say which side of the router a
+/// problem is on.
+fn print_gateway_comparison(gateway: &PingStats, target: &PingStats) {
+    println!();
+    println!("{}", "Local vs. Upstream:".bold());
+    println!();
+    println!(
+        "  Gateway {:<24} avg {:>5.0} ms   loss {:>3.0}%",
+        gateway.ip,
+        gateway.avg(),
+        gateway.loss_pct()
+    );
+    println!(
+        "  Target  {:<24} avg {:>5.0} ms   loss {:>3.0}%",
+        target.host,
+        target.avg(),
+        target.loss_pct()
+    );
+    println!();
+
+    let gateway_bad = gateway.received == 0 || gateway.avg() > 20.0 || gateway.loss_pct() > 0.0;
+    let target_bad = target.received == 0 || target.avg() > 100.0 || target.loss_pct() > 0.0;
+    let verdict = match (gateway_bad, target_bad) {
+        (true, _) if gateway.received == 0 => format!(
+            "{} Gateway did not answer (it may drop pings); cannot separate local from upstream",
+            "--".dimmed()
+        ),
+        (true, _) => format!(
+            "{} Slow or lossy gateway: the problem is likely local (Wi-Fi/LAN)",
+            "!!".yellow()
+        ),
+        (false, true) => format!(
+            "{} Gateway is fine: the slowdown is upstream (ISP or beyond)",
+            "!!".yellow()
+        ),
+        (false, false) => format!(
+            "{} Both the local network and the path look healthy",
+            "OK".green()
+        ),
+    };
+    println!("  {}", verdict);
+}
+
+pub async fn run(
+    targets: Vec<String>,
+    opts: PingOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let PingOptions {
+        count,
+        explain,
+        simple_jitter,
+        tcp,
+        source,
+        selection,
+        with_gateway,
+        mtu,
+        mode,
+        csv,
+        watch,
+        interval,
+        timeout,
+        family,
+    } = opts;
+    let source = match source.as_deref() {
+        Some(source) => Some(resolve_source(source).await?),
+        None => None,
+    };
+    let timing = Timing::from_args(interval, timeout)?;
+
+    if watch {
+        let target = &targets[0];
+        let resolved = resolve_family(target, family)?;
+        let ip = select_ips(target, &resolved, selection)?.remove(0);
+        let stats = watch_ip(target, &ip, tcp, source, timing, simple_jitter).await;
+        if explain && stats.received > 0 {
+            crate::explain::print(&crate::explain::connection(
+                stats.avg(),
+                Some(stats.jitter(simple_jitter)),
+                Some(stats.loss_pct()),
+                None,
+            ));
+        }
+        println!();
+        return Ok(());
+    }
 
+    // Resolve everything up front so a bad --index fails before any probing
+    let mut plan = Vec::new();
     for target in &targets {
+        let resolved = resolve_family(target, family)?;
+        let ips = select_ips(target, &resolved, selection)?;
+        plan.push((target.clone(), resolved, ips));
+    }
+
+    if mtu {
+        for (target, _, ips) in &plan {
+            for ip in ips {
+                print_mtu(target, ip).await;
+            }
+        }
+        println!();
+        return Ok(());
+    }
+
+    // Every address (and the gateway) is pinged at once, so N hosts take as
+    // long as one. Results are printed afterwards in target order.
+    let spawn = |host: String, ip: String| {
+        tokio::spawn(async move { ping_ip(&host, &ip, count, tcp, source, timing).await })
+    };
+    let gateway_ip = if with_gateway {
+        crate::utils::get_default_route().await.gateway
+    } else {
+        None
+    };
+    let gateway_job = gateway_ip
+        .clone()
+        .map(|gw| spawn("gateway".to_string(), gw));
+    let jobs: Vec<Vec<_>> = plan
+        .iter()
+        .map(|(target, _, ips)| {
+            ips.iter()
+                .map(|ip| spawn(target.clone(), ip.clone()))
+                .collect()
+        })
+        .collect();
+
+    let mut noted = false;
+    let gateway = match (gateway_ip, gateway_job) {
+        (Some(gw), Some(job)) => {
+            println!();
+            println!("{} gateway {}...", "Pinging".dimmed(), gw.cyan());
+            let stats = job.await?;
+            note_fallback(&stats, &mut noted);
+            print_ping_stats(&stats, simple_jitter);
+            Some(stats)
+        }
+        _ if with_gateway => {
+            println!();
+            println!(
+                "{}",
+                "No default gateway found; skipping gateway ping.".yellow()
+            );
+            None
+        }
+        _ => None,
+    };
+
+    if mode.is_json() || csv {
+        let mut reports = Vec::new();
+        for job in jobs.into_iter().flatten() {
+            reports.push(job.await?.report(simple_jitter));
+        }
+        if mode.is_json() {
+            println!("{}", crate::output::to_json(&reports)?);
+        } else {
+            println!("{}", CSV_HEADER);
+            for report in &reports {
+                println!("{}", report.csv_row());
+            }
+        }
+        return Ok(());
+    }
+
+    let addresses: usize = jobs.iter().map(Vec::len).sum();
+    if addresses > 1 {
+        println!();
+        println!(
+            "{}",
+            format!(
+                "Pinging {} addresses concurrently ({} probes each)...",
+                addresses, count
+            )
+            .dimmed()
+        );
+    }
+
+    for ((target, resolved, ips), jobs) in plan.iter().zip(jobs) {
         println!();
         println!("{} {}...", "Pinging".dimmed(), target.cyan());
-        let stats = do_ping(target, count).await;
-        print_ping_stats(&stats);
+        if resolved.len() > 1 {
+            let list: Vec<String> = resolved
+                .iter()
+                .enumerate()
+                .map(|(i, ip)| format!("[{}] {}", i, ip))
+                .collect();
+            println!(
+                "{}",
+                format!("{} addresses: {}", resolved.len(), list.join(", ")).dimmed()
+            );
+            if ips.len() == 1 {
+                println!(
+                    "{}",
+                    format!(
+                        "Using {} (--index <n> to pick another, --all-ips for each)",
+                        ips[0]
+                    )
+                    .dimmed()
+                );
+            }
+        }
+
+        let mut summary = Vec::new();
+        for (ip, job) in ips.iter().zip(jobs) {
+            let stats = job.await?;
+            note_fallback(&stats, &mut noted);
+            print_ping_stats(&stats, simple_jitter);
+            if explain {
+                crate::explain::print(&crate::explain::connection(
+                    stats.avg(),
+                    Some(stats.jitter(simple_jitter)),
+                    Some(stats.loss_pct()),
+                    None,
+                ));
+            }
+            if let Some(ref gateway) = gateway {
+                print_gateway_comparison(gateway, &stats);
+            }
+            summary.push((ip.clone(), stats.avg(), stats.loss_pct()));
+        }
+
+        if summary.len() > 1 {
+            println!();
+            println!("{} {}", "Per-address summary:".bold(), target.cyan());
+            println!();
+            for (ip, avg, loss) in &summary {
+                println!("  {:<40} avg {:>5.0} ms   loss {:>3.0}%", ip, avg, loss);
+            }
+        }
     }
 
     println!();
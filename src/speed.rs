@@ -1,53 +1,166 @@
 use crate::display;
+use crate::output::{self, OutputMode};
+use crate::utils::{rfc3550_jitter, simple_jitter};
 use colored::Colorize;
 use serde::Serialize;
-use std::sync::LazyLock;
-use std::time::Instant;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 
 #[derive(Serialize)]
 struct SpeedResult {
     server: String,
-    download_mbps: f64,
-    upload_mbps: f64,
+    download_mbps: Option<f64>,
+    upload_mbps: Option<f64>,
     latency_ms: f64,
     jitter_ms: Option<f64>,
     packet_loss_pct: Option<f64>,
+    /// Host given with `--ping-host`; jitter and loss are measured against it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ping_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_latency_ms: Option<f64>,
+    /// Worst average latency while the link was saturated (`--bufferbloat`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_loaded_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bufferbloat_grade: Option<&'static str>,
+    /// Throughput of each transfer size, to show ramp-up and variance
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    download_samples: Vec<ThroughputSample>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    upload_samples: Vec<ThroughputSample>,
+    /// 0-100 score from `display::quality_score`
+    quality_score: u8,
     timestamp: String,
+    /// Individual results when several runs were summarized into medians.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    runs: Vec<RunMeasurement>,
 }
 
-struct ServerInfo {
-    name: &'static str,
-    location: &'static str,
-    download_url: &'static str,
-    upload_url: &'static str,
+/// Throughput measured for one transfer.
+#[derive(Clone, Copy, Serialize)]
+struct ThroughputSample {
+    bytes: u64,
+    mbps: f64,
+}
+
+/// The best rate among a set of samples.
+fn best_mbps(samples: &[ThroughputSample]) -> f64 {
+    samples.iter().map(|s| s.mbps).fold(0.0_f64, f64::max)
+}
+
+#[derive(Clone)]
+pub struct ServerInfo {
+    name: String,
+    location: String,
+    /// Endpoint probed with HEAD requests for latency
+    latency_url: String,
+    /// Bulk download endpoint with `{bytes}` standing for the payload size,
+    /// or `None` if the server cannot serve large payloads
+    download_url: Option<String>,
+    /// Endpoint that accepts large POST bodies, if the server has one
+    upload_url: Option<String>,
+}
+
+impl ServerInfo {
+    /// Whether at least one direction can be measured; a custom download
+    /// URL without an upload URL (or the reverse) still counts.
+    fn supports_throughput(&self) -> bool {
+        self.download_url.is_some() || self.upload_url.is_some()
+    }
 }
 
 static SERVERS: LazyLock<Vec<ServerInfo>> = LazyLock::new(|| {
     vec![
         ServerInfo {
-            name: "Cloudflare",
-            location: "Edge",
-            download_url: "https://speed.cloudflare.com/__down?bytes=25000000",
-            upload_url: "https://speed.cloudflare.com/__up",
+            name: "Cloudflare".to_string(),
+            location: "Edge".to_string(),
+            latency_url: "https://speed.cloudflare.com/__down?bytes=0".to_string(),
+            download_url: Some("https://speed.cloudflare.com/__down?bytes={bytes}".to_string()),
+            upload_url: Some("https://speed.cloudflare.com/__up".to_string()),
         },
+        // generate_204 returns an empty body, so it only measures latency
         ServerInfo {
-            name: "Google",
-            location: "CDN",
-            download_url: "https://www.google.com/generate_204",
-            upload_url: "https://www.google.com/generate_204",
+            name: "Google".to_string(),
+            location: "CDN".to_string(),
+            latency_url: "https://www.google.com/generate_204".to_string(),
+            download_url: None,
+            upload_url: None,
         },
     ]
 });
 
-fn select_server(name: Option<&str>) -> &'static ServerInfo {
-    if let Some(name) = name {
-        SERVERS
+/// Reject anything but an http(s) URL before the test starts.
+fn validate_url(flag: &str, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // "{bytes}" is only a placeholder, so check a concrete URL
+    let parsed = reqwest::Url::parse(&url.replace("{bytes}", "0"))
+        .map_err(|e| format!("Invalid {} '{}': {}", flag, url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(format!(
+            "Invalid {} '{}': must be an http:// or https:// URL",
+            flag, url
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Pick a built-in server by name, or build a custom one from explicit
+/// endpoints such as another machine running `netctl speed --serve`.
+/// A `server_url` base fills in that server's `/down` and `/up` paths.
+pub fn select_server(
+    name: Option<&str>,
+    server_url: Option<String>,
+    download_url: Option<String>,
+    upload_url: Option<String>,
+) -> Result<ServerInfo, Box<dyn std::error::Error>> {
+    let (download_url, upload_url) = match server_url {
+        Some(base) => {
+            validate_url("--server-url", &base)?;
+            let base = base.trim_end_matches('/');
+            (
+                Some(format!("{}/down?bytes={{bytes}}", base)),
+                Some(format!("{}/up", base)),
+            )
+        }
+        None => (download_url, upload_url),
+    };
+    if let Some(ref url) = download_url {
+        validate_url("--download-url", url)?;
+    }
+    if let Some(ref url) = upload_url {
+        validate_url("--upload-url", url)?;
+    }
+
+    if download_url.is_some() || upload_url.is_some() {
+        let latency_url = download_url
+            .as_deref()
+            .map(|url| url.replace("{bytes}", "0"))
+            .or_else(|| upload_url.clone())
+            .unwrap_or_default();
+        let location = reqwest::Url::parse(&latency_url)?
+            .host_str()
+            .unwrap_or("unknown")
+            .to_string();
+        return Ok(ServerInfo {
+            name: "Custom".to_string(),
+            location,
+            latency_url,
+            download_url,
+            upload_url,
+        });
+    }
+
+    let server = match name {
+        Some(name) => SERVERS
             .iter()
             .find(|s| s.name.to_lowercase() == name.to_lowercase())
-            .unwrap_or(&SERVERS[0])
-    } else {
-        &SERVERS[0]
-    }
+            .unwrap_or(&SERVERS[0]),
+        None => &SERVERS[0],
+    };
+    Ok(server.clone())
 }
 
 async fn measure_latency(client: &reqwest::Client, url: &str, samples: u32) -> Vec<f64> {
@@ -61,129 +174,1086 @@ async fn measure_latency(client: &reqwest::Client, url: &str, samples: u32) -> V
     latencies
 }
 
-async fn measure_download(client: &reqwest::Client, url: &str) -> Result<f64, Box<dyn std::error::Error>> {
+/// `preferred` followed by the other built-in servers that can measure
+/// throughput, in the order `run` falls back through them. Custom endpoints
+/// are never swapped for a public server.
+fn candidate_servers(preferred: ServerInfo) -> Vec<ServerInfo> {
+    let mut candidates = vec![preferred.clone()];
+    if preferred.name != "Custom" {
+        candidates.extend(
+            SERVERS
+                .iter()
+                .filter(|s| s.name != preferred.name && s.supports_throughput())
+                .cloned(),
+        );
+    }
+    candidates
+}
+
+/// How often the parallel download checks on its streams.
+const STREAM_POLL: Duration = Duration::from_millis(5);
+/// Bytes requested per GET in `--duration` mode; each stream re-requests
+/// until time is up. Small enough to finish within the client timeout on
+/// slow links.
+const TIMED_CHUNK_BYTES: u64 = 5_000_000;
+/// Bytes per POST in `--duration` mode.
+const TIMED_UPLOAD_BYTES: usize = 1_000_000;
+
+/// How much each throughput measurement transfers.
+#[derive(Clone, Copy)]
+pub enum Payload {
+    /// The built-in ladder of increasing sizes
+    Ladder,
+    /// One transfer of this many bytes
+    Fixed(u64),
+    /// Keep transferring until this much time has passed
+    Timed(Duration),
+}
+
+/// Build the payload plan from `--size` and `--duration`.
+pub fn payload(
+    size: Option<&str>,
+    duration: Option<u64>,
+) -> Result<Payload, Box<dyn std::error::Error>> {
+    match (size, duration) {
+        (_, Some(0)) => Err("--duration must be at least 1 second".into()),
+        (_, Some(secs)) => Ok(Payload::Timed(Duration::from_secs(secs))),
+        (Some(size), None) => match crate::utils::parse_bytes(size) {
+            Some(bytes) if bytes > 0 => Ok(Payload::Fixed(bytes)),
+            _ => Err(format!("Invalid --size '{}' (use e.g. 500KB, 50MB, or 1GB)", size).into()),
+        },
+        (None, None) => Ok(Payload::Ladder),
+    }
+}
+
+/// Progress shared between the streams of one parallel download.
+#[derive(Default)]
+struct StreamCounters {
+    bytes: AtomicU64,
+    /// Streams that have received their first byte
+    started: AtomicUsize,
+    /// Streams that have finished, successfully or not
+    done: AtomicUsize,
+}
+
+/// How often `--duration` transfers redraw their time-based progress bar.
+const PROGRESS_TICK: Duration = Duration::from_millis(100);
+/// Cells in the transfer progress bar.
+const PROGRESS_WIDTH: usize = 20;
+
+/// A live progress bar after a "Measuring ..." step, redrawn in place with
+/// `\r` as a transfer advances: by bytes against an expected total, or by
+/// time for `--duration` runs. Draws nothing when hidden.
+struct Progress {
+    label: String,
+    visible: bool,
+    goal: Option<ProgressGoal>,
+    /// The percentage on screen, to redraw only when it changes
+    drawn: Option<u32>,
+}
+
+enum ProgressGoal {
+    Bytes(u64),
+    Time(Instant, Duration),
+}
+
+impl Progress {
+    /// A bar that continues the line `label` started.
+    fn new(label: String, visible: bool) -> Self {
+        Progress {
+            label,
+            visible,
+            goal: None,
+            drawn: None,
+        }
+    }
+
+    fn hidden() -> Self {
+        Self::new(String::new(), false)
+    }
+
+    /// Measure progress against the bytes the payload plan will move.
+    fn expect_bytes(&mut self, total: u64) {
+        self.goal = Some(ProgressGoal::Bytes(total));
+    }
+
+    /// Measure progress by elapsed time, starting now.
+    fn expect_time(&mut self, duration: Duration) {
+        self.goal = Some(ProgressGoal::Time(Instant::now(), duration));
+    }
+
+    /// Redraw for `done` bytes moved so far (ignored for time goals).
+    fn update(&mut self, done: u64) {
+        let fraction = match self.goal {
+            _ if !self.visible => return,
+            Some(ProgressGoal::Bytes(total)) if total > 0 => done as f64 / total as f64,
+            Some(ProgressGoal::Time(start, duration)) => {
+                start.elapsed().as_secs_f64() / duration.as_secs_f64()
+            }
+            _ => return,
+        }
+        .min(1.0);
+        let percent = (fraction * 100.0) as u32;
+        if self.drawn != Some(percent) {
+            self.drawn = Some(percent);
+            let bar = display::print_progress_bar(fraction, PROGRESS_WIDTH);
+            print!("\r{}{}", self.label, bar);
+            let _ = std::io::stdout().flush();
+        }
+    }
+
+    /// Wipe the bar, leaving the cursor after the label for the step's result.
+    fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            // The bar plus " 100%"
+            let blank = " ".repeat(PROGRESS_WIDTH + 7);
+            print!("\r{}{}\r{}", self.label, blank, self.label);
+            let _ = std::io::stdout().flush();
+        }
+    }
+}
+
+/// Counts a stream as done however its task ends: finished, failed, or
+/// given up on after short bodies.
+struct DoneGuard(Arc<StreamCounters>);
+
+impl Drop for DoneGuard {
+    fn drop(&mut self) {
+        self.0.done.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A response this much smaller than requested (e.g. a 204) is not a
+/// throughput sample.
+fn is_short_body(received: u64, requested: u64) -> bool {
+    received < requested / 2
+}
+
+/// Fetch `url` to the end, adding every chunk to the shared counters and
+/// counting the stream as started at its first byte when `signal_start`
+/// is set. Returns the number of bytes received.
+async fn download_stream(
+    client: &reqwest::Client,
+    url: &str,
+    counters: &StreamCounters,
+    signal_start: bool,
+) -> Result<u64, reqwest::Error> {
+    let mut resp = client.get(url).send().await?.error_for_status()?;
+    let mut first = signal_start;
+    let mut received = 0;
+    while let Some(chunk) = resp.chunk().await? {
+        received += chunk.len() as u64;
+        counters
+            .bytes
+            .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        if first {
+            counters.started.fetch_add(1, Ordering::Relaxed);
+            first = false;
+        }
+    }
+    Ok(received)
+}
+
+/// Block until every stream has its first byte or one has already ended.
+async fn wait_for_streams(counters: &StreamCounters, connections: u32) {
+    while counters.started.load(Ordering::Relaxed) < connections as usize
+        && counters.done.load(Ordering::Relaxed) == 0
+    {
+        tokio::time::sleep(STREAM_POLL).await;
+    }
+}
+
+/// Download `url` on each of `connections` concurrent streams and measure
+/// their combined rate. The window opens once every stream has
+/// received its first byte and closes when the first stream finishes, so
+/// neither connection ramp-up nor a lone straggler skews the total.
+async fn parallel_download(
+    client: &reqwest::Client,
+    url: &str,
+    size: u64,
+    connections: u32,
+    progress: &mut Progress,
+    completed: u64,
+) -> Result<Option<ThroughputSample>, Box<dyn std::error::Error>> {
+    let counters = Arc::new(StreamCounters::default());
+    let handles: Vec<_> = (0..connections)
+        .map(|_| {
+            let (client, url, counters) = (client.clone(), url.to_string(), counters.clone());
+            tokio::spawn(async move {
+                let result = download_stream(&client, &url, &counters, true).await;
+                counters.done.fetch_add(1, Ordering::Relaxed);
+                result
+            })
+        })
+        .collect();
+
+    wait_for_streams(&counters, connections).await;
+    let (start, start_bytes) = (Instant::now(), counters.bytes.load(Ordering::Relaxed));
+    while counters.done.load(Ordering::Relaxed) == 0 {
+        tokio::time::sleep(STREAM_POLL).await;
+        progress.update(completed + counters.bytes.load(Ordering::Relaxed));
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let window_bytes = counters.bytes.load(Ordering::Relaxed) - start_bytes;
+
+    for handle in &handles {
+        handle.abort();
+    }
+    let mut short = false;
+    for result in futures_util::future::join_all(handles).await {
+        // Aborted streams were still running when the window closed
+        match result {
+            Ok(Ok(received)) => short |= is_short_body(received, size),
+            Ok(Err(e)) if window_bytes == 0 => return Err(e.into()),
+            Ok(Err(e)) => log::debug!("download stream failed: {}", e),
+            Err(_) => {}
+        }
+    }
+    if short {
+        log::debug!("{} returned less than the requested {} bytes", url, size);
+        return Ok(None);
+    }
+
+    if window_bytes == 0 || elapsed <= 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(ThroughputSample {
+        bytes: window_bytes,
+        mbps: (window_bytes as f64 * 8.0) / (elapsed * 1_000_000.0),
+    }))
+}
+
+/// Probe every built-in server and return the lowest-latency one that can
+/// measure throughput, announcing the choice. A latency-only server is
+/// chosen only when no full one answers.
+async fn fastest_server(
+    client: &reqwest::Client,
+    quiet: bool,
+) -> Result<ServerInfo, Box<dyn std::error::Error>> {
+    let mut probed = Vec::new();
+    for server in SERVERS.iter() {
+        let latencies = measure_latency(client, &server.latency_url, 3).await;
+        let avg =
+            (!latencies.is_empty()).then(|| latencies.iter().sum::<f64>() / latencies.len() as f64);
+        log::debug!("{} probe latency: {:?}", server.name, avg);
+        probed.push((server, avg));
+    }
+
+    let best = |full_only: bool| {
+        probed
+            .iter()
+            .filter(|(s, _)| !full_only || s.supports_throughput())
+            .filter_map(|(s, avg)| avg.map(|ms| (*s, ms)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    };
+    let (server, ms) = best(true)
+        .or_else(|| best(false))
+        .ok_or("could not reach any speed test server; check your internet connection")?;
+
+    let note = format!("Auto-selected {} ({:.0} ms probe latency)", server.name, ms);
+    if quiet {
+        eprintln!("{}", note.dimmed());
+    } else {
+        println!("  {}", note);
+        println!();
+    }
+    Ok(server.clone())
+}
+
+/// Keep `connections` streams downloading back to back for `duration`,
+/// timed from when every stream has its first byte.
+async fn timed_download(
+    client: &reqwest::Client,
+    url: &str,
+    connections: u32,
+    duration: Duration,
+    progress: &mut Progress,
+) -> Result<Option<ThroughputSample>, Box<dyn std::error::Error>> {
+    let url = url.replace("{bytes}", &TIMED_CHUNK_BYTES.to_string());
+    let counters = Arc::new(StreamCounters::default());
+    let handles: Vec<_> = (0..connections)
+        .map(|_| {
+            let (client, url, counters) = (client.clone(), url.clone(), counters.clone());
+            tokio::spawn(async move {
+                let _done = DoneGuard(counters.clone());
+                let mut first = true;
+                loop {
+                    let received = download_stream(&client, &url, &counters, first).await;
+                    first = false;
+                    match received {
+                        Ok(received) if !is_short_body(received, TIMED_CHUNK_BYTES) => {}
+                        Ok(_) => return Ok(()),
+                        Err(e) => return Err(e),
+                    }
+                }
+            })
+        })
+        .collect();
+
+    wait_for_streams(&counters, connections).await;
+    let (start, start_bytes) = (Instant::now(), counters.bytes.load(Ordering::Relaxed));
+    progress.expect_time(duration);
+    // Stop early once every stream has given up
+    while start.elapsed() < duration && counters.done.load(Ordering::Relaxed) < connections as usize
+    {
+        let left = duration.saturating_sub(start.elapsed());
+        tokio::time::sleep(left.min(PROGRESS_TICK)).await;
+        progress.update(0);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let window_bytes = counters.bytes.load(Ordering::Relaxed) - start_bytes;
+
+    for handle in &handles {
+        handle.abort();
+    }
+    for result in futures_util::future::join_all(handles).await {
+        match result {
+            // A stream only stops on its own when the server sends short bodies
+            Ok(Ok(())) => {
+                log::debug!("{} returned less than the requested bytes", url);
+                return Ok(None);
+            }
+            Ok(Err(e)) if window_bytes == 0 => return Err(e.into()),
+            Ok(Err(e)) => log::debug!("download stream failed: {}", e),
+            Err(_) => {}
+        }
+    }
+
+    Ok((window_bytes > 0).then(|| ThroughputSample {
+        bytes: window_bytes,
+        mbps: (window_bytes as f64 * 8.0) / (elapsed * 1_000_000.0),
+    }))
+}
+
+/// With more than one connection, each size is fetched on that many
+/// parallel streams; otherwise the transfers run one after another.
+async fn measure_download(
+    client: &reqwest::Client,
+    url: &str,
+    connections: u32,
+    payload: Payload,
+    progress: &mut Progress,
+) -> Result<Vec<ThroughputSample>, Box<dyn std::error::Error>> {
     // Perform multiple downloads to get a reliable measurement
-    let sizes: Vec<u64> = vec![1_000_000, 5_000_000, 10_000_000, 25_000_000];
-    let mut best_mbps = 0.0_f64;
+    let sizes: Vec<u64> = match payload {
+        Payload::Ladder => vec![1_000_000, 5_000_000, 10_000_000, 25_000_000],
+        Payload::Fixed(size) => vec![size],
+        Payload::Timed(duration) => {
+            return Ok(timed_download(client, url, connections, duration, progress)
+                .await?
+                .into_iter()
+                .collect())
+        }
+    };
+    progress.expect_bytes(sizes.iter().sum::<u64>() * connections as u64);
+    let mut samples = Vec::new();
+    let mut completed = 0;
 
     for size in sizes {
-        let download_url = if url.contains("cloudflare") {
-            format!("https://speed.cloudflare.com/__down?bytes={}", size)
-        } else {
-            url.to_string()
-        };
+        let download_url = url.replace("{bytes}", &size.to_string());
+        if connections > 1 {
+            let sample = parallel_download(
+                client,
+                &download_url,
+                size,
+                connections,
+                progress,
+                completed,
+            );
+            samples.extend(sample.await?);
+            completed += size * connections as u64;
+            progress.update(completed);
+            continue;
+        }
 
         let start = Instant::now();
-        let resp = client.get(&download_url).send().await?;
-        let bytes = resp.bytes().await?;
+        let mut resp = client.get(&download_url).send().await?.error_for_status()?;
+        let mut received = 0;
+        while let Some(chunk) = resp.chunk().await? {
+            received += chunk.len() as u64;
+            progress.update(completed + received);
+        }
         let elapsed = start.elapsed().as_secs_f64();
+        completed += size;
+        progress.update(completed);
 
-        if elapsed > 0.0 {
-            let mbps = (bytes.len() as f64 * 8.0) / (elapsed * 1_000_000.0);
-            best_mbps = best_mbps.max(mbps);
+        if is_short_body(received, size) {
+            log::debug!(
+                "{} returned {} of the requested {} bytes, skipping sample",
+                download_url,
+                received,
+                size
+            );
+        } else if elapsed > 0.0 {
+            samples.push(ThroughputSample {
+                bytes: received,
+                mbps: (received as f64 * 8.0) / (elapsed * 1_000_000.0),
+            });
         }
     }
 
-    Ok(best_mbps)
+    Ok(samples)
+}
+
+/// POST back to back for `duration`, as one combined sample.
+async fn timed_upload(
+    client: &reqwest::Client,
+    url: &str,
+    duration: Duration,
+    progress: &mut Progress,
+) -> Result<Vec<ThroughputSample>, Box<dyn std::error::Error>> {
+    let data = vec![0u8; TIMED_UPLOAD_BYTES];
+    let start = Instant::now();
+    progress.expect_time(duration);
+    let mut sent = 0u64;
+    while start.elapsed() < duration {
+        client
+            .post(url)
+            .body(data.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        sent += TIMED_UPLOAD_BYTES as u64;
+        progress.update(sent);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    Ok(vec![ThroughputSample {
+        bytes: sent,
+        mbps: (sent as f64 * 8.0) / (elapsed * 1_000_000.0),
+    }])
 }
 
-async fn measure_upload(client: &reqwest::Client, url: &str) -> Result<f64, Box<dyn std::error::Error>> {
-    let payload_sizes: Vec<usize> = vec![500_000, 1_000_000, 5_000_000];
-    let mut best_mbps = 0.0_f64;
+/// Each payload is POSTed whole, so the progress bar advances as each
+/// one is acknowledged.
+async fn measure_upload(
+    client: &reqwest::Client,
+    url: &str,
+    payload: Payload,
+    progress: &mut Progress,
+) -> Result<Vec<ThroughputSample>, Box<dyn std::error::Error>> {
+    let payload_sizes: Vec<usize> = match payload {
+        Payload::Ladder => vec![500_000, 1_000_000, 5_000_000],
+        Payload::Fixed(size) => vec![size as usize],
+        Payload::Timed(duration) => return timed_upload(client, url, duration, progress).await,
+    };
+    progress.expect_bytes(payload_sizes.iter().sum::<usize>() as u64);
+    let mut samples = Vec::new();
+    let mut completed = 0;
 
     for size in payload_sizes {
         let data = vec![0u8; size];
         let start = Instant::now();
-        let _ = client.post(url).body(data).send().await?;
+        client
+            .post(url)
+            .body(data)
+            .send()
+            .await?
+            .error_for_status()?;
         let elapsed = start.elapsed().as_secs_f64();
+        completed += size as u64;
+        progress.update(completed);
 
         if elapsed > 0.0 {
-            let mbps = (size as f64 * 8.0) / (elapsed * 1_000_000.0);
-            best_mbps = best_mbps.max(mbps);
+            samples.push(ThroughputSample {
+                bytes: size as u64,
+                mbps: (size as f64 * 8.0) / (elapsed * 1_000_000.0),
+            });
         }
     }
 
-    Ok(best_mbps)
+    Ok(samples)
 }
 
-pub async fn run(
-    server: Option<String>,
-    detailed: bool,
-    output: Option<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let server_info = select_server(server.as_deref());
+/// Latency probe interval while measuring under load.
+const LOADED_PROBE_INTERVAL: Duration = Duration::from_millis(100);
 
-    println!();
-    println!("{}", "Running network speed test...".dimmed());
-    println!();
+/// Average latency while the link is busy downloading and uploading.
+struct LoadedLatency {
+    download_ms: Option<f64>,
+    upload_ms: Option<f64>,
+}
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
+impl LoadedLatency {
+    fn worst(&self) -> Option<f64> {
+        match (self.download_ms, self.upload_ms) {
+            (Some(d), Some(u)) => Some(d.max(u)),
+            (d, u) => d.or(u),
+        }
+    }
+}
+
+/// Probe `url` repeatedly until `load` completes, returning the average.
+async fn latency_during<F: std::future::Future>(
+    client: &reqwest::Client,
+    url: &str,
+    load: F,
+) -> Option<f64> {
+    let mut latencies = Vec::new();
+    tokio::pin!(load);
+    loop {
+        let probe = async {
+            let start = Instant::now();
+            let ok = client.head(url).send().await.is_ok();
+            ok.then(|| start.elapsed().as_secs_f64() * 1000.0)
+        };
+        tokio::select! {
+            _ = &mut load => break,
+            sample = probe => latencies.extend(sample),
+        }
+        tokio::select! {
+            _ = &mut load => break,
+            _ = tokio::time::sleep(LOADED_PROBE_INTERVAL) => {}
+        }
+    }
+    (!latencies.is_empty()).then(|| latencies.iter().sum::<f64>() / latencies.len() as f64)
+}
+
+/// Measure latency while a download, then an upload, saturates the link.
+async fn measure_loaded_latency(
+    client: &reqwest::Client,
+    server_info: &ServerInfo,
+    connections: u32,
+    payload: Payload,
+) -> Result<LoadedLatency, Box<dyn std::error::Error>> {
+    // Probe on a separate connection pool so probes don't queue behind the
+    // transfer on a shared HTTP/2 connection
+    let probe_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
         .build()?;
+    let url = &server_info.latency_url;
+    let mut hidden = Progress::hidden();
+    let download_ms = match server_info.download_url.as_deref() {
+        Some(download) => {
+            latency_during(
+                &probe_client,
+                url,
+                measure_download(client, download, connections, payload, &mut hidden),
+            )
+            .await
+        }
+        None => None,
+    };
+    let upload_ms = match server_info.upload_url.as_deref() {
+        Some(upload) => {
+            let upload = measure_upload(client, upload, payload, &mut hidden);
+            latency_during(&probe_client, url, upload).await
+        }
+        None => None,
+    };
+    Ok(LoadedLatency {
+        download_ms,
+        upload_ms,
+    })
+}
+
+/// Grade how much latency grows under load, in the A-F style used by
+/// common bufferbloat tests.
+fn bufferbloat_grade(increase_ms: f64) -> &'static str {
+    match increase_ms {
+        d if d < 30.0 => "A",
+        d if d < 60.0 => "B",
+        d if d < 200.0 => "C",
+        d if d < 400.0 => "D",
+        _ => "F",
+    }
+}
+
+/// Numbers from one complete pass of the speed test.
+#[derive(Clone, Serialize)]
+struct RunMeasurement {
+    download_mbps: Option<f64>,
+    upload_mbps: Option<f64>,
+    latency_ms: f64,
+    jitter_ms: Option<f64>,
+    packet_loss_pct: Option<f64>,
+    /// Latency probes got through but the bulk download failed or stalled
+    #[serde(skip)]
+    download_stalled: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    download_samples: Vec<ThroughputSample>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    upload_samples: Vec<ThroughputSample>,
+}
+
+/// Whether a transfer gave up waiting rather than being refused.
+fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .is_some_and(reqwest::Error::is_timeout)
+}
+
+/// Run latency, download, and upload measurements once, printing progress.
+/// Jitter and packet loss are only sampled when `extended` is set. A failed
+/// transfer is an error rather than a zero rate; a download that stalls
+/// while latency probes get through is reported as stalled.
+async fn measure_once(
+    client: &reqwest::Client,
+    server_info: &ServerInfo,
+    extended: bool,
+    simple: bool,
+    connections: u32,
+    payload: Payload,
+    quiet: bool,
+) -> Result<RunMeasurement, Box<dyn std::error::Error>> {
+    let step = |label: &str| {
+        if !quiet {
+            print!("  Measuring {}... ", label);
+        }
+    };
+    // Transfer steps redraw a progress bar, which only makes sense on a terminal
+    let progress = |label: &str| {
+        let visible = !quiet && std::io::stdout().is_terminal();
+        Progress::new(format!("  Measuring {}... ", label), visible)
+    };
+    let finish = |status: colored::ColoredString| {
+        if !quiet {
+            println!("{}", status);
+        }
+    };
 
     // Measure latency
-    print!("  Measuring latency... ");
-    let latencies = measure_latency(&client, server_info.download_url, 5).await;
-    let avg_latency = if latencies.is_empty() {
+    step("latency");
+    let latencies = measure_latency(client, &server_info.latency_url, 5).await;
+    let latency_ms = if latencies.is_empty() {
         0.0
     } else {
         latencies.iter().sum::<f64>() / latencies.len() as f64
     };
-    println!("{}", "done".green());
+    finish("done".green());
 
     // Measure download
-    print!("  Measuring download speed... ");
-    let download_mbps = measure_download(&client, server_info.download_url).await.unwrap_or(0.0);
-    println!("{}", "done".green());
+    step("download speed");
+    let mut download_stalled = false;
+    let mut download_samples = Vec::new();
+    let mut bar = progress("download speed");
+    let download_mbps = match server_info.download_url.as_deref() {
+        Some(url) => {
+            let result = measure_download(client, url, connections, payload, &mut bar).await;
+            bar.clear();
+            match result {
+                // Every response was short: the endpoint doesn't serve payloads
+                Ok(samples) if samples.is_empty() => {
+                    finish("server returned no full-size payloads".yellow());
+                    None
+                }
+                Ok(samples) if best_mbps(&samples) >= 0.1 => {
+                    download_samples = samples;
+                    finish("done".green());
+                    Some(best_mbps(&download_samples))
+                }
+                // Small HEAD probes working while large transfers don't is the
+                // classic path-MTU blackhole signature
+                Ok(_) if !latencies.is_empty() => {
+                    download_stalled = true;
+                    finish("stalled".yellow());
+                    Some(0.0)
+                }
+                Err(e) if is_timeout(e.as_ref()) && !latencies.is_empty() => {
+                    log::debug!("download timed out: {}", e);
+                    download_stalled = true;
+                    finish("stalled".yellow());
+                    Some(0.0)
+                }
+                Ok(_) => {
+                    finish("failed".red());
+                    let name = &server_info.name;
+                    return Err(format!("download from {} made no progress", name).into());
+                }
+                Err(e) => {
+                    finish("failed".red());
+                    return Err(format!("download from {} failed: {}", server_info.name, e).into());
+                }
+            }
+        }
+        None => {
+            finish("unsupported by this server".yellow());
+            None
+        }
+    };
 
     // Measure upload
-    print!("  Measuring upload speed... ");
-    let upload_mbps = measure_upload(&client, server_info.upload_url).await.unwrap_or(0.0);
-    println!("{}", "done".green());
-
-    // Calculate jitter and packet loss if detailed
-    let (jitter, packet_loss) = if detailed {
-        let extra_latencies = measure_latency(&client, server_info.download_url, 20).await;
-        let jitter = if extra_latencies.len() > 1 {
-            let diffs: Vec<f64> = extra_latencies
-                .windows(2)
-                .map(|w| (w[1] - w[0]).abs())
-                .collect();
-            Some(diffs.iter().sum::<f64>() / diffs.len() as f64)
+    step("upload speed");
+    let mut bar = progress("upload speed");
+    let mut upload_samples = Vec::new();
+    let upload_mbps = match server_info.upload_url.as_deref() {
+        Some(url) => {
+            let result = measure_upload(client, url, payload, &mut bar).await;
+            bar.clear();
+            match result {
+                Ok(samples) if !samples.is_empty() => {
+                    upload_samples = samples;
+                    finish("done".green());
+                    Some(best_mbps(&upload_samples))
+                }
+                // Uploads too quick to time leave no samples, hence no rate
+                Ok(_) => {
+                    finish("no upload completed".yellow());
+                    None
+                }
+                Err(e) => {
+                    finish("failed".red());
+                    return Err(format!("upload to {} failed: {}", server_info.name, e).into());
+                }
+            }
+        }
+        None => {
+            finish("unsupported by this server".yellow());
+            None
+        }
+    };
+
+    // Calculate jitter and packet loss if detailed (explanations need them too)
+    let (jitter_ms, packet_loss_pct) = if extended {
+        let extra_latencies = measure_latency(client, &server_info.latency_url, 20).await;
+        let jitter = if simple {
+            simple_jitter(&extra_latencies)
         } else {
-            Some(0.0)
+            rfc3550_jitter(&extra_latencies)
         };
         let total_sent = 20;
         let total_received = extra_latencies.len();
         let loss = ((total_sent - total_received) as f64 / total_sent as f64) * 100.0;
-        (jitter, Some(loss))
+        (Some(jitter), Some(loss))
     } else {
         (None, None)
     };
 
+    Ok(RunMeasurement {
+        download_mbps,
+        upload_mbps,
+        latency_ms,
+        jitter_ms,
+        packet_loss_pct,
+        download_stalled,
+        download_samples,
+        upload_samples,
+    })
+}
+
+/// Median of a non-empty set of values; the mean of the middle two for even counts.
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Smallest and largest of a set of values, or `None` when there are none.
+fn range(values: impl Iterator<Item = f64>) -> Option<(f64, f64)> {
+    values.fold(None, |range, v| match range {
+        Some((lo, hi)) => Some((f64::min(lo, v), f64::max(hi, v))),
+        None => Some((v, v)),
+    })
+}
+
+/// Format a throughput figure, or note that the server couldn't measure it.
+fn format_throughput(mbps: Option<f64>) -> String {
+    match mbps {
+        Some(mbps) => display::format_mbps(mbps),
+        None => "unsupported by this server".to_string(),
+    }
+}
+
+/// Combine several runs into one measurement of per-metric medians.
+fn summarize(runs: &[RunMeasurement]) -> RunMeasurement {
+    let optional_median = |values: Vec<f64>| (!values.is_empty()).then(|| median(values));
+    RunMeasurement {
+        download_mbps: optional_median(runs.iter().filter_map(|r| r.download_mbps).collect()),
+        upload_mbps: optional_median(runs.iter().filter_map(|r| r.upload_mbps).collect()),
+        latency_ms: median(runs.iter().map(|r| r.latency_ms).collect()),
+        jitter_ms: optional_median(runs.iter().filter_map(|r| r.jitter_ms).collect()),
+        packet_loss_pct: optional_median(runs.iter().filter_map(|r| r.packet_loss_pct).collect()),
+        download_stalled: runs.iter().any(|r| r.download_stalled),
+        // Samples stay with their individual runs
+        download_samples: Vec::new(),
+        upload_samples: Vec::new(),
+    }
+}
+
+/// Settings for one `netctl speed` invocation against a chosen server.
+pub struct SpeedOptions {
+    pub detailed: bool,
+    pub output: Option<String>,
+    /// JSON prints only the result, like `--output -`
+    pub mode: OutputMode,
+    /// Add to the history in `output` instead of overwriting it
+    pub append: bool,
+    pub explain: bool,
+    pub simple_jitter: bool,
+    pub runs: u32,
+    pub ping_host: Option<String>,
+    /// Parallel download streams; 1 measures a single connection
+    pub connections: u32,
+    /// Also measure latency while the link is saturated
+    pub bufferbloat: bool,
+    /// Probe the built-in servers and test against the fastest
+    pub auto: bool,
+    pub payload: Payload,
+}
+
+pub async fn run(
+    server_info: ServerInfo,
+    opts: SpeedOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let SpeedOptions {
+        detailed,
+        output,
+        mode,
+        append,
+        explain,
+        simple_jitter: simple,
+        runs,
+        ping_host,
+        connections,
+        bufferbloat,
+        auto,
+        payload,
+    } = opts;
+    let runs = runs.max(1);
+    let connections = connections.max(1);
+    // With a ping host, jitter and loss come from it rather than the speed server
+    let sample_server_jitter = (detailed || explain) && ping_host.is_none();
+    // `--json` and `--output -` reserve stdout for the JSON result
+    let quiet = mode.is_json() || output::is_stdout(output.as_deref());
+
+    if !quiet {
+        println!();
+        println!("{}", "Running network speed test...".dimmed());
+        println!();
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let preferred = if auto {
+        fastest_server(&client, quiet).await?
+    } else {
+        server_info
+    };
+
+    // A server that doesn't answer, or whose transfer fails, hands over to
+    // the next candidate; only when all of them fail is the test an error
+    let mut failures: Vec<String> = Vec::new();
+    let mut chosen = None;
+    for server in candidate_servers(preferred) {
+        if let Some(last) = failures.last() {
+            let note = format!("{}, falling back to {}", last, server.name);
+            if quiet {
+                eprintln!("{}", note.yellow());
+            } else {
+                println!("  {}", note.yellow());
+            }
+        }
+        if measure_latency(&client, &server.latency_url, 2)
+            .await
+            .is_empty()
+        {
+            log::debug!("{} did not answer {}", server.name, server.latency_url);
+            failures.push(format!("{} unreachable", server.name));
+            continue;
+        }
+        let mut measurements = Vec::new();
+        for i in 0..runs {
+            if runs > 1 && !quiet {
+                println!("  {}", format!("Run {}/{}", i + 1, runs).bold());
+            }
+            let measurement = measure_once(
+                &client,
+                &server,
+                sample_server_jitter,
+                simple,
+                connections,
+                payload,
+                quiet,
+            )
+            .await;
+            match measurement {
+                Ok(measurement) => measurements.push(measurement),
+                Err(e) => {
+                    failures.push(e.to_string());
+                    break;
+                }
+            }
+        }
+        if measurements.len() == runs as usize {
+            chosen = Some((server, measurements));
+            break;
+        }
+    }
+    let Some((server_info, measurements)) = chosen else {
+        return Err(format!(
+            "no speed test server completed the test: {}",
+            failures.join("; ")
+        )
+        .into());
+    };
+
+    let loaded = if bufferbloat && server_info.supports_throughput() {
+        if !quiet {
+            print!("  Measuring latency under load... ");
+        }
+        let loaded = measure_loaded_latency(&client, &server_info, connections, payload).await?;
+        if !quiet {
+            println!("{}", "done".green());
+        }
+        Some(loaded)
+    } else {
+        None
+    };
+
+    let host_stats = match ping_host {
+        Some(ref host) => {
+            if !quiet {
+                print!("  Measuring latency to {}... ", host);
+            }
+            let stats = crate::ping::do_ping(host, 20, false, None).await;
+            if !quiet {
+                println!("{}", "done".green());
+            }
+            Some(stats)
+        }
+        None => None,
+    };
+
+    if runs > 1 && !quiet {
+        println!();
+        println!("{}", "Individual runs:".bold());
+        for (i, m) in measurements.iter().enumerate() {
+            println!(
+                "  Run {}:  {} {:>12}   {} {:>12}   {:.0} ms",
+                i + 1,
+                "↓".cyan(),
+                format_throughput(m.download_mbps),
+                "↑".green(),
+                format_throughput(m.upload_mbps),
+                m.latency_ms
+            );
+        }
+    }
+
+    let summary = summarize(&measurements);
+    let RunMeasurement {
+        download_mbps,
+        upload_mbps,
+        latency_ms: avg_latency,
+        jitter_ms: jitter,
+        packet_loss_pct: packet_loss,
+        download_stalled,
+        ..
+    } = summary;
+    let host_latency = host_stats.as_ref().map(|s| s.avg());
+    let (jitter, packet_loss) = match host_stats {
+        Some(ref stats) => (Some(stats.jitter(simple)), Some(stats.loss_pct())),
+        None => (jitter, packet_loss),
+    };
+    // Quality describes the host the user cares about when one was given
+    let latency = host_latency.unwrap_or(avg_latency);
+    let score = display::quality_score(latency, jitter, packet_loss);
+    let latency_loaded = loaded.as_ref().and_then(LoadedLatency::worst);
+    let grade = latency_loaded.map(|ms| bufferbloat_grade(ms - avg_latency));
+
+    let result = SpeedResult {
+        server: format!("{} ({})", server_info.name, server_info.location),
+        download_mbps,
+        upload_mbps,
+        latency_ms: avg_latency,
+        jitter_ms: jitter,
+        packet_loss_pct: packet_loss,
+        ping_host: ping_host.clone(),
+        host_latency_ms: host_latency,
+        latency_loaded_ms: latency_loaded,
+        bufferbloat_grade: grade,
+        // With several runs, each entry in `runs` carries its own samples
+        download_samples: if runs == 1 {
+            measurements[0].download_samples.clone()
+        } else {
+            Vec::new()
+        },
+        upload_samples: if runs == 1 {
+            measurements[0].upload_samples.clone()
+        } else {
+            Vec::new()
+        },
+        quality_score: score,
+        timestamp: chrono::Local::now().to_rfc3339(),
+        runs: if runs > 1 {
+            measurements.clone()
+        } else {
+            Vec::new()
+        },
+    };
+    if quiet {
+        println!("{}", output::to_json(&result)?);
+        return Ok(());
+    }
+
     // Display results
     println!();
     display::print_header("NETWORK SPEED TEST");
     display::print_row("Server:", &format!("{} ({})", server_info.name, server_info.location));
     display::print_row("Ping:", &format!("{:.0} ms", avg_latency));
+    if let (Some(host), Some(ms)) = (ping_host.as_deref(), host_latency) {
+        display::print_row("Host ping:", &format!("{:.0} ms ({})", ms, host));
+    }
     display::print_empty_row();
-    display::print_row("Download:", &format!("  {}", display::format_mbps(download_mbps)));
-    display::print_row("Upload:", &format!("  {}", display::format_mbps(upload_mbps)));
+    display::print_row("Download:", &format!("  {}", format_throughput(download_mbps)));
+    display::print_row("Upload:", &format!("  {}", format_throughput(upload_mbps)));
+
+    if runs > 1 {
+        // A direction no run measured shows "-", like other missing values
+        let show = |range: Option<(f64, f64)>, unit: &str, decimals: usize| {
+            range.map_or_else(
+                || "-".to_string(),
+                |(lo, hi)| format!("{:.*} - {:.*} {}", decimals, lo, decimals, hi, unit),
+            )
+        };
+        display::print_empty_row();
+        display::print_row("Runs:", &format!("{} (median shown)", runs));
+        if server_info.download_url.is_some() {
+            let down = range(measurements.iter().filter_map(|m| m.download_mbps));
+            display::print_row("Download range:", &show(down, "Mbps", 1));
+        }
+        if server_info.upload_url.is_some() {
+            let up = range(measurements.iter().filter_map(|m| m.upload_mbps));
+            display::print_row("Upload range:", &show(up, "Mbps", 1));
+        }
+        let ping = range(measurements.iter().map(|m| m.latency_ms));
+        display::print_row("Ping range:", &show(ping, "ms", 0));
+    }
 
     if detailed {
         display::print_empty_row();
-        let (_, quality_str) = display::quality_assessment(avg_latency);
-        display::print_row("Latency:", &format!("{:.0} ms ({})", avg_latency, quality_str));
+        let (_, quality_str) = display::quality_assessment(latency);
+        display::print_row("Latency:", &format!("{:.0} ms ({})", latency, quality_str));
         if let Some(j) = jitter {
             display::print_row("Jitter:", &format!("{:.0} ms", j));
         }
         if let Some(loss) = packet_loss {
             display::print_row("Packet Loss:", &format!("{:.1}%", loss));
         }
+        display::print_row("Score:", &format!("{}/100", score));
+    }
+
+    if let Some(ref loaded) = loaded {
+        display::print_empty_row();
+        let show = |ms: Option<f64>| {
+            ms.map_or_else(
+                || "-".to_string(),
+                |ms| format!("{:.0} ms (+{:.0})", ms, (ms - avg_latency).max(0.0)),
+            )
+        };
+        display::print_row("Idle latency:", &format!("{:.0} ms", avg_latency));
+        display::print_row("Loaded (down):", &show(loaded.download_ms));
+        display::print_row("Loaded (up):", &show(loaded.upload_ms));
+        if let Some(grade) = grade {
+            display::print_row("Bufferbloat:", &format!("Grade {}", grade));
+        }
     }
 
     display::print_empty_row();
-    let (label, _) = display::quality_assessment(avg_latency);
+    let (label, _) = display::quality_assessment(latency);
     let status_icon = if label == "Excellent" || label == "Good" {
         "OK".green().to_string()
     } else {
@@ -191,27 +1261,127 @@ pub async fn run(
     };
     display::print_row(
         "Connection:",
-        &format!("{} {}", status_icon, display::quality_assessment(avg_latency).1),
+        &format!("{} {}", status_icon, display::quality_assessment(latency).1),
     );
     display::print_footer();
 
+    let unmeasured = match (&server_info.download_url, &server_info.upload_url) {
+        (None, None) => Some(format!(
+            "{} only supports latency measurements; use --server cloudflare for throughput.",
+            server_info.name
+        )),
+        (Some(_), None) => {
+            Some("No upload URL; only download was measured (add --upload-url).".into())
+        }
+        (None, Some(_)) => {
+            Some("No download URL; only upload was measured (add --download-url).".into())
+        }
+        (Some(_), Some(_)) => None,
+    };
+    if let Some(note) = unmeasured {
+        println!();
+        println!("{}", note.dimmed());
+    }
+
+    if bufferbloat && !server_info.supports_throughput() {
+        println!();
+        println!(
+            "{}",
+            "Bufferbloat needs a server that supports throughput; skipped.".dimmed()
+        );
+    }
+
+    if download_stalled {
+        let host = reqwest::Url::parse(&server_info.latency_url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| server_info.name.to_lowercase());
+        println!();
+        println!(
+            "{}",
+            "Latency probes succeeded but the download stalled. This often means an MTU".yellow()
+        );
+        println!(
+            "{}",
+            "blackhole (common with PPPoE and VPNs): small packets pass, full-size ones vanish."
+                .yellow()
+        );
+        println!(
+            "  Check the path MTU with: {}",
+            format!("netctl ping {} --mtu", host).bold()
+        );
+    }
+
+    if explain {
+        crate::explain::print(&crate::explain::connection(
+            latency,
+            jitter,
+            packet_loss,
+            download_mbps.zip(upload_mbps),
+        ));
+    }
+
     // Export to JSON if requested
     if let Some(path) = output {
-        let result = SpeedResult {
-            server: format!("{} ({})", server_info.name, server_info.location),
-            download_mbps,
-            upload_mbps,
-            latency_ms: avg_latency,
-            jitter_ms: jitter,
-            packet_loss_pct: packet_loss,
-            timestamp: chrono::Local::now().to_rfc3339(),
-        };
-        let json = serde_json::to_string_pretty(&result)?;
-        std::fs::write(&path, &json)?;
-        println!();
-        println!("  Results saved to {}", path.green());
+        if append {
+            output::append_json(&path, &result)?;
+            println!();
+            println!("  Results appended to {}", path.green());
+        } else {
+            let json = output::to_json(&result)?;
+            std::fs::write(&path, &json)?;
+            println!();
+            println!("  Results saved to {}", path.green());
+        }
     }
 
     println!();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Answer every request on a local port with an empty 200, returning
+    /// the base URL.
+    async fn empty_body_server() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    while matches!(socket.read(&mut buf).await, Ok(n) if n > 0) {
+                        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+                        if socket.write_all(response).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn range_of_nothing_is_none() {
+        assert_eq!(range(std::iter::empty()), None);
+        assert_eq!(range([3.0].into_iter()), Some((3.0, 3.0)));
+        assert_eq!(range([5.0, 1.5, 9.0].into_iter()), Some((1.5, 9.0)));
+    }
+
+    #[tokio::test]
+    async fn timed_download_ends_on_empty_bodies() {
+        let url = format!("{}/down?bytes={{bytes}}", empty_body_server().await);
+        let client = reqwest::Client::new();
+        let mut progress = Progress::hidden();
+        let download = timed_download(&client, &url, 2, Duration::from_secs(30), &mut progress);
+        let sample = tokio::time::timeout(Duration::from_secs(5), download)
+            .await
+            .expect("timed download hung on empty bodies")
+            .unwrap();
+        assert!(sample.is_none());
+    }
+}
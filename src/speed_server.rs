@@ -0,0 +1,152 @@
+use colored::Colorize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest download a client may request, to keep a typo from streaming forever.
+const MAX_DOWNLOAD_BYTES: u64 = 1_000_000_000;
+/// Request heads larger than this are rejected.
+const MAX_HEAD_BYTES: usize = 8192;
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Turn ":8080" into "0.0.0.0:8080"; full addresses pass through.
+fn listen_addr(addr: &str) -> String {
+    if addr.starts_with(':') {
+        format!("0.0.0.0{}", addr)
+    } else {
+        addr.to_string()
+    }
+}
+
+/// Read the request line and headers, returning them along with any body
+/// bytes that arrived in the same reads.
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<(String, Vec<u8>)>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buf[..end]).into_owned();
+            return Ok(Some((head, buf[end + 4..].to_vec())));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Ok(None);
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, length: u64) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nContent-Type: application/octet-stream\r\nConnection: close\r\n\r\n",
+        status, length
+    );
+    stream.write_all(head.as_bytes()).await
+}
+
+/// Serve one request: zeros for `/down?bytes=N`, a discarding sink for `/up`.
+async fn handle(mut stream: TcpStream) -> std::io::Result<String> {
+    let Some((head, body_start)) = read_head(&mut stream).await? else {
+        return Ok("malformed request".to_string());
+    };
+    let mut lines = head.lines();
+    let mut request = lines.next().unwrap_or("").split_whitespace();
+    let method = request.next().unwrap_or("");
+    let target = request.next().unwrap_or("");
+    let content_length: u64 = lines
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.trim().parse().ok())
+        .unwrap_or(0);
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    match (method, path) {
+        ("GET" | "HEAD", "/down") => {
+            let bytes = query
+                .split('&')
+                .find_map(|kv| kv.strip_prefix("bytes="))
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0)
+                .min(MAX_DOWNLOAD_BYTES);
+            respond(&mut stream, "200 OK", bytes).await?;
+            if method == "GET" {
+                let zeros = vec![0u8; CHUNK_BYTES];
+                let mut remaining = bytes;
+                while remaining > 0 {
+                    let n = remaining.min(CHUNK_BYTES as u64) as usize;
+                    stream.write_all(&zeros[..n]).await?;
+                    remaining -= n as u64;
+                }
+            }
+            Ok(format!(
+                "{} /down {}",
+                method,
+                crate::display::format_bytes(bytes)
+            ))
+        }
+        ("POST", "/up") => {
+            let mut remaining = content_length.saturating_sub(body_start.len() as u64);
+            let mut buf = vec![0u8; CHUNK_BYTES];
+            while remaining > 0 {
+                let n = stream.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                remaining = remaining.saturating_sub(n as u64);
+            }
+            respond(&mut stream, "200 OK", 0).await?;
+            Ok(format!(
+                "POST /up {}",
+                crate::display::format_bytes(content_length)
+            ))
+        }
+        _ => {
+            respond(&mut stream, "404 Not Found", 0).await?;
+            Ok(format!("{} {} not found", method, path))
+        }
+    }
+}
+
+/// Run a minimal HTTP server that another netctl can use as a speed test peer.
+pub async fn serve(addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let addr = listen_addr(addr);
+    let listener = TcpListener::bind(&addr).await?;
+    let port = listener.local_addr()?.port();
+
+    println!();
+    println!(
+        "{} {}",
+        "Serving speed test endpoints on".bold(),
+        format!("http://{}", addr).cyan()
+    );
+    println!();
+    println!("  GET  /down?bytes=N   streams N zero bytes");
+    println!("  POST /up             discards the request body");
+    println!();
+    println!("From another machine, run:");
+    println!(
+        "  netctl speed --download-url 'http://<this-host>:{}/down?bytes={{bytes}}' --upload-url http://<this-host>:{}/up",
+        port, port
+    );
+    println!();
+    println!("{}", "Press Ctrl+C to stop.".dimmed());
+    println!();
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            let summary = match handle(stream).await {
+                Ok(summary) => summary,
+                Err(e) => format!("connection error: {}", e),
+            };
+            println!(
+                "  {} {} {}",
+                chrono::Local::now().format("%H:%M:%S").to_string().dimmed(),
+                peer.ip(),
+                summary
+            );
+        });
+    }
+}
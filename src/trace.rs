@@ -0,0 +1,198 @@
+use crate::error::NetctlError;
+use crate::icmp;
+use crate::utils::run_command;
+use colored::Colorize;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Probes sent with each TTL, like the system `traceroute`.
+const PROBES_PER_HOP: usize = 3;
+/// How long each probe waits for the router to answer.
+const HOP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Cap on each hop's reverse DNS lookup, so a silent resolver doesn't stall the trace.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// One TTL's worth of probes: who answered, and each probe's round-trip
+/// time (`None` if it was lost).
+struct Hop {
+    ttl: u8,
+    responder: Option<IpAddr>,
+    rtts: Vec<Option<f64>>,
+}
+
+/// The name `ip` reverse-resolves to, if any.
+async fn reverse_name(ip: IpAddr) -> Option<String> {
+    let lookup = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&ip));
+    match tokio::time::timeout(RESOLVE_TIMEOUT, lookup).await {
+        Ok(Ok(Ok(name))) if name != ip.to_string() => Some(name),
+        _ => None,
+    }
+}
+
+async fn print_hop(hop: &Hop) {
+    let mut fields = Vec::new();
+    if let Some(ip) = hop.responder {
+        fields.push(match reverse_name(ip).await {
+            Some(name) => format!("{} ({})", name.cyan(), ip),
+            None => ip.to_string().cyan().to_string(),
+        });
+    }
+    fields.extend(hop.rtts.iter().map(|rtt| match rtt {
+        Some(ms) => format!("{:.1} ms", ms),
+        None => "*".dimmed().to_string(),
+    }));
+    println!("{:>3}  {}", hop.ttl, fields.join("  "));
+}
+
+/// Send every probe for `ttl` at once, returning the hop and whether the
+/// trace ends there. Errors when no raw ICMP socket can be opened.
+async fn probe(ip: IpAddr, ttl: u8) -> std::io::Result<(Hop, bool)> {
+    let probes = (0..PROBES_PER_HOP).map(|i| {
+        let seq = ttl as u16 * PROBES_PER_HOP as u16 + i as u16;
+        icmp::probe_hop(ip, ttl as u32, seq, HOP_TIMEOUT)
+    });
+    let mut hop = Hop {
+        ttl,
+        responder: None,
+        rtts: Vec::new(),
+    };
+    let mut last = false;
+    for reply in futures_util::future::join_all(probes).await {
+        let reply = reply?;
+        if let Some(reply) = &reply {
+            hop.responder.get_or_insert(reply.from);
+            last |= reply.last;
+        }
+        hop.rtts.push(reply.map(|r| r.rtt_ms));
+    }
+    Ok((hop, last))
+}
+
+/// Trace with the system `traceroute`, for when raw sockets need root.
+/// Returns the hop count if `ip` was reached.
+async fn system_trace(ip: IpAddr, max_hops: u8) -> Result<Option<u8>, Box<dyn std::error::Error>> {
+    let program = if ip.is_ipv6() && cfg!(target_os = "macos") {
+        "traceroute6"
+    } else {
+        "traceroute"
+    };
+    let max = max_hops.to_string();
+    let probes = PROBES_PER_HOP.to_string();
+    let wait = HOP_TIMEOUT.as_secs().to_string();
+    let target = ip.to_string();
+    let args = ["-n", "-q", &probes, "-w", &wait, "-m", &max, &target];
+    let deadline = HOP_TIMEOUT * (PROBES_PER_HOP as u32 * max_hops as u32) + Duration::from_secs(5);
+    let output = match run_command(program, &args, deadline).await {
+        Ok(out) => out,
+        Err(NetctlError::NotFound { .. }) => {
+            return Err(format!(
+                "tracing needs root for raw ICMP sockets, or `{}` installed",
+                program
+            )
+            .into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Lines look like " 3  10.0.0.1  1.234 ms  *  10.0.0.2  1.502 ms";
+    // the header and anything else not starting with a hop number is skipped
+    let mut reached = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(Ok(ttl)) = tokens.next().map(str::parse::<u8>) else {
+            continue;
+        };
+        let mut hop = Hop {
+            ttl,
+            responder: None,
+            rtts: Vec::new(),
+        };
+        while let Some(token) = tokens.next() {
+            if token == "*" {
+                hop.rtts.push(None);
+            } else if let Ok(from) = token.parse::<IpAddr>() {
+                hop.responder.get_or_insert(from);
+            } else if let Ok(ms) = token.parse::<f64>() {
+                if tokens.peek() == Some(&"ms") {
+                    hop.rtts.push(Some(ms));
+                }
+            }
+        }
+        if hop.responder == Some(ip) {
+            reached.get_or_insert(ttl);
+        }
+        print_hop(&hop).await;
+    }
+    Ok(reached)
+}
+
+/// Show the route to `host`, one line per hop, by sending ICMP echo requests
+/// with increasing TTLs (or running the system `traceroute` without root).
+pub async fn run(host: &str, max_hops: u8) -> Result<(), Box<dyn std::error::Error>> {
+    if max_hops == 0 {
+        return Err("--max-hops must be at least 1".into());
+    }
+    let ip: IpAddr = crate::ping::resolve_host(host)
+        .ok_or_else(|| format!("could not resolve {}", host))?
+        .parse()?;
+
+    println!(
+        "{} {} ({}), {} hops max",
+        "Tracing route to".bold(),
+        host.cyan(),
+        ip,
+        max_hops
+    );
+
+    let mut reached = None;
+    let mut unreachable_at = None;
+    for ttl in 1..=max_hops {
+        let (hop, last) = match probe(ip, ttl).await {
+            Ok(result) => result,
+            Err(e) => {
+                log::debug!("raw ICMP socket unavailable ({}), using traceroute", e);
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    crate::display::print_unprivileged_note(
+                        "raw ICMP sockets need root, tracing with the system traceroute",
+                    );
+                }
+                reached = system_trace(ip, max_hops).await?;
+                break;
+            }
+        };
+        print_hop(&hop).await;
+        if last {
+            match hop.responder {
+                Some(from) if from != ip => unreachable_at = Some((ttl, from)),
+                _ => reached = Some(ttl),
+            }
+            break;
+        }
+    }
+
+    println!();
+    let plural = |n: u8| if n == 1 { "" } else { "s" };
+    match (reached, unreachable_at) {
+        (Some(hops), _) => println!(
+            "{} {} in {} hop{}",
+            "Reached".green(),
+            ip,
+            hops,
+            plural(hops)
+        ),
+        (None, Some((hops, from))) => println!(
+            "{} {} reported {} unreachable at hop {}",
+            "Stopped:".yellow(),
+            from,
+            ip,
+            hops
+        ),
+        (None, None) => println!(
+            "{} {} within {} hops",
+            "Did not reach".yellow(),
+            ip,
+            max_hops
+        ),
+    }
+    Ok(())
+}
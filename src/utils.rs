@@ -1,36 +1,616 @@
-/// Look up a process name by PID using the system `ps` command.
+use crate::error::NetctlError;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The program and its arguments as one loggable line.
+fn command_line(cmd: &Command) -> String {
+    let mut line = cmd.get_program().to_string_lossy().into_owned();
+    for arg in cmd.get_args() {
+        line.push(' ');
+        line.push_str(&arg.to_string_lossy());
+    }
+    line
+}
+
+fn log_output(line: &str, output: &Output) {
+    log::debug!(
+        "{} finished with {} ({} bytes stdout)",
+        line,
+        output.status,
+        output.stdout.len()
+    );
+    log::trace!(
+        "stdout:\n{}",
+        String::from_utf8_lossy(&output.stdout).trim_end()
+    );
+    if !output.stderr.is_empty() {
+        log::trace!(
+            "stderr:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+}
+
+/// Return the netctl data directory (~/.netctl), creating it if needed.
+pub fn netctl_dir() -> PathBuf {
+    let base = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    let dir = base.join(".netctl");
+    if !dir.exists() {
+        let _ = std::fs::create_dir_all(&dir);
+    }
+    dir
+}
+
+/// Parse a size like "500KB", "10MB", or "1.5GB" (decimal units) into
+/// bytes. A bare number is bytes.
+pub fn parse_bytes(size: &str) -> Option<u64> {
+    let size = size.trim().to_uppercase();
+    if let Some(num) = size.strip_suffix("GB") {
+        num.trim()
+            .parse::<f64>()
+            .ok()
+            .map(|n| (n * 1_000_000_000.0) as u64)
+    } else if let Some(num) = size.strip_suffix("MB") {
+        num.trim()
+            .parse::<f64>()
+            .ok()
+            .map(|n| (n * 1_000_000.0) as u64)
+    } else if let Some(num) = size.strip_suffix("KB") {
+        num.trim().parse::<f64>().ok().map(|n| (n * 1_000.0) as u64)
+    } else {
+        size.parse::<u64>().ok()
+    }
+}
+
+/// Parse a duration like "2h", "30m", "45s", "7d", or "2w".
+/// A bare number is interpreted as hours.
+pub fn parse_duration(dur: &str) -> Option<chrono::Duration> {
+    let dur = dur.trim().to_lowercase();
+    if let Some(weeks) = dur.strip_suffix('w') {
+        weeks.parse::<i64>().ok().map(chrono::Duration::weeks)
+    } else if let Some(days) = dur.strip_suffix('d') {
+        days.parse::<i64>().ok().map(chrono::Duration::days)
+    } else if let Some(hours) = dur.strip_suffix('h') {
+        hours.parse::<i64>().ok().map(chrono::Duration::hours)
+    } else if let Some(mins) = dur.strip_suffix('m') {
+        mins.parse::<i64>().ok().map(chrono::Duration::minutes)
+    } else if let Some(secs) = dur.strip_suffix('s') {
+        secs.parse::<i64>().ok().map(chrono::Duration::seconds)
+    } else {
+        // Default to hours
+        dur.parse::<i64>().ok().map(chrono::Duration::hours)
+    }
+}
+
+/// Parse a local time like "14:00", "03:30:15", or "2026-10-15 09:00" into
+/// the next moment it occurs. A time of day that has already passed today
+/// refers to tomorrow.
+pub fn parse_at(at: &str) -> Option<chrono::DateTime<chrono::Local>> {
+    use chrono::{Local, NaiveDateTime, NaiveTime, TimeZone};
+
+    let at = at.trim();
+    let now = Local::now();
+    let naive = if let Some(dt) = ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(at, fmt).ok())
+    {
+        dt
+    } else {
+        let time = ["%H:%M", "%H:%M:%S"]
+            .iter()
+            .find_map(|fmt| NaiveTime::parse_from_str(at, fmt).ok())?;
+        let today = now.date_naive().and_time(time);
+        if today > now.naive_local() {
+            today
+        } else {
+            today + chrono::Duration::days(1)
+        }
+    };
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// Interarrival jitter as defined in RFC 3550 section 6.4.1: a running
+/// estimate that moves 1/16 of the way toward each new consecutive difference.
+/// This matches the jitter reported by VoIP and RTP tools.
+pub fn rfc3550_jitter(latencies: &[f64]) -> f64 {
+    latencies.windows(2).fold(0.0, |jitter, w| {
+        jitter + ((w[1] - w[0]).abs() - jitter) / 16.0
+    })
+}
+
+/// Mean absolute difference between consecutive latencies.
+pub fn simple_jitter(latencies: &[f64]) -> f64 {
+    if latencies.len() < 2 {
+        return 0.0;
+    }
+    let diffs: Vec<f64> = latencies.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    diffs.iter().sum::<f64>() / diffs.len() as f64
+}
+
+/// Look up process names and owners by PID with a single `ps` call, so a
+/// refresh costs one fork instead of one per socket. Returns the names and
+/// the users, each keyed by PID.
 ///
-/// Returns "Unknown" if the PID is empty, "-", or cannot be resolved.
-pub fn get_process_name(pid: &str) -> String {
-    if pid.is_empty() || pid == "-" {
-        return "Unknown".to_string();
-    }
-    let output = std::process::Command::new("ps")
-        .args(["-p", pid, "-o", "comm="])
-        .output();
-    match output {
-        Ok(out) => {
-            let name = String::from_utf8_lossy(&out.stdout).trim().to_string();
-            if name.is_empty() {
-                "Unknown".to_string()
-            } else {
-                // Extract just the binary name from the path
-                name.rsplit('/').next().unwrap_or(&name).to_string()
-            }
+/// PIDs that are empty, "-", or cannot be resolved map to "Unknown" and "-".
+pub async fn get_process_info(pids: &[&str]) -> (HashMap<String, String>, HashMap<String, String>) {
+    let mut names: HashMap<String, String> = pids
+        .iter()
+        .map(|pid| (pid.to_string(), "Unknown".to_string()))
+        .collect();
+    let mut users: HashMap<String, String> = pids
+        .iter()
+        .map(|pid| (pid.to_string(), "-".to_string()))
+        .collect();
+    let mut valid: Vec<&str> = pids
+        .iter()
+        .copied()
+        .filter(|pid| !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit()))
+        .collect();
+    valid.sort_unstable();
+    valid.dedup();
+    if valid.is_empty() {
+        return (names, users);
+    }
+    let output = run_command(
+        "ps",
+        &["-p", &valid.join(","), "-o", "pid=,user=,comm="],
+        COMMAND_TIMEOUT,
+    )
+    .await;
+    let Ok(out) = output else {
+        return (names, users);
+    };
+    // Lines are "  123 alice    /usr/bin/name"; the name may contain spaces
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        let Some((pid, rest)) = line.trim_start().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some((user, name)) = rest.trim_start().split_once(char::is_whitespace) else {
+            continue;
+        };
+        users.insert(pid.to_string(), user.to_string());
+        let name = name.trim();
+        if !name.is_empty() {
+            // Extract just the binary name from the path
+            let name = name.rsplit('/').next().unwrap_or(name).to_string();
+            names.insert(pid.to_string(), name);
         }
-        Err(_) => "Unknown".to_string(),
     }
+    (names, users)
+}
+
+/// Process names by PID, as from `get_process_info`.
+pub async fn get_process_names(pids: &[&str]) -> HashMap<String, String> {
+    get_process_info(pids).await.0
 }
 
-/// Format bytes per second into a human-readable rate string.
+/// Format bytes per second into a human-readable rate string, in decimal
+/// or (with `--binary`) binary units.
 pub fn format_rate(bytes_per_sec: f64) -> String {
-    if bytes_per_sec >= 1_000_000_000.0 {
-        format!("{:.1} GB/s", bytes_per_sec / 1_000_000_000.0)
-    } else if bytes_per_sec >= 1_000_000.0 {
-        format!("{:.1} MB/s", bytes_per_sec / 1_000_000.0)
-    } else if bytes_per_sec >= 1_000.0 {
-        format!("{:.0} KB/s", bytes_per_sec / 1_000.0)
+    crate::display::format_scaled(bytes_per_sec, "/s")
+}
+
+/// Return true when the process is running with root privileges (e.g. under sudo).
+pub fn is_elevated() -> bool {
+    // geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// How long helpers that shell out to quick system tools (`ip`, `ps`, `ss`)
+/// wait before giving up on them.
+pub const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Run a command from async code without blocking the executor. The wait
+/// happens on the blocking pool; past `timeout` the process is killed.
+/// A missing binary, a nonzero exit, and a timeout are distinct errors.
+pub async fn run_command(
+    program: &str,
+    args: &[&str],
+    timeout: Duration,
+) -> Result<Output, NetctlError> {
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let line = command_line(&cmd);
+    log::debug!("exec: {}", line);
+
+    let child = cmd.spawn().map_err(|e| {
+        log::debug!("{} failed to start: {}", line, e);
+        if e.kind() == std::io::ErrorKind::NotFound {
+            NetctlError::NotFound {
+                command: program.to_string(),
+            }
+        } else {
+            NetctlError::Io {
+                command: line.clone(),
+                source: e,
+            }
+        }
+    })?;
+    let pid = child.id();
+
+    let waited = tokio::time::timeout(
+        timeout,
+        tokio::task::spawn_blocking(move || child.wait_with_output()),
+    )
+    .await;
+    let output = match waited {
+        Ok(Ok(Ok(output))) => output,
+        Ok(Ok(Err(e))) => {
+            return Err(NetctlError::Io {
+                command: line,
+                source: e,
+            })
+        }
+        Ok(Err(e)) => {
+            return Err(NetctlError::Io {
+                command: line,
+                source: std::io::Error::other(e),
+            })
+        }
+        Err(_) => {
+            // The blocking wait returns once the process is gone
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGKILL);
+            }
+            log::debug!("{} timed out after {:?}", line, timeout);
+            return Err(NetctlError::TimedOut {
+                command: line,
+                secs: timeout.as_secs(),
+            });
+        }
+    };
+    log_output(&line, &output);
+
+    if output.status.success() {
+        Ok(output)
     } else {
-        format!("{:.0} B/s", bytes_per_sec)
+        Err(NetctlError::Failed {
+            command: line,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Catch Ctrl+C so long-running loops can clean up instead of dying mid-way.
+/// Poll `interrupted()` after installing.
+pub fn catch_interrupt() {
+    // Only an atomic store happens in the handler, which is async-signal-safe
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as *const () as libc::sighandler_t);
+    }
+}
+
+/// True once Ctrl+C has been pressed after `catch_interrupt()`.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Sleep for `duration`, waking early on Ctrl+C. Returns false if interrupted.
+pub async fn sleep_unless_interrupted(duration: std::time::Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + duration;
+    while !interrupted() {
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        tokio::time::sleep((deadline - now).min(std::time::Duration::from_millis(250))).await;
+    }
+    false
+}
+
+/// Extract the port from an address such as "1.2.3.4:443", "[::1]:22", or "*:53".
+pub fn port_of(addr: &str) -> Option<u16> {
+    addr.rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+}
+
+/// Extract the host from an address, dropping the port and IPv6 brackets:
+/// "1.2.3.4:443" -> "1.2.3.4", "[2001:db8::1]:443" -> "2001:db8::1".
+pub fn host_of(addr: &str) -> &str {
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port == "*" || port.parse::<u16>().is_ok() => host,
+        _ => addr,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// Return the well-known service name for a port, if any.
+pub fn port_service(port: u16) -> Option<&'static str> {
+    let service = match port {
+        20 | 21 => "FTP",
+        22 => "SSH",
+        23 => "Telnet",
+        25 | 465 | 587 => "SMTP",
+        53 => "DNS",
+        67 | 68 => "DHCP",
+        80 | 8080 => "HTTP",
+        110 | 995 => "POP3",
+        123 => "NTP",
+        143 | 993 => "IMAP",
+        389 | 636 => "LDAP",
+        443 | 8443 => "HTTPS",
+        445 => "SMB",
+        853 => "DoT",
+        1194 => "OpenVPN",
+        1433 => "MSSQL",
+        3306 => "MySQL",
+        3389 => "RDP",
+        5353 => "mDNS",
+        5432 => "PostgreSQL",
+        5900 => "VNC",
+        6379 => "Redis",
+        9200 => "Elasticsearch",
+        11211 => "Memcached",
+        27017 => "MongoDB",
+        51820 => "WireGuard",
+        _ => return None,
+    };
+    Some(service)
+}
+
+/// Application-name filter for `--app` and `--exclude`. Both are
+/// case-insensitive substring matches; a name must match `--app` (when
+/// given) and must not match any `--exclude`, so exclusion wins.
+pub struct AppFilter {
+    include: Option<String>,
+    exclude: Vec<String>,
+}
+
+impl AppFilter {
+    pub fn new(include: Option<String>, exclude: Vec<String>) -> Self {
+        AppFilter {
+            include: include.map(|i| i.to_lowercase()),
+            exclude: exclude
+                .into_iter()
+                .map(|e| e.trim().to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn allows(&self, app: &str) -> bool {
+        let app = app.to_lowercase();
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|i| app.contains(i.as_str()));
+        included && !self.exclude.iter().any(|e| app.contains(e.as_str()))
+    }
+}
+
+/// Interfaces hidden by default: loopback, container veths, and bridges.
+pub const VIRTUAL_INTERFACES: &[&str] = &["lo", "lo0", "veth*", "docker*", "br-*"];
+
+/// Match `name` against a glob where `*` is any run of characters and `?`
+/// is any single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(p: &[char], n: &[char]) -> bool {
+        match (p.first(), n.first()) {
+            (None, None) => true,
+            (Some('*'), _) => matches(&p[1..], n) || (!n.is_empty() && matches(p, &n[1..])),
+            (Some('?'), Some(_)) => matches(&p[1..], &n[1..]),
+            (Some(a), Some(b)) if a == b => matches(&p[1..], &n[1..]),
+            _ => false,
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    matches(&p, &n)
+}
+
+/// Which network interfaces interface-level views should show.
+pub struct IfaceFilter {
+    exclude: Vec<String>,
+}
+
+impl IfaceFilter {
+    /// Exclude the virtual defaults (unless `include_virtual`) plus any
+    /// comma-separated glob patterns in `extra`.
+    pub fn new(include_virtual: bool, extra: Option<&str>) -> Self {
+        let mut exclude: Vec<String> = if include_virtual {
+            Vec::new()
+        } else {
+            VIRTUAL_INTERFACES.iter().map(|p| p.to_string()).collect()
+        };
+        exclude.extend(
+            extra
+                .iter()
+                .flat_map(|e| e.split(','))
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty()),
+        );
+        IfaceFilter { exclude }
+    }
+
+    pub fn allows(&self, iface: &str) -> bool {
+        !self.exclude.iter().any(|p| glob_match(p, iface))
+    }
+}
+
+/// The interface and next-hop gateway of the default route.
+pub struct DefaultRoute {
+    pub interface: String,
+    pub gateway: Option<String>,
+}
+
+/// Parse the default route from `route -n get default` (macOS) or
+/// `ip route show default` (Linux).
+pub async fn get_default_route() -> DefaultRoute {
+    // macOS
+    if let Ok(output) = run_command("route", &["-n", "get", "default"], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let field = |key: &str| {
+            stdout
+                .lines()
+                .find_map(|l| l.trim().strip_prefix(key))
+                .map(|v| v.trim().to_string())
+        };
+        if let Some(interface) = field("interface:") {
+            return DefaultRoute {
+                interface,
+                gateway: field("gateway:"),
+            };
+        }
+    }
+    // Linux
+    if let Ok(output) = run_command("ip", &["route", "show", "default"], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Some(line) = stdout.lines().next() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let field = |key: &str| {
+                parts
+                    .iter()
+                    .position(|&p| p == key)
+                    .and_then(|i| parts.get(i + 1))
+                    .map(|v| v.to_string())
+            };
+            if let Some(interface) = field("dev") {
+                return DefaultRoute {
+                    interface,
+                    gateway: field("via"),
+                };
+            }
+        }
+    }
+    DefaultRoute {
+        interface: "unknown".to_string(),
+        gateway: None,
+    }
+}
+
+/// Name of the interface carrying the default route, or "unknown".
+pub async fn get_default_interface() -> String {
+    get_default_route().await.interface
+}
+
+/// Interface the kernel would send traffic for `dest` out of, from
+/// `route -n get` (macOS) or `ip route get` (Linux).
+pub async fn route_interface(dest: &str) -> Option<String> {
+    // macOS
+    if let Ok(output) = run_command("route", &["-n", "get", dest], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Some(iface) = stdout
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("interface:"))
+        {
+            return Some(iface.trim().to_string());
+        }
+    }
+    // Linux, e.g. "1.1.1.1 via 192.168.1.1 dev eth0 src 192.168.1.20 uid 1000"
+    let output = run_command("ip", &["route", "get", dest], COMMAND_TIMEOUT)
+        .await
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let parts: Vec<&str> = stdout.split_whitespace().collect();
+    parts
+        .iter()
+        .position(|&p| p == "dev")
+        .and_then(|i| parts.get(i + 1))
+        .map(|iface| iface.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 16 RTTs alternating 10 and 20 ms: 15 differences of 10 ms each.
+    fn alternating() -> Vec<f64> {
+        (0..16)
+            .map(|i| if i % 2 == 0 { 10.0 } else { 20.0 })
+            .collect()
+    }
+
+    #[test]
+    fn jitter_of_empty_input_is_zero() {
+        assert_eq!(rfc3550_jitter(&[]), 0.0);
+        assert_eq!(simple_jitter(&[]), 0.0);
+    }
+
+    #[test]
+    fn jitter_of_one_sample_is_zero() {
+        assert_eq!(rfc3550_jitter(&[12.5]), 0.0);
+        assert_eq!(simple_jitter(&[12.5]), 0.0);
+    }
+
+    #[test]
+    fn jitter_of_constant_rtts_is_zero() {
+        let rtts = [23.0; 16];
+        assert_eq!(rfc3550_jitter(&rtts), 0.0);
+        assert_eq!(simple_jitter(&rtts), 0.0);
+    }
+
+    #[test]
+    fn rfc3550_jitter_converges_by_sixteenths() {
+        // J moves 1/16 of the way to 10 on each of the 15 steps, so
+        // J = 10 * (1 - (15/16)^15)
+        let expected = 10.0 * (1.0 - (15.0f64 / 16.0).powi(15));
+        assert!((rfc3550_jitter(&alternating()) - expected).abs() < 1e-9);
+        assert!((expected - 6.2019).abs() < 1e-4);
+    }
+
+    #[tokio::test]
+    async fn run_command_returns_output_on_success() {
+        let output = run_command("sh", &["-c", "echo hi"], COMMAND_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hi\n");
+    }
+
+    #[tokio::test]
+    async fn run_command_reports_missing_binary() {
+        let result = run_command("netctl-no-such-binary", &[], COMMAND_TIMEOUT).await;
+        assert!(matches!(
+            result,
+            Err(NetctlError::NotFound { ref command }) if command == "netctl-no-such-binary"
+        ));
+    }
+
+    #[tokio::test]
+    async fn run_command_reports_nonzero_exit() {
+        let result = run_command("sh", &["-c", "echo oops >&2; exit 3"], COMMAND_TIMEOUT).await;
+        match result {
+            Err(NetctlError::Failed { status, stderr, .. }) => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr, "oops");
+            }
+            other => panic!("expected Failed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn run_command_kills_on_timeout() {
+        let start = std::time::Instant::now();
+        let result = run_command("sleep", &["10"], Duration::from_millis(200)).await;
+        assert!(matches!(result, Err(NetctlError::TimedOut { .. })));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn process_info_names_and_owns_each_pid() {
+        let me = std::process::id().to_string();
+        let (names, users) = get_process_info(&[me.as_str(), "-", ""]).await;
+        assert_ne!(names[&me], "Unknown");
+        assert_ne!(users[&me], "-");
+        assert_eq!((names["-"].as_str(), users["-"].as_str()), ("Unknown", "-"));
+        assert_eq!((names[""].as_str(), users[""].as_str()), ("Unknown", "-"));
+    }
+
+    #[test]
+    fn simple_jitter_is_mean_difference() {
+        assert_eq!(simple_jitter(&alternating()), 10.0);
+        assert_eq!(simple_jitter(&[10.0, 14.0, 11.0]), 3.5);
     }
 }
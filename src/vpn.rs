@@ -1,167 +1,476 @@
+use crate::display;
+use crate::geoip::{self, GeoDb};
+use crate::output::{self, OutputMode, RecordSink};
+use crate::utils::{get_default_interface, run_command, COMMAND_TIMEOUT};
 use colored::Colorize;
+use serde::Serialize;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Number of recent link events kept on screen in watch mode.
+const EVENTS_SHOWN: usize = 10;
+/// Answers with the caller's public IP as plain text.
+const PUBLIC_IP_URL: &str = "https://api.ipify.org";
+const PUBLIC_IP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long watch mode reuses a public IP while the local link is unchanged.
+const PUBLIC_IP_REFRESH: Duration = Duration::from_secs(300);
+/// WireGuard re-handshakes every 2 minutes while traffic flows; a handshake
+/// older than this usually means the peer stopped answering.
+const STALE_HANDSHAKE_SECS: u64 = 180;
+/// Probes per tunnel for the `--detailed` latency check.
+const LATENCY_PROBES: u32 = 3;
+/// Exit status of `vpn watch --until-change` once the connected state flips;
+/// 1 is taken by errors and 2 by usage errors.
+pub const STATE_CHANGED_EXIT: i32 = 3;
+
+/// Why `watch` stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEnd {
+    /// Whoever reads `--output` went away
+    ReaderGone,
+    /// With `--until-change`, the tunnel went down or came up
+    StateChanged,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct VpnInfo {
     connected: bool,
-    interface: Option<String>,
-    server: Option<String>,
-    protocol: Option<String>,
+    /// Every active tunnel; more than one with split-tunnel or multi-hop setups
+    tunnels: Vec<Tunnel>,
     local_ip: Option<String>,
-    vpn_ip: Option<String>,
     dns_servers: Vec<String>,
     connected_since: Option<String>,
+    /// The address traffic leaves the internet from, whether or not a
+    /// tunnel is up
+    public_ip: Option<String>,
+    public_country: Option<String>,
+}
+
+/// One active tunnel interface.
+#[derive(Debug, Serialize)]
+struct Tunnel {
+    interface: String,
+    protocol: String,
+    server: Option<String>,
+    vpn_ip: Option<String>,
     bytes_sent: Option<u64>,
     bytes_received: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    wireguard_peers: Vec<WireguardPeer>,
+    /// Only measured with `--detailed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency: Option<TunnelLatency>,
 }
 
-fn detect_vpn() -> VpnInfo {
-    let mut info = VpnInfo {
-        connected: false,
-        interface: None,
-        server: None,
-        protocol: None,
-        local_ip: None,
-        vpn_ip: None,
-        dns_servers: Vec::new(),
-        connected_since: None,
-        bytes_sent: None,
-        bytes_received: None,
-    };
+/// Round-trip time to the far end of a tunnel.
+#[derive(Debug, Serialize)]
+struct TunnelLatency {
+    /// The gateway on the tunnel interface, or the WireGuard endpoint
+    target: String,
+    /// "icmp", or "tcp" when ICMP went unanswered
+    method: &'static str,
+    avg_ms: Option<f64>,
+    loss_pct: f64,
+}
 
-    // Check for common VPN interfaces
-    let vpn_interfaces = detect_vpn_interfaces();
-    if let Some((iface, proto)) = vpn_interfaces {
-        info.connected = true;
-        info.interface = Some(iface.clone());
-        info.protocol = Some(proto);
+/// A peer of a WireGuard interface, from `wg show <iface> dump`.
+#[derive(Debug, Serialize)]
+struct WireguardPeer {
+    public_key: String,
+    endpoint: Option<String>,
+    allowed_ips: Vec<String>,
+    /// Seconds since the latest handshake, `None` if there hasn't been one
+    handshake_age_secs: Option<u64>,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
 
-        // Get VPN interface IP
-        info.vpn_ip = get_interface_ip(&iface);
+impl WireguardPeer {
+    fn is_stale(&self) -> bool {
+        self.handshake_age_secs
+            .is_none_or(|age| age > STALE_HANDSHAKE_SECS)
+    }
+}
 
-        // Get local IP from primary interface
-        info.local_ip = get_local_ip();
+/// One watch-mode snapshot streamed to `--output` as NDJSON.
+#[derive(Serialize)]
+struct VpnRecord<'a> {
+    timestamp: String,
+    #[serde(flatten)]
+    info: &'a VpnInfo,
+}
 
-        // Get DNS servers
-        info.dns_servers = get_dns_servers();
+/// The link state compared between watch-mode refreshes.
+struct LinkSnapshot {
+    info: VpnInfo,
+    default_route: String,
+}
 
-        // Try to get traffic stats for the interface
-        let (sent, recv) = get_interface_stats(&iface);
-        info.bytes_sent = Some(sent);
-        info.bytes_received = Some(recv);
+/// Describe what changed between two snapshots, one line per transition.
+fn link_events(prev: &LinkSnapshot, cur: &LinkSnapshot) -> Vec<String> {
+    let mut events = Vec::new();
+    let has = |tunnels: &[Tunnel], iface: &str| tunnels.iter().any(|t| t.interface == iface);
+    let down: Vec<&Tunnel> = prev
+        .info
+        .tunnels
+        .iter()
+        .filter(|t| !has(&cur.info.tunnels, &t.interface))
+        .collect();
+    let up: Vec<&Tunnel> = cur
+        .info
+        .tunnels
+        .iter()
+        .filter(|t| !has(&prev.info.tunnels, &t.interface))
+        .collect();
+
+    if let ([from], [to]) = (down.as_slice(), up.as_slice()) {
+        events.push(format!(
+            "tunnel moved from {} to {}",
+            from.interface, to.interface
+        ));
+    } else {
+        for tunnel in down {
+            events.push(format!("{} went DOWN", tunnel.interface));
+        }
+        for tunnel in up {
+            events.push(format!("{} came UP", tunnel.interface));
+        }
+    }
+    for tunnel in &cur.info.tunnels {
+        let before = prev
+            .info
+            .tunnels
+            .iter()
+            .find(|t| t.interface == tunnel.interface);
+        if before.is_some_and(|before| before.vpn_ip != tunnel.vpn_ip) {
+            events.push(format!(
+                "{} IP changed to {}",
+                tunnel.interface,
+                tunnel.vpn_ip.as_deref().unwrap_or("none")
+            ));
+        }
     }
 
-    // Check for WireGuard specifically
-    if !info.connected {
-        if let Some(wg_info) = detect_wireguard() {
-            info.connected = true;
-            info.protocol = Some("WireGuard".to_string());
-            info.server = Some(wg_info.1);
-            info.vpn_ip = get_interface_ip(&wg_info.0);
-            info.interface = Some(wg_info.0);
-            info.local_ip = get_local_ip();
-            info.dns_servers = get_dns_servers();
+    if let (Some(before), Some(after)) = (&prev.info.public_ip, &cur.info.public_ip) {
+        if before != after {
+            events.push(format!("public IP changed to {}", after));
         }
     }
+    // Only across two connected snapshots: a reconnect already reads as UP
+    if prev.info.connected && cur.info.connected && prev.info.local_ip != cur.info.local_ip {
+        events.push(format!(
+            "local IP changed to {}",
+            cur.info.local_ip.as_deref().unwrap_or("none")
+        ));
+    }
+    if prev.default_route != cur.default_route {
+        events.push(format!("default route changed to {}", cur.default_route));
+    }
 
+    events
+}
+
+/// Detect the tunnel and look up the public egress IP alongside it.
+async fn detect_vpn() -> VpnInfo {
+    let mut info = detect_tunnel().await;
+    lookup_public_ip(&mut info).await;
     info
 }
 
-fn detect_vpn_interfaces() -> Option<(String, String)> {
-    // Check ifconfig / ip for VPN-related interfaces
-    let tun_interfaces = ["utun", "tun", "tap", "ppp", "wg", "ipsec", "gif"];
+/// Fill in the public egress IP and its country.
+async fn lookup_public_ip(info: &mut VpnInfo) {
+    info.public_ip = get_public_ip().await;
+    info.public_country = info
+        .public_ip
+        .as_deref()
+        .and_then(|ip| ip.parse().ok())
+        .and_then(public_country);
+}
+
+async fn detect_tunnel() -> VpnInfo {
+    // Interfaces `wg` manages are WireGuard whatever they're named (macOS
+    // wireguard-go uses utun), and may not show up in the interface scan
+    let mut found = detect_vpn_interfaces().await;
+    for iface in wireguard_interfaces().await {
+        match found.iter_mut().find(|(name, _)| *name == iface) {
+            Some(entry) => entry.1 = "WireGuard".to_string(),
+            None => found.push((iface, "WireGuard".to_string())),
+        }
+    }
+    log::debug!("VPN interface scan: {:?}", found);
 
-    // Try ip link on Linux
-    if let Ok(output) = std::process::Command::new("ip")
-        .args(["link", "show"])
-        .output()
-    {
+    let mut tunnels = Vec::new();
+    for (iface, protocol) in found {
+        tunnels.push(tunnel_details(iface, protocol).await);
+    }
+    let connected = !tunnels.is_empty();
+    VpnInfo {
+        connected,
+        tunnels,
+        // Local IP from the primary interface
+        local_ip: if connected {
+            get_local_ip().await
+        } else {
+            None
+        },
+        dns_servers: if connected {
+            get_dns_servers().await
+        } else {
+            Vec::new()
+        },
+        connected_since: None,
+        public_ip: None,
+        public_country: None,
+    }
+}
+
+async fn tunnel_details(interface: String, protocol: String) -> Tunnel {
+    let wireguard_peers = if protocol == "WireGuard" {
+        wireguard_peers(&interface).await
+    } else {
+        Vec::new()
+    };
+    // Try to get traffic stats for the interface
+    let (sent, recv) = get_interface_stats(&interface).await;
+    Tunnel {
+        server: wireguard_peers
+            .iter()
+            .find_map(|peer| peer.endpoint.clone()),
+        vpn_ip: get_interface_ip(&interface).await,
+        bytes_sent: Some(sent),
+        bytes_received: Some(recv),
+        wireguard_peers,
+        latency: None,
+        interface,
+        protocol,
+    }
+}
+
+/// The next hop on `iface`: a `via` route through it, or the remote end of
+/// a point-to-point link (`peer` in `ip addr`, `-->` in `ifconfig`).
+async fn tunnel_gateway(iface: &str) -> Option<String> {
+    if let Ok(output) = run_command("ip", &["route", "show", "dev", iface], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
-        for line in stdout.lines() {
-            for prefix in &tun_interfaces {
-                if line.contains(prefix) && line.contains("UP") {
-                    let name = line
-                        .split(':')
-                        .nth(1)
-                        .unwrap_or("")
-                        .trim()
-                        .split('@')
-                        .next()
-                        .unwrap_or("")
-                        .to_string();
-                    let proto = if name.starts_with("wg") {
-                        "WireGuard"
-                    } else if name.starts_with("tun") || name.starts_with("utun") {
-                        "OpenVPN/IKEv2"
-                    } else if name.starts_with("ppp") {
-                        "PPTP/L2TP"
-                    } else {
-                        "VPN"
-                    };
-                    return Some((name, proto.to_string()));
-                }
-            }
+        let via = stdout.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            parts.find(|&p| p == "via")?;
+            parts.next().map(str::to_string)
+        });
+        if via.is_some() {
+            return via;
+        }
+    }
+    for (program, args, marker) in [
+        ("ip", vec!["addr", "show", iface], "peer"),
+        ("ifconfig", vec![iface], "-->"),
+    ] {
+        let Ok(output) = run_command(program, &args, COMMAND_TIMEOUT).await else {
+            continue;
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let peer = stdout.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            parts.find(|&p| p == marker)?;
+            parts
+                .next()
+                .map(|p| p.split('/').next().unwrap_or(p).to_string())
+        });
+        if peer.is_some() {
+            return peer;
         }
     }
+    None
+}
+
+/// Ping the tunnel's gateway, or its WireGuard endpoint when there's no
+/// gateway, falling back to timing TCP connects to the endpoint's port when
+/// ICMP goes unanswered. `None` when there's nothing to probe.
+async fn measure_latency(tunnel: &Tunnel) -> Option<TunnelLatency> {
+    let endpoint: Option<SocketAddr> = tunnel.server.as_deref().and_then(|s| s.parse().ok());
+    let target = tunnel_gateway(&tunnel.interface)
+        .await
+        .or_else(|| endpoint.map(|e| e.ip().to_string()))?;
+    log::debug!("measuring {} latency via {}", tunnel.interface, target);
+
+    let stats = crate::ping::do_ping(&target, LATENCY_PROBES, false, None).await;
+    let (stats, method, target) = match endpoint {
+        Some(endpoint) if stats.received() == 0 => {
+            let label = endpoint.to_string();
+            let stats = crate::ping::tcp_ping(&label, endpoint, LATENCY_PROBES).await;
+            (stats, "tcp", label)
+        }
+        _ => (stats, "icmp", target),
+    };
+    Some(TunnelLatency {
+        target,
+        method,
+        avg_ms: (stats.received() > 0).then(|| stats.avg()),
+        loss_pct: stats.loss_pct(),
+    })
+}
+
+/// The public IP as seen by `PUBLIC_IP_URL`, or `None` when offline or
+/// the service doesn't answer.
+async fn get_public_ip() -> Option<String> {
+    let client = reqwest::Client::builder()
+        .timeout(PUBLIC_IP_TIMEOUT)
+        .build()
+        .ok()?;
+    let response = client.get(PUBLIC_IP_URL).send().await;
+    let body = match response.and_then(|r| r.error_for_status()) {
+        Ok(response) => response.text().await.ok()?,
+        Err(e) => {
+            log::debug!("public IP lookup failed: {}", e);
+            return None;
+        }
+    };
+    let ip: IpAddr = body.trim().parse().ok()?;
+    Some(ip.to_string())
+}
+
+/// Country of `ip` from the installed GeoLite2 database, if there is one.
+/// The database is opened once and kept for watch mode's refreshes.
+fn public_country(ip: IpAddr) -> Option<String> {
+    static DB: OnceLock<Option<GeoDb>> = OnceLock::new();
+    let db = DB.get_or_init(|| {
+        let path = geoip::default_path()?;
+        GeoDb::open(&path)
+            .map_err(|e| log::debug!("GeoIP database unusable: {}", e))
+            .ok()
+    });
+    let location = db.as_ref()?.lookup(ip)?;
+    location.country.or(location.country_code)
+}
+
+/// Name of the first active VPN tunnel interface, if any.
+pub async fn active_interface() -> Option<String> {
+    detect_vpn_interfaces()
+        .await
+        .into_iter()
+        .map(|(iface, _)| iface)
+        .chain(wireguard_interfaces().await)
+        .next()
+}
 
-    // Try ifconfig on macOS
-    if let Ok(output) = std::process::Command::new("ifconfig").output() {
+/// Prefixes of interface names that VPN clients create.
+const TUNNEL_PREFIXES: [&str; 7] = ["utun", "tun", "tap", "ppp", "wg", "ipsec", "gif"];
+
+/// Guess the VPN protocol from a tunnel interface's name.
+fn guess_protocol(name: &str) -> &'static str {
+    if name.starts_with("wg") {
+        "WireGuard"
+    } else if name.starts_with("tun") || name.starts_with("utun") {
+        "OpenVPN/IKEv2"
+    } else if name.starts_with("ppp") {
+        "PPTP/L2TP"
+    } else {
+        "VPN"
+    }
+}
+
+/// Every tunnel interface that is up, with a guess at its protocol.
+async fn detect_vpn_interfaces() -> Vec<(String, String)> {
+    fn add(found: &mut Vec<(String, String)>, name: &str) {
+        let is_tunnel = TUNNEL_PREFIXES.iter().any(|p| name.starts_with(p));
+        if is_tunnel && !found.iter().any(|(n, _)| n == name) {
+            found.push((name.to_string(), guess_protocol(name).to_string()));
+        }
+    }
+    let mut found = Vec::new();
+
+    // Try ip link on Linux
+    if let Ok(output) = run_command("ip", &["link", "show"], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut current_iface = String::new();
+        // Interface lines look like "5: wg0: <POINTOPOINT,NOARP,UP,LOWER_UP> ..."
         for line in stdout.lines() {
-            if !line.starts_with('\t') && !line.starts_with(' ') && line.contains(':') {
-                current_iface = line.split(':').next().unwrap_or("").to_string();
+            if line.starts_with(' ') || !line.contains("UP") {
+                continue;
             }
-            for prefix in &tun_interfaces {
-                if current_iface.starts_with(prefix) && line.contains("inet ") {
-                    let proto = if current_iface.starts_with("wg") {
-                        "WireGuard"
-                    } else if current_iface.starts_with("utun") || current_iface.starts_with("tun")
-                    {
-                        "OpenVPN/IKEv2"
-                    } else if current_iface.starts_with("ppp") {
-                        "PPTP/L2TP"
-                    } else {
-                        "VPN"
-                    };
-                    return Some((current_iface.clone(), proto.to_string()));
+            let name = line
+                .split(':')
+                .nth(1)
+                .unwrap_or("")
+                .trim()
+                .split('@')
+                .next()
+                .unwrap_or("");
+            add(&mut found, name);
+        }
+    }
+
+    // Try ifconfig on macOS, where `ip` doesn't exist
+    if found.is_empty() {
+        if let Ok(output) = run_command("ifconfig", &[], COMMAND_TIMEOUT).await {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let mut current_iface = String::new();
+            for line in stdout.lines() {
+                if !line.starts_with('\t') && !line.starts_with(' ') && line.contains(':') {
+                    current_iface = line.split(':').next().unwrap_or("").to_string();
+                }
+                if line.contains("inet ") {
+                    add(&mut found, &current_iface);
                 }
             }
         }
     }
 
-    None
+    found
 }
 
-fn detect_wireguard() -> Option<(String, String)> {
-    if let Ok(output) = std::process::Command::new("wg").args(["show"]).output() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if !stdout.is_empty() {
-            let iface = stdout
-                .lines()
-                .next()
-                .and_then(|l| l.split(':').next())
-                .unwrap_or("wg0")
-                .trim()
-                .to_string();
-            let endpoint = stdout
-                .lines()
-                .find(|l| l.contains("endpoint"))
-                .and_then(|l| l.split(':').nth(1))
-                .unwrap_or("unknown")
-                .trim()
-                .to_string();
-            return Some((iface, endpoint));
-        }
+/// Interfaces `wg` knows about, if it's installed.
+async fn wireguard_interfaces() -> Vec<String> {
+    match run_command("wg", &["show", "interfaces"], COMMAND_TIMEOUT).await {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        Err(_) => Vec::new(),
     }
-    None
 }
 
-fn get_interface_ip(iface: &str) -> Option<String> {
+/// Peers of WireGuard interface `iface`. `wg show <iface> dump` prints the
+/// interface's own keys on the first line, then one tab-separated line per
+/// peer: public key, preshared key, endpoint, allowed IPs, latest handshake
+/// (Unix time, 0 for never), rx bytes, tx bytes, keepalive. Empty when `wg`
+/// isn't installed or isn't permitted (it usually needs root).
+async fn wireguard_peers(iface: &str) -> Vec<WireguardPeer> {
+    let Ok(output) = run_command("wg", &["show", iface, "dump"], COMMAND_TIMEOUT).await else {
+        return Vec::new();
+    };
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            let present = |field: &str| (field != "(none)").then(|| field.to_string());
+            let handshake: u64 = fields[4].parse().unwrap_or(0);
+            Some(WireguardPeer {
+                public_key: fields[0].to_string(),
+                endpoint: present(fields[2]),
+                allowed_ips: fields[3]
+                    .split(',')
+                    .filter(|ip| *ip != "(none)")
+                    .map(str::to_string)
+                    .collect(),
+                handshake_age_secs: (handshake > 0).then(|| now.saturating_sub(handshake)),
+                rx_bytes: fields[5].parse().unwrap_or(0),
+                tx_bytes: fields[6].parse().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// IPv4 address assigned to an interface, via `ip addr` or `ifconfig`.
+pub async fn get_interface_ip(iface: &str) -> Option<String> {
     // Linux
-    if let Ok(output) = std::process::Command::new("ip")
-        .args(["addr", "show", iface])
-        .output()
-    {
+    if let Ok(output) = run_command("ip", &["addr", "show", iface], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines() {
             let trimmed = line.trim();
@@ -174,10 +483,7 @@ fn get_interface_ip(iface: &str) -> Option<String> {
         }
     }
     // macOS
-    if let Ok(output) = std::process::Command::new("ifconfig")
-        .arg(iface)
-        .output()
-    {
+    if let Ok(output) = run_command("ifconfig", &[iface], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines() {
             let trimmed = line.trim();
@@ -189,17 +495,14 @@ fn get_interface_ip(iface: &str) -> Option<String> {
     None
 }
 
-fn get_local_ip() -> Option<String> {
+async fn get_local_ip() -> Option<String> {
     // Try to get the primary (non-VPN) IP
-    if let Ok(output) = std::process::Command::new("hostname").args(["-I"]).output() {
+    if let Ok(output) = run_command("hostname", &["-I"], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout);
         return stdout.split_whitespace().next().map(|s| s.to_string());
     }
     // macOS fallback
-    if let Ok(output) = std::process::Command::new("ipconfig")
-        .args(["getifaddr", "en0"])
-        .output()
-    {
+    if let Ok(output) = run_command("ipconfig", &["getifaddr", "en0"], COMMAND_TIMEOUT).await {
         let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
         if !stdout.is_empty() {
             return Some(stdout);
@@ -208,7 +511,7 @@ fn get_local_ip() -> Option<String> {
     None
 }
 
-fn get_dns_servers() -> Vec<String> {
+async fn get_dns_servers() -> Vec<String> {
     let mut servers = Vec::new();
 
     // Read /etc/resolv.conf
@@ -224,10 +527,7 @@ fn get_dns_servers() -> Vec<String> {
 
     // macOS: scutil --dns
     if servers.is_empty() {
-        if let Ok(output) = std::process::Command::new("scutil")
-            .args(["--dns"])
-            .output()
-        {
+        if let Ok(output) = run_command("scutil", &["--dns"], COMMAND_TIMEOUT).await {
             let stdout = String::from_utf8_lossy(&output.stdout);
             for line in stdout.lines() {
                 if line.contains("nameserver[") {
@@ -245,7 +545,7 @@ fn get_dns_servers() -> Vec<String> {
     servers
 }
 
-fn get_interface_stats(iface: &str) -> (u64, u64) {
+async fn get_interface_stats(iface: &str) -> (u64, u64) {
     // Linux: /sys/class/net/<iface>/statistics/
     let tx_path = format!("/sys/class/net/{}/statistics/tx_bytes", iface);
     let rx_path = format!("/sys/class/net/{}/statistics/rx_bytes", iface);
@@ -261,10 +561,7 @@ fn get_interface_stats(iface: &str) -> (u64, u64) {
 
     // macOS: netstat -I <iface> -b
     if sent == 0 && recv == 0 {
-        if let Ok(output) = std::process::Command::new("netstat")
-            .args(["-I", iface, "-b"])
-            .output()
-        {
+        if let Ok(output) = run_command("netstat", &["-I", iface, "-b"], COMMAND_TIMEOUT).await {
             let stdout = String::from_utf8_lossy(&output.stdout);
             if let Some(line) = stdout.lines().nth(1) {
                 let parts: Vec<&str> = line.split_whitespace().collect();
@@ -285,46 +582,155 @@ fn format_bytes(bytes: u64) -> String {
     crate::display::format_bytes(bytes)
 }
 
-fn print_vpn_status(info: &VpnInfo, detailed: bool) {
-    println!();
-    println!("{}", "VPN Status:".bold());
-    println!();
+/// "45s", "3m 12s", or "2h 5m".
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
 
-    if info.connected {
+/// A WireGuard public key shortened for display.
+fn short_key(key: &str) -> String {
+    match key.get(..8) {
+        Some(prefix) => format!("{}…", prefix),
+        None => key.to_string(),
+    }
+}
+
+fn print_latency(latency: &TunnelLatency) {
+    let via = if latency.method == "tcp" {
+        format!("{}, TCP connect", latency.target)
+    } else {
+        latency.target.clone()
+    };
+    match latency.avg_ms {
+        Some(avg) => {
+            let mut line = format!("{:.1} ms", avg).cyan().to_string();
+            if latency.loss_pct > 0.0 {
+                line = format!("{}, {:.0}% loss", line, latency.loss_pct);
+            }
+            println!("  Latency:       {} ({})", line, via.dimmed());
+        }
+        None => println!(
+            "  Latency:       {} ({})",
+            "no response".yellow(),
+            via.dimmed()
+        ),
+    }
+}
+
+fn print_wireguard_peers(peers: &[WireguardPeer]) {
+    for peer in peers {
+        println!();
         println!(
-            "State:           {} Connected",
-            "OK".green()
+            "{} {}:",
+            "WireGuard Peer".bold(),
+            short_key(&peer.public_key)
         );
-        if let Some(ref server) = info.server {
-            println!("Server:          {}", server);
+        if let Some(ref endpoint) = peer.endpoint {
+            println!("  Endpoint:      {}", endpoint);
         }
-        if let Some(ref proto) = info.protocol {
-            println!("Protocol:        {}", proto);
+        if !peer.allowed_ips.is_empty() {
+            println!("  Allowed IPs:   {}", peer.allowed_ips.join(", "));
         }
-        if let Some(ref iface) = info.interface {
-            println!("Interface:       {}", iface);
+        let handshake = match peer.handshake_age_secs {
+            Some(age) => format!("{} ago", format_age(age)),
+            None => "never".to_string(),
+        };
+        if peer.is_stale() {
+            println!(
+                "  Handshake:     {} {}",
+                handshake.yellow(),
+                "(stale: the tunnel is probably dead)".yellow()
+            );
+        } else {
+            println!("  Handshake:     {}", handshake.green());
         }
+        println!(
+            "  Transfer:      {} received, {} sent",
+            format_bytes(peer.rx_bytes),
+            format_bytes(peer.tx_bytes)
+        );
+    }
+}
+
+fn print_public_ip(info: &VpnInfo) {
+    let ip = info.public_ip.as_deref().unwrap_or("unknown");
+    match info.public_country {
+        Some(ref country) => println!("Public IP:       {} ({})", ip.cyan(), country),
+        None => println!("Public IP:       {}", ip.cyan()),
+    }
+}
+
+fn print_vpn_status(info: &VpnInfo, detailed: bool) {
+    println!();
+    println!("{}", "VPN Status:".bold());
+    println!();
+
+    if info.connected {
+        let count = match info.tunnels.len() {
+            1 => String::new(),
+            n => format!(" ({} tunnels)", n),
+        };
+        println!("State:           {} Connected{}", "OK".green(), count);
 
         let local = info.local_ip.as_deref().unwrap_or("unknown");
-        let vpn = info.vpn_ip.as_deref().unwrap_or("unknown");
-        println!("IP Address:      {} -> {}", local.dimmed(), vpn.cyan());
+        for (i, tunnel) in info.tunnels.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            if let Some(ref server) = tunnel.server {
+                println!("Server:          {}", server);
+            }
+            println!("Protocol:        {}", tunnel.protocol);
+            println!("Interface:       {}", tunnel.interface);
+            let vpn = tunnel.vpn_ip.as_deref().unwrap_or("unknown");
+            println!("IP Address:      {} -> {}", local.dimmed(), vpn.cyan());
+        }
+        if info.tunnels.len() > 1 {
+            println!();
+        }
 
         if !info.dns_servers.is_empty() {
             println!("DNS Servers:     {}", info.dns_servers.join(", "));
         }
 
+        print_public_ip(info);
+
         if let Some(ref since) = info.connected_since {
             println!("Connected:       {}", since);
         }
 
-        if detailed {
-            println!();
-            println!("{}:", "Tunnel Stats".bold());
-            if let Some(sent) = info.bytes_sent {
-                println!("  Data Sent:     {}", format_bytes(sent));
-            }
-            if let Some(recv) = info.bytes_received {
-                println!("  Data Received: {}", format_bytes(recv));
+        for tunnel in &info.tunnels {
+            if detailed {
+                println!();
+                if info.tunnels.len() > 1 {
+                    println!("{} ({}):", "Tunnel Stats".bold(), tunnel.interface);
+                } else {
+                    println!("{}:", "Tunnel Stats".bold());
+                }
+                if let Some(sent) = tunnel.bytes_sent {
+                    println!("  Data Sent:     {}", format_bytes(sent));
+                }
+                if let Some(recv) = tunnel.bytes_received {
+                    println!("  Data Received: {}", format_bytes(recv));
+                }
+                if let Some(ref latency) = tunnel.latency {
+                    print_latency(latency);
+                }
+                print_wireguard_peers(&tunnel.wireguard_peers);
+            } else if !tunnel.wireguard_peers.is_empty()
+                && tunnel.wireguard_peers.iter().all(WireguardPeer::is_stale)
+            {
+                println!();
+                println!(
+                    "{} no recent WireGuard handshake on {}; the tunnel is probably dead \
+                     (see --detailed)",
+                    "Warning:".yellow(),
+                    tunnel.interface
+                );
             }
         }
     } else {
@@ -332,6 +738,7 @@ fn print_vpn_status(info: &VpnInfo, detailed: bool) {
             "State:           {} Not Connected",
             "--".red()
         );
+        print_public_ip(info);
         println!();
         println!("{}", "No active VPN tunnel detected.".dimmed());
         println!(
@@ -343,18 +750,133 @@ fn print_vpn_status(info: &VpnInfo, detailed: bool) {
     println!();
 }
 
-pub async fn status(detailed: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let info = detect_vpn();
-    print_vpn_status(&info, detailed);
+pub async fn status(detailed: bool, mode: OutputMode) -> Result<(), Box<dyn std::error::Error>> {
+    let mut info = detect_vpn().await;
+    // Takes a few seconds per tunnel, so watch mode's refreshes skip it
+    if detailed {
+        for tunnel in &mut info.tunnels {
+            tunnel.latency = measure_latency(tunnel).await;
+        }
+    }
+    if mode.is_json() {
+        println!("{}", output::to_json(&info)?);
+    } else {
+        print_vpn_status(&info, detailed);
+    }
     Ok(())
 }
 
-pub async fn watch() -> Result<(), Box<dyn std::error::Error>> {
+/// Redraw the VPN status every `interval` seconds, streaming snapshots to
+/// `output_path` and link events to `log_path`. With `until_change`, returns
+/// `WatchEnd::StateChanged` as soon as the tunnel goes down (or comes up, if
+/// none was up at the start); main exits with `STATE_CHANGED_EXIT` then.
+pub async fn watch(
+    output_path: Option<String>,
+    log_path: Option<String>,
+    interval: u64,
+    until_change: bool,
+) -> Result<WatchEnd, Box<dyn std::error::Error>> {
+    if interval == 0 {
+        return Err("--interval must be at least 1 second".into());
+    }
+    let mut sink = output_path.as_deref().map(RecordSink::open).transpose()?;
+    let mut log = match log_path {
+        Some(ref path) => Some(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        None => None,
+    };
+    let mut prev: Option<LinkSnapshot> = None;
+    let mut public_checked: Option<std::time::Instant> = None;
+    let mut initially_connected = None;
+    let mut events: Vec<String> = Vec::new();
+
+    let quiet = output::is_stdout(output_path.as_deref());
+
     loop {
-        print!("\x1B[2J\x1B[H");
-        let info = detect_vpn();
-        print_vpn_status(&info, true);
-        println!("{}", "Refreshing every 5s... (Ctrl+C to stop)".dimmed());
-        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        let mut snapshot = LinkSnapshot {
+            info: detect_tunnel().await,
+            default_route: get_default_interface().await,
+        };
+        // The public IP takes a request to an outside service, so it is only
+        // looked up again once the local link changes or the last one is old
+        let fresh = public_checked.is_some_and(|at| at.elapsed() < PUBLIC_IP_REFRESH);
+        let recheck = match prev {
+            Some(ref prev) if fresh => {
+                snapshot.info.public_ip = prev.info.public_ip.clone();
+                snapshot.info.public_country = prev.info.public_country.clone();
+                !link_events(prev, &snapshot).is_empty()
+                    || prev.info.local_ip != snapshot.info.local_ip
+            }
+            _ => true,
+        };
+        if recheck {
+            lookup_public_ip(&mut snapshot.info).await;
+            public_checked = Some(std::time::Instant::now());
+        }
+
+        if let Some(ref mut sink) = sink {
+            let record = VpnRecord {
+                timestamp: output::timestamp(),
+                info: &snapshot.info,
+            };
+            if !sink.write(&record)? {
+                eprintln!("{}", "Output reader disconnected, stopping.".dimmed());
+                return Ok(WatchEnd::ReaderGone);
+            }
+        }
+
+        if let Some(ref prev) = prev {
+            let now = chrono::Local::now();
+            for event in link_events(prev, &snapshot) {
+                if let Some(ref mut log) = log {
+                    writeln!(log, "{} {}", now.format("%Y-%m-%d %H:%M:%S"), event)?;
+                }
+                events.push(format!("{} {}", now.format("%H:%M:%S"), event));
+            }
+        }
+
+        // With `--output -` stdout carries only the records
+        if !quiet {
+            display::begin_frame();
+            print_vpn_status(&snapshot.info, true);
+
+            if !events.is_empty() {
+                println!("{}:", "Link Events".bold());
+                let skip = events.len().saturating_sub(EVENTS_SHOWN);
+                for event in &events[skip..] {
+                    println!("  {}", event.yellow());
+                }
+                println!();
+            }
+
+            let until = if until_change {
+                " until the VPN state changes"
+            } else {
+                ""
+            };
+            println!(
+                "{}",
+                format!(
+                    "Refreshing every {}s{}... (Ctrl+C to stop)",
+                    interval, until
+                )
+                .dimmed()
+            );
+            display::end_frame();
+        }
+
+        let connected = snapshot.info.connected;
+        if until_change && *initially_connected.get_or_insert(connected) != connected {
+            let state = if connected { "came up" } else { "went down" };
+            eprintln!("{}", format!("VPN {}, exiting.", state).yellow());
+            return Ok(WatchEnd::StateChanged);
+        }
+        prev = Some(snapshot);
+
+        tokio::time::sleep(Duration::from_secs(interval)).await;
     }
 }
@@ -0,0 +1,336 @@
+use crate::output::OutputMode;
+use crate::utils::{run_command, COMMAND_TIMEOUT};
+use colored::Colorize;
+use serde::Serialize;
+
+const AIRPORT_PATH: &str =
+    "/System/Library/PrivateFrameworks/Apple80211.framework/Versions/Current/Resources/airport";
+
+#[derive(Debug, Default, Serialize)]
+struct WifiInfo {
+    connected: bool,
+    interface: Option<String>,
+    ssid: Option<String>,
+    bssid: Option<String>,
+    channel: Option<u32>,
+    band: Option<String>,
+    signal_dbm: Option<i32>,
+    noise_dbm: Option<i32>,
+    link_rate_mbps: Option<f64>,
+}
+
+/// Map a center frequency in MHz to its band.
+fn band_for_freq(freq_mhz: u32) -> &'static str {
+    match freq_mhz {
+        0..=2500 => "2.4 GHz",
+        2501..=5940 => "5 GHz",
+        _ => "6 GHz",
+    }
+}
+
+/// Infer the band from a channel number when the tool doesn't report it.
+fn band_for_channel(channel: u32) -> &'static str {
+    if channel <= 14 {
+        "2.4 GHz"
+    } else {
+        "5 GHz"
+    }
+}
+
+/// Convert a center frequency in MHz to a channel number.
+fn channel_for_freq(freq_mhz: u32) -> Option<u32> {
+    match freq_mhz {
+        2412..=2472 => Some((freq_mhz - 2407) / 5),
+        2484 => Some(14),
+        5160..=5885 => Some((freq_mhz - 5000) / 5),
+        5955..=7115 => Some((freq_mhz - 5950) / 5),
+        _ => None,
+    }
+}
+
+/// Parse the leading integer of a value like "-55 dBm" or "149,80".
+fn leading_int(value: &str) -> Option<i32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .enumerate()
+        .take_while(|(i, c)| c.is_ascii_digit() || (*i == 0 && *c == '-'))
+        .map(|(_, c)| c)
+        .collect();
+    digits.parse().ok()
+}
+
+/// macOS: parse `airport -I`, falling back to `wdutil info` on newer systems.
+async fn detect_macos() -> Option<WifiInfo> {
+    if let Ok(output) = run_command(AIRPORT_PATH, &["-I"], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut info = WifiInfo::default();
+        for line in stdout.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "SSID" => info.ssid = Some(value.to_string()),
+                // BSSID contains colons, so keep everything after the key
+                "BSSID" => {
+                    info.bssid = line.split_once("BSSID:").map(|(_, b)| b.trim().to_string())
+                }
+                "agrCtlRSSI" => info.signal_dbm = leading_int(value),
+                "agrCtlNoise" => info.noise_dbm = leading_int(value),
+                "lastTxRate" => info.link_rate_mbps = value.parse().ok(),
+                "channel" => info.channel = leading_int(value).map(|c| c as u32),
+                _ => {}
+            }
+        }
+        if info.ssid.is_some() {
+            info.connected = true;
+            info.interface = Some("en0".to_string());
+            return Some(info);
+        }
+    }
+
+    if let Ok(output) = run_command("wdutil", &["info"], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut info = WifiInfo::default();
+        // Only the first WIFI section is relevant; stop at the next section
+        let mut in_wifi = false;
+        for line in stdout.lines() {
+            let trimmed = line.trim();
+            if trimmed == "WIFI" {
+                in_wifi = true;
+                continue;
+            }
+            let is_section_header =
+                !trimmed.is_empty() && trimmed.chars().all(|c| c.is_ascii_uppercase() || c == ' ');
+            if in_wifi && is_section_header {
+                break;
+            }
+            if !in_wifi {
+                continue;
+            }
+            let Some((key, value)) = trimmed.split_once(" : ") else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "Interface Name" => info.interface = Some(value.to_string()),
+                "SSID" => info.ssid = Some(value.to_string()),
+                "BSSID" => info.bssid = Some(value.to_string()),
+                "RSSI" => info.signal_dbm = leading_int(value),
+                "Noise" => info.noise_dbm = leading_int(value),
+                "Tx Rate" => {
+                    info.link_rate_mbps =
+                        value.split_whitespace().next().and_then(|v| v.parse().ok())
+                }
+                // e.g. "5g149/80" or "2g6/20"
+                "Channel" => {
+                    if let Some((band, chan)) = value.split_once('g') {
+                        info.band = match band {
+                            "2" => Some("2.4 GHz".to_string()),
+                            "5" | "6" => Some(format!("{} GHz", band)),
+                            _ => None,
+                        };
+                        info.channel = leading_int(chan).map(|c| c as u32);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let has_ssid = info
+            .ssid
+            .as_deref()
+            .is_some_and(|s| !s.is_empty() && s != "None");
+        if has_ssid {
+            info.connected = true;
+            return Some(info);
+        }
+    }
+
+    None
+}
+
+/// Linux: parse `iw dev <iface> link`, falling back to `nmcli`.
+async fn detect_linux() -> Option<WifiInfo> {
+    if let Ok(output) = run_command("iw", &["dev"], COMMAND_TIMEOUT).await {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let interfaces: Vec<String> = stdout
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("Interface "))
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        for iface in interfaces {
+            let Ok(link) = run_command("iw", &["dev", &iface, "link"], COMMAND_TIMEOUT).await
+            else {
+                continue;
+            };
+            let link = String::from_utf8_lossy(&link.stdout);
+            let mut info = WifiInfo {
+                interface: Some(iface.clone()),
+                ..Default::default()
+            };
+            for line in link.lines() {
+                let trimmed = line.trim();
+                if let Some(rest) = trimmed.strip_prefix("Connected to ") {
+                    info.connected = true;
+                    info.bssid = rest.split_whitespace().next().map(|s| s.to_string());
+                } else if let Some(ssid) = trimmed.strip_prefix("SSID: ") {
+                    info.ssid = Some(ssid.to_string());
+                } else if let Some(freq) = trimmed.strip_prefix("freq: ") {
+                    if let Some(freq) = freq.split('.').next().and_then(|f| f.parse::<u32>().ok()) {
+                        info.channel = channel_for_freq(freq);
+                        info.band = Some(band_for_freq(freq).to_string());
+                    }
+                } else if let Some(signal) = trimmed.strip_prefix("signal: ") {
+                    info.signal_dbm = leading_int(signal);
+                } else if let Some(rate) = trimmed.strip_prefix("tx bitrate: ") {
+                    info.link_rate_mbps =
+                        rate.split_whitespace().next().and_then(|r| r.parse().ok());
+                }
+            }
+            if info.connected {
+                return Some(info);
+            }
+        }
+    }
+
+    // nmcli escapes colons inside fields as "\:"
+    if let Ok(output) = run_command(
+        "nmcli",
+        &[
+            "-t",
+            "-f",
+            "active,ssid,bssid,chan,signal,rate,device",
+            "dev",
+            "wifi",
+        ],
+        COMMAND_TIMEOUT,
+    )
+    .await
+    {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for line in stdout.lines() {
+            let unescaped = line.replace("\\:", "\u{0}");
+            let fields: Vec<String> = unescaped
+                .split(':')
+                .map(|f| f.replace('\u{0}', ":"))
+                .collect();
+            if fields.len() < 7 || fields[0] != "yes" {
+                continue;
+            }
+            // nmcli reports signal as a 0-100 quality; approximate dBm from it
+            let quality: Option<i32> = fields[4].parse().ok();
+            return Some(WifiInfo {
+                connected: true,
+                ssid: Some(fields[1].clone()),
+                bssid: Some(fields[2].clone()),
+                channel: fields[3].parse().ok(),
+                signal_dbm: quality.map(|q| q / 2 - 100),
+                link_rate_mbps: fields[5]
+                    .split_whitespace()
+                    .next()
+                    .and_then(|r| r.parse().ok()),
+                interface: Some(fields[6].clone()),
+                ..Default::default()
+            });
+        }
+    }
+
+    None
+}
+
+async fn detect_wifi() -> WifiInfo {
+    let detected = match detect_macos().await {
+        Some(info) => Some(info),
+        None => detect_linux().await,
+    };
+    let mut info = detected.unwrap_or_default();
+    log::trace!("detected Wi-Fi: {:?}", info);
+    if info.band.is_none() {
+        info.band = info.channel.map(|c| band_for_channel(c).to_string());
+    }
+    info
+}
+
+/// Assess signal strength in dBm.
+fn signal_assessment(dbm: i32) -> String {
+    if dbm >= -50 {
+        "Excellent".green().to_string()
+    } else if dbm >= -60 {
+        "Good".green().to_string()
+    } else if dbm >= -70 {
+        "Fair".yellow().to_string()
+    } else {
+        "Weak".red().to_string()
+    }
+}
+
+fn print_wifi_status(info: &WifiInfo) {
+    println!();
+    println!("{}", "Wi-Fi Status:".bold());
+    println!();
+
+    if !info.connected {
+        println!("State:           {} Not Connected", "--".red());
+        println!();
+        println!("{}", "No active Wi-Fi connection detected.".dimmed());
+        println!(
+            "{}",
+            "Checked: airport/wdutil (macOS), iw/nmcli (Linux)".dimmed()
+        );
+        println!();
+        return;
+    }
+
+    println!("State:           {} Connected", "OK".green());
+    if let Some(ref ssid) = info.ssid {
+        println!("SSID:            {}", ssid.cyan());
+    }
+    if let Some(ref bssid) = info.bssid {
+        println!("BSSID:           {}", bssid);
+    }
+    if let Some(channel) = info.channel {
+        let band = info.band.as_deref().unwrap_or("");
+        println!("Channel:         {} ({})", channel, band);
+    }
+    if let Some(signal) = info.signal_dbm {
+        let quality = signal_assessment(signal);
+        println!("Signal:          {} dBm ({})", signal, quality);
+    }
+    if let Some(noise) = info.noise_dbm {
+        match info.signal_dbm {
+            Some(signal) => {
+                println!("Noise:           {} dBm (SNR {} dB)", noise, signal - noise)
+            }
+            None => println!("Noise:           {} dBm", noise),
+        }
+    }
+    if let Some(rate) = info.link_rate_mbps {
+        println!("Link Rate:       {:.0} Mbps", rate);
+    }
+    if let Some(ref iface) = info.interface {
+        println!("Interface:       {}", iface);
+    }
+
+    if info.signal_dbm.is_some_and(|s| s < -70) {
+        println!();
+        println!(
+            "{}",
+            "Weak signal: expect lower speeds and higher latency. Move closer to the router."
+                .yellow()
+        );
+    }
+
+    println!();
+}
+
+pub async fn run(mode: OutputMode) -> Result<(), Box<dyn std::error::Error>> {
+    let info = detect_wifi().await;
+    if mode.is_json() {
+        println!("{}", crate::output::to_json(&info)?);
+    } else {
+        print_wifi_status(&info);
+    }
+    Ok(())
+}